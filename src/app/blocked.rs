@@ -0,0 +1,157 @@
+use std::collections::{BTreeSet, HashMap, VecDeque};
+
+use bytes::Bytes;
+use tokio::time::Instant;
+
+use crate::{
+    command::{BlockOn, Command},
+    db::Db,
+    resp::RESP,
+};
+
+use super::ResponseSender;
+
+/// The clients parked by the Database Task because their blocking command couldn't be
+/// served yet. A client is retried whenever one of the keys it waits on is written to,
+/// the clients waiting on the same key being retried in the order they blocked.
+#[derive(Debug, Default)]
+pub(super) struct BlockedClients {
+    clients: HashMap<u64, BlockedClient>,
+    /// The clients waiting on each key, in the order they blocked.
+    queues: HashMap<Bytes, VecDeque<u64>>,
+    /// The clients that time out, soonest first.
+    deadlines: BTreeSet<(Instant, u64)>,
+    next_id: u64,
+}
+
+#[derive(Debug)]
+struct BlockedClient {
+    command: Command,
+    keys: Vec<Bytes>,
+    deadline: Option<Instant>,
+    response_sender: ResponseSender,
+}
+
+impl BlockedClients {
+    /// Parks `command` until it can be served or times out.
+    pub fn block(
+        &mut self,
+        db: &mut Db,
+        command: Command,
+        block_on: BlockOn,
+        response_sender: ResponseSender,
+    ) {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        // A deadline too far away to be represented never comes.
+        let deadline = block_on
+            .timeout
+            .and_then(|timeout| Instant::now().checked_add(timeout));
+        if let Some(deadline) = deadline {
+            self.deadlines.insert((deadline, id));
+        }
+        for key in &block_on.keys {
+            db.add_blocked_key(key.clone());
+            let queue = self.queues.entry(key.clone()).or_default();
+            // A key given more than once only counts once.
+            if queue.back() != Some(&id) {
+                queue.push_back(id);
+            }
+        }
+        let client = BlockedClient {
+            command,
+            keys: block_on.keys,
+            deadline,
+            response_sender,
+        };
+        self.clients.insert(id, client);
+    }
+
+    /// Retries the clients waiting on the keys written to since the last call. Serving a
+    /// client can write to other keys, so this goes on until no key is left to look at.
+    pub async fn serve(&mut self, db: &mut Db) {
+        loop {
+            let ready_keys = db.take_ready_keys();
+            if ready_keys.is_empty() {
+                break;
+            }
+            for key in ready_keys {
+                self.serve_key(db, &key).await;
+            }
+        }
+    }
+
+    /// When the next client times out.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.deadlines.first().map(|(deadline, _)| *deadline)
+    }
+
+    /// Replies a null array to the clients whose deadline has passed, like Redis does
+    /// for every blocking command.
+    pub fn time_out(&mut self, db: &mut Db, now: Instant) {
+        while let Some(&(deadline, id)) = self.deadlines.first() {
+            if deadline > now {
+                break;
+            }
+            if let Some(client) = self.unblock(db, id) {
+                let _ = client.response_sender.send(Ok(RESP::NullArray));
+            }
+        }
+    }
+
+    /// Forgets the clients that disconnected while blocked.
+    pub fn remove_disconnected(&mut self, db: &mut Db) {
+        let disconnected: Vec<u64> = self
+            .clients
+            .iter()
+            .filter(|(_, client)| client.response_sender.is_closed())
+            .map(|(id, _)| *id)
+            .collect();
+        for id in disconnected {
+            self.unblock(db, id);
+        }
+    }
+
+    async fn serve_key(&mut self, db: &mut Db, key: &[u8]) {
+        let queue: Vec<u64> = match self.queues.get(key) {
+            Some(queue) => queue.iter().copied().collect(),
+            None => return,
+        };
+        for id in queue {
+            let client = match self.clients.get(&id) {
+                Some(client) => client,
+                None => continue,
+            };
+            // Serving a client that's gone would lose whatever its command pops.
+            if client.response_sender.is_closed() {
+                self.unblock(db, id);
+                continue;
+            }
+            // The client stays blocked when there's still nothing for it, or if the key
+            // now holds another type.
+            if let Ok(Some(resp)) = client.command.clone().try_execute(db).await {
+                if let Some(client) = self.unblock(db, id) {
+                    let _ = client.response_sender.send(Ok(resp));
+                }
+            }
+        }
+    }
+
+    fn unblock(&mut self, db: &mut Db, id: u64) -> Option<BlockedClient> {
+        let client = self.clients.remove(&id)?;
+        if let Some(deadline) = client.deadline {
+            self.deadlines.remove(&(deadline, id));
+        }
+        for key in &client.keys {
+            if let Some(queue) = self.queues.get_mut(key) {
+                queue.retain(|&queued| queued != id);
+                if queue.is_empty() {
+                    self.queues.remove(key);
+                    db.remove_blocked_key(key);
+                }
+            }
+        }
+        Some(client)
+    }
+}
@@ -1,12 +1,17 @@
 use tokio::{
     net::TcpListener,
     sync::{mpsc, oneshot},
+    time::{self, Instant, MissedTickBehavior},
 };
 
+mod blocked;
+
+use blocked::BlockedClients;
+
 use crate::{
     command::Command,
     connection::Connection,
-    db::Db,
+    db::{Db, ACTIVE_EXPIRE_INTERVAL},
     error::{Error, Result},
     resp::RESP,
 };
@@ -48,13 +53,55 @@ impl App {
         // Spawn the Database Task
         // Listens for incoming `DbRequest`s from the `db_request_receiver`,
         // executes them and send the result back to the task that sent the `DbRequest`.
+        // Blocking commands that can't be served right away are parked until a write
+        // to one of their keys lets them through or they time out.
+        // Expired keys that are never read again are removed in the background
+        // between requests.
         tokio::spawn(async move {
             let mut db = Db::new();
-
-            while let Some(db_request) = db_request_receiver.recv().await {
-                let result = db_request.command.execute_cmd(&mut db).await;
-                // Send the
-                db_request.response_sender.send(Ok(result)).unwrap();
+            let mut blocked = BlockedClients::default();
+            let mut active_expire_timer = time::interval(ACTIVE_EXPIRE_INTERVAL);
+            active_expire_timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+            loop {
+                let next_deadline = blocked.next_deadline();
+                tokio::select! {
+                    db_request = db_request_receiver.recv() => {
+                        let db_request = match db_request {
+                            Some(db_request) => db_request,
+                            None => break,
+                        };
+                        let DbRequest { mut command, response_sender } = db_request;
+                        match command.block_on() {
+                            Some(block_on) => {
+                                command.pin(&mut db);
+                                match command.clone().try_execute(&mut db).await {
+                                    Ok(Some(resp)) => {
+                                        let _ = response_sender.send(Ok(resp));
+                                    }
+                                    Ok(None) => blocked.block(&mut db, command, block_on, response_sender),
+                                    Err(error) => {
+                                        let _ = response_sender.send(Err(error));
+                                    }
+                                }
+                            }
+                            None => {
+                                let result = command.execute_cmd(&mut db).await;
+                                // Send the result back, the client may have disconnected in the meantime
+                                // which isn't the Database Task's problem.
+                                let _ = response_sender.send(result);
+                            }
+                        }
+                        blocked.serve(&mut db).await;
+                    }
+                    _ = time::sleep_until(next_deadline.unwrap_or_else(Instant::now)), if next_deadline.is_some() => {
+                        blocked.time_out(&mut db, Instant::now());
+                    }
+                    _ = active_expire_timer.tick() => {
+                        db.active_expire_cycle();
+                        blocked.remove_disconnected(&mut db);
+                    }
+                }
             }
         });
 
@@ -89,10 +136,24 @@ impl App {
                     return Err(e);
                 }
             };
-            let command = Command::try_from(raw_command)?;
-            println!("COMMAND: {command:?}");
-
-            let db_response: RESP = Self::handle_command(command, db_request_sender.clone()).await;
+            // A command that fails to parse is reported back to the client,
+            // the connection stays open for the next command.
+            let db_response: RESP = match Command::try_from(raw_command) {
+                Ok(command) => {
+                    println!("COMMAND: {command:?}");
+                    if command.block_on().is_some() {
+                        // A blocked client may disconnect before it's served, dropping the
+                        // pending request lets the Database Task know not to serve it.
+                        tokio::select! {
+                            db_response = Self::handle_command(command, db_request_sender.clone()) => db_response,
+                            _ = connection.closed() => break 'listen,
+                        }
+                    } else {
+                        Self::handle_command(command, db_request_sender.clone()).await
+                    }
+                }
+                Err(parse_error) => parse_error.into(),
+            };
             connection.write_frame(&db_response).await?;
         }
         Ok(())
@@ -118,9 +179,7 @@ impl App {
 
         let resp = match response_receiver.await {
             Ok(Ok(request_result)) => request_result,
-            Ok(Err(execution_error)) => RESP::Error(format!(
-                "Error: {execution_error:?}" // ! Implement debug for error
-            )),
+            Ok(Err(execution_error)) => execution_error.into(),
             Err(receiver_error) => {
                 RESP::Error(format!("Error receiving results: {receiver_error}"))
             }
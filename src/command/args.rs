@@ -0,0 +1,179 @@
+use std::{time::Duration, vec};
+
+use bytes::Bytes;
+
+use crate::{
+    error::{Error, Result},
+    resp::RESP,
+    utils::{parse_float, parse_integer},
+};
+
+/// The most elements a random sample that may repeat holds, its reply being built
+/// whole.
+const MAX_SAMPLE_LEN: u64 = 1 << 20;
+
+/// Cursor over the arguments of a command, the command name excluded.
+pub(crate) struct Args {
+    /// The command name in lowercase.
+    name: String,
+    /// The command name as sent by the client.
+    raw_name: String,
+    args: vec::IntoIter<RESP>,
+}
+
+impl Args {
+    pub fn new(frame: RESP) -> Result<Args> {
+        let mut args = if let RESP::Array(args) = frame {
+            args.into_iter()
+        } else {
+            return Err(Error::Msg("Commands should be an array".to_string()));
+        };
+        let raw_name = match args.next() {
+            Some(name) => extract_string(&name)?,
+            None => return Err(Error::Msg("Command array is empty".to_string())),
+        };
+
+        Ok(Args {
+            name: raw_name.to_lowercase(),
+            raw_name,
+            args,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The error for a command name that no command family recognised.
+    pub fn unknown_command(self) -> Error {
+        Error::UnknownCommand(self.raw_name)
+    }
+
+    /// Number of arguments left to be consumed.
+    pub fn remaining(&self) -> usize {
+        self.args.len()
+    }
+
+    /// Checks the number of arguments following Redis' arity convention, the
+    /// command name is counted and a negative `arity` means "at least `-arity`".
+    pub fn check_arity(&self, arity: i32) -> Result<()> {
+        let argc = self.args.len() as i32 + 1;
+        let is_valid = if arity < 0 {
+            argc >= -arity
+        } else {
+            argc == arity
+        };
+        if is_valid {
+            Ok(())
+        } else {
+            Err(Error::WrongArity(self.name.clone()))
+        }
+    }
+
+    pub fn next_resp(&mut self) -> Result<RESP> {
+        self.args.next().ok_or(Error::Syntax)
+    }
+
+    pub fn next_bytes(&mut self) -> Result<Bytes> {
+        extract_string_as_bytes(&self.next_resp()?)
+    }
+
+    pub fn next_string(&mut self) -> Result<String> {
+        extract_string(&self.next_resp()?)
+    }
+
+    /// Keys are binary safe so they're read as is.
+    pub fn next_key(&mut self) -> Result<Bytes> {
+        self.next_bytes()
+    }
+
+    /// Reads an option name, uppercased so it can be matched case-insensitively.
+    pub fn next_token(&mut self) -> Result<String> {
+        Ok(self.next_string()?.to_uppercase())
+    }
+
+    /// The next argument in uppercase without consuming it, `None` if there's none or
+    /// it isn't a string.
+    pub fn peek_token(&self) -> Option<String> {
+        let next = self.args.as_slice().first()?;
+        Some(extract_string(next).ok()?.to_uppercase())
+    }
+
+    pub fn next_integer(&mut self) -> Result<i64> {
+        parse_integer(self.next_string()?.as_bytes()).ok_or(Error::NotAnInteger)
+    }
+
+    pub fn next_float(&mut self) -> Result<f64> {
+        parse_float(self.next_string()?.as_bytes()).ok_or(Error::NotAFloat)
+    }
+
+    /// Reads a count that can't be negative.
+    pub fn next_count(&mut self) -> Result<usize> {
+        usize::try_from(self.next_integer()?)
+            .map_err(|_| Error::OutOfRange("value is out of range, must be positive".to_string()))
+    }
+
+    /// Reads the count of a random sample, positive for distinct elements and negative
+    /// for elements that may repeat, up to `MAX_SAMPLE_LEN` of them.
+    pub fn next_sample_count(&mut self) -> Result<i64> {
+        let count = self.next_integer()?;
+        if count < 0 && count.unsigned_abs() > MAX_SAMPLE_LEN {
+            return Err(Error::OutOfRange("value is out of range".to_string()));
+        }
+        Ok(count)
+    }
+
+    /// Reads the timeout of a blocking command in seconds, fractions of a second
+    /// included. A zero timeout means waiting forever.
+    pub fn next_timeout(&mut self) -> Result<Duration> {
+        let timeout = parse_float(self.next_string()?.as_bytes())
+            .filter(|timeout| timeout.is_finite())
+            .ok_or_else(|| Error::Msg("timeout is not a float or out of range".to_string()))?;
+        if timeout < 0.0 {
+            return Err(Error::Msg("timeout is negative".to_string()));
+        }
+        let out_of_range = || Error::OutOfRange("timeout is out of range".to_string());
+        // Redis' bound, the timeout in milliseconds must fit a signed 64 bit integer.
+        if timeout * 1000.0 > i64::MAX as f64 {
+            return Err(out_of_range());
+        }
+        Duration::try_from_secs_f64(timeout).map_err(|_| out_of_range())
+    }
+
+    /// Consumes all the remaining arguments as values.
+    pub fn rest_bytes(&mut self) -> Result<Vec<Bytes>> {
+        let mut values = Vec::with_capacity(self.remaining());
+        while self.remaining() > 0 {
+            values.push(self.next_bytes()?);
+        }
+        Ok(values)
+    }
+
+    /// Consumes all the remaining arguments as keys.
+    pub fn rest_keys(&mut self) -> Result<Vec<Bytes>> {
+        let mut keys = Vec::with_capacity(self.remaining());
+        while self.remaining() > 0 {
+            keys.push(self.next_key()?);
+        }
+        Ok(keys)
+    }
+}
+
+fn extract_string(val: &RESP) -> Result<String> {
+    match val {
+        RESP::Bulk(body) => Ok(body.iter().map(|b| *b as char).collect()),
+        RESP::Simple(body) => Ok(body.clone()),
+        _ => Err(Error::Msg(
+            "Expected command argument to be a string".to_string(),
+        )),
+    }
+}
+
+fn extract_string_as_bytes(val: &RESP) -> Result<Bytes> {
+    match val {
+        RESP::Bulk(body) => Ok(body.clone()),
+        _ => Err(Error::Msg(
+            "Expected command argument to be a bulk string".to_string(),
+        )),
+    }
+}
@@ -0,0 +1,321 @@
+use bytes::Bytes;
+
+use crate::{
+    db::{BitFieldOp, BitFieldOverflow, BitFieldType, BitOp, BitRange, BitUnit, Db},
+    error::{Error, Result},
+    resp::RESP,
+    utils::parse_integer,
+};
+
+use super::args::Args;
+
+/// Bit offsets must address a string no longer than 512MB, Redis' default
+/// `proto-max-bulk-len`.
+const MAX_BIT_OFFSET: u64 = 512 * 1024 * 1024 * 8 - 1;
+
+#[derive(Debug, Clone)]
+pub enum BitmapCommand {
+    SetBit {
+        key: Bytes,
+        offset: u64,
+        bit: bool,
+    },
+    GetBit {
+        key: Bytes,
+        offset: u64,
+    },
+    Count {
+        key: Bytes,
+        range: Option<BitRange>,
+    },
+    Pos {
+        key: Bytes,
+        bit: bool,
+        range: Option<BitRange>,
+    },
+    Op {
+        op: BitOp,
+        destination: Bytes,
+        keys: Vec<Bytes>,
+    },
+    /// `BITFIELD` and `BITFIELD_RO`.
+    Field {
+        key: Bytes,
+        ops: Vec<BitFieldOp>,
+    },
+}
+
+impl BitmapCommand {
+    pub(super) fn execute(self, db: &mut Db) -> Result<RESP> {
+        use BitmapCommand::*;
+        let resp = match self {
+            SetBit { key, offset, bit } => RESP::Integer(db.setbit(&key, offset, bit)? as i64),
+            GetBit { key, offset } => RESP::Integer(db.getbit(&key, offset)? as i64),
+            Count { key, range } => RESP::Integer(db.bitcount(&key, range)? as i64),
+            Pos { key, bit, range } => RESP::Integer(db.bitpos(&key, bit, range)?),
+            Op {
+                op,
+                destination,
+                keys,
+            } => RESP::Integer(db.bitop(op, destination, &keys)? as i64),
+            Field { key, ops } => RESP::Array(
+                db.bitfield(&key, &ops)?
+                    .into_iter()
+                    .map(|value| value.map_or(RESP::Null, RESP::Integer))
+                    .collect(),
+            ),
+        };
+        Ok(resp)
+    }
+
+    /// Parses the command if it's a bitmap command, returns `None` otherwise.
+    pub(super) fn parse(args: &mut Args) -> Result<Option<BitmapCommand>> {
+        use BitmapCommand::*;
+        let command = match args.name() {
+            "setbit" => {
+                args.check_arity(4)?;
+                let key = args.next_key()?;
+                let offset = parse_offset(&args.next_bytes()?, None)?;
+                let bit = match args.next_integer() {
+                    Ok(bit @ (0 | 1)) => bit == 1,
+                    _ => {
+                        return Err(Error::OutOfRange(
+                            "bit is not an integer or out of range".to_string(),
+                        ))
+                    }
+                };
+                SetBit { key, offset, bit }
+            }
+            "getbit" => {
+                args.check_arity(3)?;
+                GetBit {
+                    key: args.next_key()?,
+                    offset: parse_offset(&args.next_bytes()?, None)?,
+                }
+            }
+            "bitcount" => {
+                args.check_arity(-2)?;
+                let key = args.next_key()?;
+                let range = match args.remaining() {
+                    0 => None,
+                    2 | 3 => {
+                        let start = args.next_integer()?;
+                        let end = args.next_integer()?;
+                        Some(BitRange {
+                            start,
+                            end: Some(end),
+                            unit: parse_unit(args)?,
+                        })
+                    }
+                    _ => return Err(Error::Syntax),
+                };
+                Count { key, range }
+            }
+            "bitpos" => {
+                args.check_arity(-3)?;
+                if args.remaining() > 5 {
+                    return Err(Error::Syntax);
+                }
+                let key = args.next_key()?;
+                let bit = match args.next_integer()? {
+                    bit @ (0 | 1) => bit == 1,
+                    _ => return Err(Error::Msg("The bit argument must be 1 or 0.".to_string())),
+                };
+                let range = if args.remaining() > 0 {
+                    let start = args.next_integer()?;
+                    let end = if args.remaining() > 0 {
+                        Some(args.next_integer()?)
+                    } else {
+                        None
+                    };
+                    Some(BitRange {
+                        start,
+                        end,
+                        unit: parse_unit(args)?,
+                    })
+                } else {
+                    None
+                };
+                Pos { key, bit, range }
+            }
+            "bitop" => {
+                args.check_arity(-4)?;
+                let op = match args.next_token()?.as_str() {
+                    "AND" => BitOp::And,
+                    "OR" => BitOp::Or,
+                    "XOR" => BitOp::Xor,
+                    "NOT" => BitOp::Not,
+                    _ => return Err(Error::Syntax),
+                };
+                let destination = args.next_key()?;
+                let keys = args.rest_keys()?;
+                if op == BitOp::Not && keys.len() != 1 {
+                    return Err(Error::Msg(
+                        "BITOP NOT must be called with a single source key.".to_string(),
+                    ));
+                }
+                Op {
+                    op,
+                    destination,
+                    keys,
+                }
+            }
+            "bitfield" | "bitfield_ro" => {
+                args.check_arity(-2)?;
+                let read_only = args.name() == "bitfield_ro";
+                let key = args.next_key()?;
+                let mut overflow = BitFieldOverflow::Wrap;
+                let mut ops = Vec::new();
+                while args.remaining() > 0 {
+                    let subcommand = args.next_token()?;
+                    let arguments = match subcommand.as_str() {
+                        "GET" => 2,
+                        "SET" | "INCRBY" => 3,
+                        "OVERFLOW" => 1,
+                        _ => return Err(Error::Syntax),
+                    };
+                    if args.remaining() < arguments {
+                        return Err(Error::Syntax);
+                    }
+                    if read_only && subcommand != "GET" {
+                        return Err(Error::Msg(
+                            "BITFIELD_RO only supports the GET subcommand".to_string(),
+                        ));
+                    }
+                    if subcommand == "OVERFLOW" {
+                        overflow = match args.next_token()?.as_str() {
+                            "WRAP" => BitFieldOverflow::Wrap,
+                            "SAT" => BitFieldOverflow::Sat,
+                            "FAIL" => BitFieldOverflow::Fail,
+                            _ => {
+                                return Err(Error::Msg(
+                                    "Invalid OVERFLOW type specified".to_string(),
+                                ))
+                            }
+                        };
+                        continue;
+                    }
+                    let ty = parse_type(&args.next_bytes()?)?;
+                    let offset = parse_offset(&args.next_bytes()?, Some(ty))?;
+                    ops.push(match subcommand.as_str() {
+                        "GET" => BitFieldOp::Get { ty, offset },
+                        "SET" => BitFieldOp::Set {
+                            ty,
+                            offset,
+                            value: args.next_integer()?,
+                            overflow,
+                        },
+                        _ => BitFieldOp::IncrBy {
+                            ty,
+                            offset,
+                            increment: args.next_integer()?,
+                            overflow,
+                        },
+                    });
+                }
+                Field { key, ops }
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(command))
+    }
+}
+
+/// Parses a bit offset. Offsets of `BITFIELD` may be given as `#N` to address the
+/// `N`th field of type `ty`.
+fn parse_offset(offset: &[u8], ty: Option<BitFieldType>) -> Result<u64> {
+    let (offset, multiplier) = match (offset.strip_prefix(b"#"), ty) {
+        (Some(index), Some(ty)) => (index, ty.bits as i64),
+        _ => (offset, 1),
+    };
+    parse_integer(offset)
+        .and_then(|offset| offset.checked_mul(multiplier))
+        .and_then(|offset| u64::try_from(offset).ok())
+        .filter(|&offset| offset <= MAX_BIT_OFFSET)
+        .ok_or_else(|| {
+            Error::OutOfRange("bit offset is not an integer or out of range".to_string())
+        })
+}
+
+/// Parses a `BITFIELD` type such as `i8` or `u16`.
+fn parse_type(ty: &[u8]) -> Result<BitFieldType> {
+    let invalid = || {
+        Error::Msg(
+            "Invalid bitfield type. Use something like i16 u8. Note that u64 is not supported but i64 is."
+                .to_string(),
+        )
+    };
+    let (signed, max_bits) = match ty.first() {
+        Some(b'i') => (true, 64),
+        Some(b'u') => (false, 63),
+        _ => return Err(invalid()),
+    };
+    match parse_integer(&ty[1..]) {
+        Some(bits) if (1..=max_bits).contains(&bits) => Ok(BitFieldType {
+            signed,
+            bits: bits as u32,
+        }),
+        _ => Err(invalid()),
+    }
+}
+
+/// Parses the optional `BYTE` or `BIT` ending a range.
+fn parse_unit(args: &mut Args) -> Result<BitUnit> {
+    if args.remaining() == 0 {
+        return Ok(BitUnit::Byte);
+    }
+    match args.next_token()?.as_str() {
+        "BYTE" => Ok(BitUnit::Byte),
+        "BIT" => Ok(BitUnit::Bit),
+        _ => Err(Error::Syntax),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{command::run, db::Db, resp::RESP};
+
+    #[test]
+    fn bitfield_replies() {
+        let mut db = Db::new();
+        assert_eq!(
+            run(&mut db, &["BITFIELD_RO", "b", "GET", "u8", "#4"]),
+            RESP::Array(vec![RESP::Integer(0)])
+        );
+        assert_eq!(run(&mut db, &["EXISTS", "b"]), RESP::Integer(0));
+        assert_eq!(
+            run(
+                &mut db,
+                &["BITFIELD", "b", "SET", "u8", "#1", "255", "GET", "u8", "8", "GET", "u4", "#3"]
+            ),
+            RESP::Array(vec![
+                RESP::Integer(0),
+                RESP::Integer(255),
+                RESP::Integer(15)
+            ])
+        );
+        assert_eq!(
+            run(
+                &mut db,
+                &["BITFIELD", "b", "OVERFLOW", "FAIL", "INCRBY", "u8", "#1", "1"]
+            ),
+            RESP::Array(vec![RESP::Null])
+        );
+        assert_eq!(
+            run(&mut db, &["BITFIELD", "b", "GET", "u8", "#-1"]),
+            RESP::Error("ERR bit offset is not an integer or out of range".to_string())
+        );
+        assert_eq!(
+            run(&mut db, &["BITFIELD_RO", "b", "SET", "u8", "0", "1"]),
+            RESP::Error("ERR BITFIELD_RO only supports the GET subcommand".to_string())
+        );
+        assert_eq!(
+            run(&mut db, &["BITFIELD", "b", "GET", "u64", "0"]),
+            RESP::Error(
+                "ERR Invalid bitfield type. Use something like i16 u8. Note that u64 is not \
+                 supported but i64 is."
+                    .to_string()
+            )
+        );
+    }
+}
@@ -0,0 +1,490 @@
+use bytes::Bytes;
+
+use crate::{
+    db::{Db, ExpireFlags},
+    error::{Error, Result},
+    resp::RESP,
+};
+
+use super::{args::Args, keys::unit_from_prefix, TimeUnit};
+
+#[derive(Debug, Clone)]
+pub enum HashCommand {
+    /// `HSET`, `HMSET` and `HSETNX`.
+    Set {
+        key: Bytes,
+        pairs: Vec<(Bytes, Bytes)>,
+        /// `HSETNX`, only set fields that don't exist.
+        only_if_missing: bool,
+        /// `HMSET` replies `OK` rather than the number of fields added.
+        reply_ok: bool,
+    },
+    Get {
+        key: Bytes,
+        field: Bytes,
+    },
+    MGet {
+        key: Bytes,
+        fields: Vec<Bytes>,
+    },
+    Del {
+        key: Bytes,
+        fields: Vec<Bytes>,
+    },
+    /// `HGETALL`, `HKEYS` and `HVALS`.
+    GetAll {
+        key: Bytes,
+        fields: bool,
+        values: bool,
+    },
+    IncrBy {
+        key: Bytes,
+        field: Bytes,
+        delta: i64,
+    },
+    IncrByFloat {
+        key: Bytes,
+        field: Bytes,
+        delta: f64,
+    },
+    Len {
+        key: Bytes,
+    },
+    Exists {
+        key: Bytes,
+        field: Bytes,
+    },
+    Strlen {
+        key: Bytes,
+        field: Bytes,
+    },
+    RandField {
+        key: Bytes,
+        /// With a count the reply is an array, even for a single field.
+        count: Option<i64>,
+        with_values: bool,
+    },
+    /// `HEXPIRE`, `HPEXPIRE`, `HEXPIREAT` and `HPEXPIREAT`.
+    Expire {
+        key: Bytes,
+        fields: Vec<Bytes>,
+        time: i64,
+        unit: TimeUnit,
+        /// Whether `time` is relative to now or a unix time.
+        relative: bool,
+        flags: ExpireFlags,
+    },
+    /// `HTTL` and `HPTTL`.
+    Ttl {
+        key: Bytes,
+        fields: Vec<Bytes>,
+        unit: TimeUnit,
+    },
+    /// `HEXPIRETIME` and `HPEXPIRETIME`.
+    ExpireTime {
+        key: Bytes,
+        fields: Vec<Bytes>,
+        unit: TimeUnit,
+    },
+    Persist {
+        key: Bytes,
+        fields: Vec<Bytes>,
+    },
+    Scan {
+        key: Bytes,
+        cursor: usize,
+        pattern: Option<Bytes>,
+        count: usize,
+        /// `NOVALUES`, only reply with the fields.
+        no_values: bool,
+    },
+}
+
+impl HashCommand {
+    pub(super) fn execute(self, db: &mut Db) -> Result<RESP> {
+        use HashCommand::*;
+        let resp = match self {
+            Set {
+                key,
+                pairs,
+                only_if_missing,
+                reply_ok,
+            } => {
+                let added = db.hset(key, pairs, only_if_missing)?;
+                if reply_ok {
+                    RESP::Simple("OK".to_string())
+                } else {
+                    RESP::Integer(added as i64)
+                }
+            }
+            Get { key, field } => db.hget(&key, &field)?.map_or(RESP::Null, RESP::Bulk),
+            MGet { key, fields } => RESP::Array(
+                db.hmget(&key, &fields)?
+                    .into_iter()
+                    .map(|value| value.map_or(RESP::Null, RESP::Bulk))
+                    .collect(),
+            ),
+            Del { key, fields } => RESP::Integer(db.hdel(&key, &fields)? as i64),
+            GetAll {
+                key,
+                fields,
+                values,
+            } => RESP::Array(flatten_pairs(db.hgetall(&key)?, fields, values)),
+            IncrBy { key, field, delta } => RESP::Integer(db.hincrby(key, field, delta)?),
+            IncrByFloat { key, field, delta } => RESP::Bulk(db.hincrbyfloat(key, field, delta)?),
+            Len { key } => RESP::Integer(db.hlen(&key)? as i64),
+            Exists { key, field } => RESP::Integer(db.hexists(&key, &field)? as i64),
+            Strlen { key, field } => RESP::Integer(db.hstrlen(&key, &field)? as i64),
+            RandField {
+                key,
+                count,
+                with_values,
+            } => {
+                let pairs = db.hrandfield(&key, count.unwrap_or(1))?;
+                match count {
+                    Some(_) => RESP::Array(flatten_pairs(pairs, true, with_values)),
+                    None => pairs
+                        .into_iter()
+                        .next()
+                        .map_or(RESP::Null, |(field, _)| RESP::Bulk(field)),
+                }
+            }
+            Expire {
+                key,
+                fields,
+                time,
+                unit,
+                relative,
+                flags,
+            } => {
+                let name = match (unit, relative) {
+                    (TimeUnit::Seconds, true) => "hexpire",
+                    (TimeUnit::Milliseconds, true) => "hpexpire",
+                    (TimeUnit::Seconds, false) => "hexpireat",
+                    (TimeUnit::Milliseconds, false) => "hpexpireat",
+                };
+                let invalid_expire_time = || Error::InvalidExpireTime(name.to_string());
+                let mut deadline = unit.to_millis(time).ok_or_else(invalid_expire_time)?;
+                if relative {
+                    deadline = deadline
+                        .checked_add(db.now() as i64)
+                        .ok_or_else(invalid_expire_time)?;
+                }
+                let replies = db.hexpire(&key, &fields, deadline, flags)?;
+                RESP::Array(replies.into_iter().map(RESP::Integer).collect())
+            }
+            Ttl { key, fields, unit } => {
+                let now = db.now();
+                let deadlines = db.hdeadlines(&key, &fields)?;
+                RESP::Array(
+                    deadlines
+                        .into_iter()
+                        .map(|deadline| match deadline {
+                            None => RESP::Integer(-2),
+                            Some(None) => RESP::Integer(-1),
+                            Some(Some(deadline)) => {
+                                let ttl = deadline.saturating_sub(now) as i64;
+                                RESP::Integer(unit.from_millis(ttl))
+                            }
+                        })
+                        .collect(),
+                )
+            }
+            ExpireTime { key, fields, unit } => RESP::Array(
+                db.hdeadlines(&key, &fields)?
+                    .into_iter()
+                    .map(|deadline| match deadline {
+                        None => RESP::Integer(-2),
+                        Some(None) => RESP::Integer(-1),
+                        Some(Some(deadline)) => RESP::Integer(unit.from_millis(deadline as i64)),
+                    })
+                    .collect(),
+            ),
+            Persist { key, fields } => RESP::Array(
+                db.hpersist(&key, &fields)?
+                    .into_iter()
+                    .map(RESP::Integer)
+                    .collect(),
+            ),
+            Scan {
+                key,
+                cursor,
+                pattern,
+                count,
+                no_values,
+            } => {
+                let (pairs, next_cursor) = db.hscan(&key, cursor, count, pattern.as_deref())?;
+                RESP::Array(vec![
+                    RESP::Bulk(next_cursor.to_string().into()),
+                    RESP::Array(flatten_pairs(pairs, true, !no_values)),
+                ])
+            }
+        };
+        Ok(resp)
+    }
+
+    /// Parses the command if it's a hash command, returns `None` otherwise.
+    pub(super) fn parse(args: &mut Args) -> Result<Option<HashCommand>> {
+        use HashCommand::*;
+        let command = match args.name() {
+            "hset" | "hmset" | "hsetnx" => {
+                let only_if_missing = args.name() == "hsetnx";
+                if only_if_missing {
+                    args.check_arity(4)?;
+                } else {
+                    args.check_arity(-4)?;
+                }
+                if args.remaining() % 2 != 1 {
+                    return Err(Error::WrongArity(args.name().to_string()));
+                }
+                let reply_ok = args.name() == "hmset";
+                let key = args.next_key()?;
+                let mut pairs = Vec::with_capacity(args.remaining() / 2);
+                while args.remaining() > 0 {
+                    pairs.push((args.next_bytes()?, args.next_bytes()?));
+                }
+                Set {
+                    key,
+                    pairs,
+                    only_if_missing,
+                    reply_ok,
+                }
+            }
+            "hget" => {
+                args.check_arity(3)?;
+                Get {
+                    key: args.next_key()?,
+                    field: args.next_bytes()?,
+                }
+            }
+            "hmget" => {
+                args.check_arity(-3)?;
+                MGet {
+                    key: args.next_key()?,
+                    fields: args.rest_bytes()?,
+                }
+            }
+            "hdel" => {
+                args.check_arity(-3)?;
+                Del {
+                    key: args.next_key()?,
+                    fields: args.rest_bytes()?,
+                }
+            }
+            "hgetall" | "hkeys" | "hvals" => {
+                args.check_arity(2)?;
+                GetAll {
+                    fields: args.name() != "hvals",
+                    values: args.name() != "hkeys",
+                    key: args.next_key()?,
+                }
+            }
+            "hincrby" => {
+                args.check_arity(4)?;
+                IncrBy {
+                    key: args.next_key()?,
+                    field: args.next_bytes()?,
+                    delta: args.next_integer()?,
+                }
+            }
+            "hincrbyfloat" => {
+                args.check_arity(4)?;
+                IncrByFloat {
+                    key: args.next_key()?,
+                    field: args.next_bytes()?,
+                    delta: args.next_float()?,
+                }
+            }
+            "hlen" => {
+                args.check_arity(2)?;
+                Len {
+                    key: args.next_key()?,
+                }
+            }
+            "hexists" => {
+                args.check_arity(3)?;
+                Exists {
+                    key: args.next_key()?,
+                    field: args.next_bytes()?,
+                }
+            }
+            "hstrlen" => {
+                args.check_arity(3)?;
+                Strlen {
+                    key: args.next_key()?,
+                    field: args.next_bytes()?,
+                }
+            }
+            "hrandfield" => {
+                args.check_arity(-2)?;
+                if args.remaining() > 3 {
+                    return Err(Error::Syntax);
+                }
+                let key = args.next_key()?;
+                let count = if args.remaining() > 0 {
+                    Some(args.next_sample_count()?)
+                } else {
+                    None
+                };
+                let with_values = match args.remaining() {
+                    0 => false,
+                    _ if args.next_token()? == "WITHVALUES" => true,
+                    _ => return Err(Error::Syntax),
+                };
+                RandField {
+                    key,
+                    count,
+                    with_values,
+                }
+            }
+            "hexpire" | "hpexpire" | "hexpireat" | "hpexpireat" => {
+                args.check_arity(-6)?;
+                let unit = unit_from_prefix(&args.name()[1..]);
+                let relative = !args.name().ends_with("at");
+                let key = args.next_key()?;
+                let time = args.next_integer()?;
+                if time < 0 {
+                    return Err(Error::Msg("invalid expire time, must be >= 0".to_string()));
+                }
+                let mut flags = ExpireFlags::default();
+                let mut option = args.next_token()?;
+                let flag = match option.as_str() {
+                    "NX" => Some(&mut flags.nx),
+                    "XX" => Some(&mut flags.xx),
+                    "GT" => Some(&mut flags.gt),
+                    "LT" => Some(&mut flags.lt),
+                    _ => None,
+                };
+                if let Some(flag) = flag {
+                    *flag = true;
+                    option = args.next_token()?;
+                }
+                if option != "FIELDS" {
+                    return Err(fields_missing());
+                }
+                Expire {
+                    key,
+                    fields: parse_fields(args)?,
+                    time,
+                    unit,
+                    relative,
+                    flags,
+                }
+            }
+            "httl" | "hpttl" | "hexpiretime" | "hpexpiretime" | "hpersist" => {
+                args.check_arity(-5)?;
+                let name = args.name().to_string();
+                let key = args.next_key()?;
+                if args.next_token()? != "FIELDS" {
+                    return Err(fields_missing());
+                }
+                let fields = parse_fields(args)?;
+                let unit = unit_from_prefix(&name[1..]);
+                match name.as_str() {
+                    "httl" | "hpttl" => Ttl { key, fields, unit },
+                    "hexpiretime" | "hpexpiretime" => ExpireTime { key, fields, unit },
+                    _ => Persist { key, fields },
+                }
+            }
+            "hscan" => {
+                args.check_arity(-3)?;
+                let key = args.next_key()?;
+                let cursor = args
+                    .next_string()?
+                    .parse()
+                    .map_err(|_| Error::Msg("invalid cursor".to_string()))?;
+                let mut pattern = None;
+                let mut count = 10;
+                let mut no_values = false;
+                while args.remaining() > 0 {
+                    match args.next_token()?.as_str() {
+                        "MATCH" => pattern = Some(args.next_bytes()?),
+                        "COUNT" => {
+                            count = usize::try_from(args.next_integer()?)
+                                .map_err(|_| Error::NotAnInteger)?;
+                            if count < 1 {
+                                return Err(Error::Syntax);
+                            }
+                        }
+                        "NOVALUES" => no_values = true,
+                        _ => return Err(Error::Syntax),
+                    }
+                }
+                Scan {
+                    key,
+                    cursor,
+                    pattern,
+                    count,
+                    no_values,
+                }
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(command))
+    }
+}
+
+/// Parses the `numfields field ...` following the `FIELDS` argument of the commands
+/// working on field expiry.
+fn parse_fields(args: &mut Args) -> Result<Vec<Bytes>> {
+    let num_fields = args.next_integer()?;
+    if num_fields <= 0 {
+        return Err(Error::Msg(
+            "Parameter `numFields` should be greater than 0".to_string(),
+        ));
+    }
+    if num_fields as usize != args.remaining() {
+        return Err(Error::Msg(
+            "The `numfields` parameter must match the number of arguments".to_string(),
+        ));
+    }
+    args.rest_bytes()
+}
+
+fn fields_missing() -> Error {
+    Error::Msg("Mandatory argument FIELDS is missing or not at the right position".to_string())
+}
+
+/// Flattens field value `pairs` into an array reply, keeping the `fields`, the
+/// `values` or both.
+fn flatten_pairs(pairs: Vec<(Bytes, Bytes)>, fields: bool, values: bool) -> Vec<RESP> {
+    let mut flattened = Vec::with_capacity(pairs.len() * 2);
+    for (field, value) in pairs {
+        if fields {
+            flattened.push(RESP::Bulk(field));
+        }
+        if values {
+            flattened.push(RESP::Bulk(value));
+        }
+    }
+    flattened
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        command::{bulks, run},
+        db::Db,
+        resp::RESP,
+    };
+
+    #[test]
+    fn random_fields_replies() {
+        let mut db = Db::new();
+        assert_eq!(run(&mut db, &["HRANDFIELD", "h"]), RESP::Null);
+        assert_eq!(run(&mut db, &["HRANDFIELD", "h", "-2"]), bulks(&[]));
+        run(&mut db, &["HSET", "h", "a", "1"]);
+        assert_eq!(
+            run(&mut db, &["HRANDFIELD", "h", "-2", "WITHVALUES"]),
+            bulks(&["a", "1", "a", "1"])
+        );
+        assert_eq!(
+            run(&mut db, &["HRANDFIELD", "h", "-9223372036854775807"]),
+            RESP::Error("ERR value is out of range".to_string())
+        );
+        assert_eq!(
+            run(&mut db, &["HRANDFIELD", "h", "1", "WITHSCORES"]),
+            RESP::Error("ERR syntax error".to_string())
+        );
+    }
+}
@@ -0,0 +1,112 @@
+use bytes::Bytes;
+
+use crate::{db::Db, error::Result, resp::RESP};
+
+use super::args::Args;
+
+#[derive(Debug, Clone)]
+pub enum HyperLogLogCommand {
+    Add {
+        key: Bytes,
+        elements: Vec<Bytes>,
+    },
+    Count {
+        keys: Vec<Bytes>,
+    },
+    Merge {
+        destination: Bytes,
+        keys: Vec<Bytes>,
+    },
+}
+
+impl HyperLogLogCommand {
+    pub(super) fn execute(self, db: &mut Db) -> Result<RESP> {
+        use HyperLogLogCommand::*;
+        let resp = match self {
+            Add { key, elements } => RESP::Integer(db.pfadd(&key, &elements)? as i64),
+            Count { keys } => RESP::Integer(db.pfcount(&keys)? as i64),
+            Merge { destination, keys } => {
+                db.pfmerge(&destination, &keys)?;
+                RESP::Simple("OK".to_string())
+            }
+        };
+        Ok(resp)
+    }
+
+    /// Parses the command if it's a HyperLogLog command, returns `None` otherwise.
+    pub(super) fn parse(args: &mut Args) -> Result<Option<HyperLogLogCommand>> {
+        use HyperLogLogCommand::*;
+        let command = match args.name() {
+            "pfadd" => {
+                args.check_arity(-2)?;
+                Add {
+                    key: args.next_key()?,
+                    elements: args.rest_bytes()?,
+                }
+            }
+            "pfcount" => {
+                args.check_arity(-2)?;
+                Count {
+                    keys: args.rest_keys()?,
+                }
+            }
+            "pfmerge" => {
+                args.check_arity(-2)?;
+                Merge {
+                    destination: args.next_key()?,
+                    keys: args.rest_keys()?,
+                }
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(command))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        command::run,
+        db::{Db, SetCondition, SetExpiry},
+        resp::RESP,
+    };
+
+    #[test]
+    fn hyperloglog_replies() {
+        let mut db = Db::new();
+        assert_eq!(run(&mut db, &["PFCOUNT", "h"]), RESP::Integer(0));
+        assert_eq!(run(&mut db, &["PFADD", "h", "a", "b"]), RESP::Integer(1));
+        assert_eq!(run(&mut db, &["PFADD", "h", "a"]), RESP::Integer(0));
+        assert_eq!(
+            run(&mut db, &["PFMERGE", "m", "h", "missing"]),
+            RESP::Simple("OK".to_string())
+        );
+        assert_eq!(run(&mut db, &["PFCOUNT", "m", "h"]), RESP::Integer(2));
+
+        run(&mut db, &["SET", "s", "HYLL"]);
+        assert_eq!(
+            run(&mut db, &["PFADD", "s", "a"]),
+            RESP::Error("WRONGTYPE Key is not a valid HyperLogLog string value.".to_string())
+        );
+        run(&mut db, &["RPUSH", "l", "a"]);
+        assert_eq!(
+            run(&mut db, &["PFCOUNT", "h", "l"]),
+            RESP::Error(
+                "WRONGTYPE Operation against a key holding the wrong kind of value".to_string()
+            )
+        );
+        // Runs covering one register short of all of them.
+        db.set(
+            "c".into(),
+            b"HYLL\x01\0\0\0\0\0\0\0\0\0\0\x80\x7f\xfe"[..].into(),
+            SetCondition::Always,
+            SetExpiry::Persist,
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            run(&mut db, &["PFCOUNT", "c"]),
+            RESP::Error("INVALIDOBJ Corrupted HLL object detected".to_string())
+        );
+    }
+}
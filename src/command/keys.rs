@@ -0,0 +1,378 @@
+use bytes::Bytes;
+
+use crate::{
+    db::{Db, ExpireFlags},
+    error::{Error, Result},
+    resp::RESP,
+};
+
+use super::args::Args;
+
+/// Commands working on keys regardless of the type of their value.
+#[derive(Debug, Clone)]
+pub enum KeyCommand {
+    /// `DEL` and `UNLINK`, there's no lazy freeing so both delete synchronously.
+    Del {
+        keys: Vec<Bytes>,
+    },
+    /// `EXISTS` and `TOUCH`, with no access times to update both just count.
+    Exists {
+        keys: Vec<Bytes>,
+    },
+    Type {
+        key: Bytes,
+    },
+    Rename {
+        key: Bytes,
+        new_key: Bytes,
+        /// `RENAMENX`, only rename if `new_key` doesn't exist.
+        only_if_missing: bool,
+    },
+    Copy {
+        source: Bytes,
+        destination: Bytes,
+        replace: bool,
+    },
+    /// `EXPIRE`, `PEXPIRE`, `EXPIREAT` and `PEXPIREAT`.
+    Expire {
+        key: Bytes,
+        time: i64,
+        unit: TimeUnit,
+        /// Whether `time` is relative to now or a unix time.
+        relative: bool,
+        flags: ExpireFlags,
+    },
+    /// `TTL` and `PTTL`.
+    Ttl {
+        key: Bytes,
+        unit: TimeUnit,
+    },
+    /// `EXPIRETIME` and `PEXPIRETIME`.
+    ExpireTime {
+        key: Bytes,
+        unit: TimeUnit,
+    },
+    Persist {
+        key: Bytes,
+    },
+    Keys {
+        pattern: Bytes,
+    },
+    Scan {
+        cursor: usize,
+        pattern: Option<Bytes>,
+        count: usize,
+        value_type: Option<String>,
+    },
+    /// `OBJECT ENCODING`.
+    ObjectEncoding {
+        key: Bytes,
+    },
+    /// `OBJECT REFCOUNT`, values are never shared so it's always 1.
+    ObjectRefcount {
+        key: Bytes,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeUnit {
+    Seconds,
+    Milliseconds,
+}
+
+impl TimeUnit {
+    /// Converts `time` in this unit to milliseconds, `None` on overflow.
+    pub fn to_millis(self, time: i64) -> Option<i64> {
+        match self {
+            TimeUnit::Seconds => time.checked_mul(1000),
+            TimeUnit::Milliseconds => Some(time),
+        }
+    }
+
+    /// Converts `millis` to this unit, rounding to the closest second.
+    pub fn from_millis(self, millis: i64) -> i64 {
+        match self {
+            TimeUnit::Seconds => (millis + 500) / 1000,
+            TimeUnit::Milliseconds => millis,
+        }
+    }
+}
+
+impl KeyCommand {
+    pub(super) fn execute(self, db: &mut Db) -> Result<RESP> {
+        use KeyCommand::*;
+        let resp = match self {
+            Del { keys } => RESP::Integer(db.del(&keys)),
+            Exists { keys } => RESP::Integer(db.exists(&keys)),
+            Type { key } => RESP::Simple(db.type_of(&key).to_string()),
+            Rename {
+                key,
+                new_key,
+                only_if_missing,
+            } => {
+                let renamed = db.rename(&key, new_key, only_if_missing)?;
+                if only_if_missing {
+                    RESP::Integer(renamed as i64)
+                } else {
+                    RESP::Simple("OK".to_string())
+                }
+            }
+            Copy {
+                source,
+                destination,
+                replace,
+            } => {
+                if source == destination {
+                    return Err(Error::Msg(
+                        "source and destination objects are the same".to_string(),
+                    ));
+                }
+                RESP::Integer(db.copy(&source, destination, replace) as i64)
+            }
+            Expire {
+                key,
+                time,
+                unit,
+                relative,
+                flags,
+            } => {
+                let name = match (unit, relative) {
+                    (TimeUnit::Seconds, true) => "expire",
+                    (TimeUnit::Milliseconds, true) => "pexpire",
+                    (TimeUnit::Seconds, false) => "expireat",
+                    (TimeUnit::Milliseconds, false) => "pexpireat",
+                };
+                let invalid_expire_time = || Error::InvalidExpireTime(name.to_string());
+                let mut deadline = unit.to_millis(time).ok_or_else(invalid_expire_time)?;
+                if relative {
+                    deadline = deadline
+                        .checked_add(db.now() as i64)
+                        .ok_or_else(invalid_expire_time)?;
+                }
+                RESP::Integer(db.expire(&key, deadline, flags) as i64)
+            }
+            Ttl { key, unit } => match db.deadline(&key) {
+                None => RESP::Integer(-2),
+                Some(None) => RESP::Integer(-1),
+                Some(Some(deadline)) => {
+                    let ttl = deadline.saturating_sub(db.now()) as i64;
+                    RESP::Integer(unit.from_millis(ttl))
+                }
+            },
+            ExpireTime { key, unit } => match db.deadline(&key) {
+                None => RESP::Integer(-2),
+                Some(None) => RESP::Integer(-1),
+                Some(Some(deadline)) => RESP::Integer(unit.from_millis(deadline as i64)),
+            },
+            Persist { key } => RESP::Integer(db.persist(&key) as i64),
+            Keys { pattern } => {
+                RESP::Array(db.keys(&pattern).into_iter().map(RESP::Bulk).collect())
+            }
+            Scan {
+                cursor,
+                pattern,
+                count,
+                value_type,
+            } => {
+                let (keys, next_cursor) =
+                    db.scan(cursor, count, pattern.as_deref(), value_type.as_deref());
+                RESP::Array(vec![
+                    RESP::Bulk(next_cursor.to_string().into()),
+                    RESP::Array(keys.into_iter().map(RESP::Bulk).collect()),
+                ])
+            }
+            ObjectEncoding { key } => match db.encoding(&key) {
+                Some(encoding) => RESP::Bulk(encoding.into()),
+                None => RESP::Null,
+            },
+            ObjectRefcount { key } => match db.exists(&[key]) {
+                0 => RESP::Null,
+                _ => RESP::Integer(1),
+            },
+        };
+        Ok(resp)
+    }
+
+    /// Parses the command if it's a key command, returns `None` otherwise.
+    pub(super) fn parse(args: &mut Args) -> Result<Option<KeyCommand>> {
+        use KeyCommand::*;
+        let command = match args.name() {
+            "del" | "unlink" => {
+                args.check_arity(-2)?;
+                Del {
+                    keys: args.rest_keys()?,
+                }
+            }
+            "exists" | "touch" => {
+                args.check_arity(-2)?;
+                Exists {
+                    keys: args.rest_keys()?,
+                }
+            }
+            "type" => {
+                args.check_arity(2)?;
+                Type {
+                    key: args.next_key()?,
+                }
+            }
+            "rename" | "renamenx" => {
+                args.check_arity(3)?;
+                Rename {
+                    only_if_missing: args.name() == "renamenx",
+                    key: args.next_key()?,
+                    new_key: args.next_key()?,
+                }
+            }
+            "copy" => {
+                args.check_arity(-3)?;
+                let source = args.next_key()?;
+                let destination = args.next_key()?;
+                let mut replace = false;
+                while args.remaining() > 0 {
+                    match args.next_token()?.as_str() {
+                        "REPLACE" => replace = true,
+                        // There's only the one database.
+                        "DB" => {
+                            if args.next_integer()? != 0 {
+                                return Err(Error::OutOfRange(
+                                    "DB index is out of range".to_string(),
+                                ));
+                            }
+                        }
+                        _ => return Err(Error::Syntax),
+                    }
+                }
+                Copy {
+                    source,
+                    destination,
+                    replace,
+                }
+            }
+            "expire" | "pexpire" | "expireat" | "pexpireat" => {
+                args.check_arity(-3)?;
+                let unit = unit_from_prefix(args.name());
+                let relative = !args.name().ends_with("at");
+                let key = args.next_key()?;
+                let time = args.next_integer()?;
+                let mut flags = ExpireFlags::default();
+                while args.remaining() > 0 {
+                    let option = args.next_token()?;
+                    match option.as_str() {
+                        "NX" => flags.nx = true,
+                        "XX" => flags.xx = true,
+                        "GT" => flags.gt = true,
+                        "LT" => flags.lt = true,
+                        _ => return Err(Error::Msg(format!("Unsupported option {option}"))),
+                    }
+                }
+                if flags.nx && (flags.xx || flags.gt || flags.lt) {
+                    return Err(Error::Msg(
+                        "NX and XX, GT or LT options at the same time are not compatible"
+                            .to_string(),
+                    ));
+                }
+                if flags.gt && flags.lt {
+                    return Err(Error::Msg(
+                        "GT and LT options at the same time are not compatible".to_string(),
+                    ));
+                }
+                Expire {
+                    key,
+                    time,
+                    unit,
+                    relative,
+                    flags,
+                }
+            }
+            "ttl" | "pttl" => {
+                args.check_arity(2)?;
+                Ttl {
+                    unit: unit_from_prefix(args.name()),
+                    key: args.next_key()?,
+                }
+            }
+            "expiretime" | "pexpiretime" => {
+                args.check_arity(2)?;
+                ExpireTime {
+                    unit: unit_from_prefix(args.name()),
+                    key: args.next_key()?,
+                }
+            }
+            "persist" => {
+                args.check_arity(2)?;
+                Persist {
+                    key: args.next_key()?,
+                }
+            }
+            "keys" => {
+                args.check_arity(2)?;
+                Keys {
+                    pattern: args.next_bytes()?,
+                }
+            }
+            "scan" => {
+                args.check_arity(-2)?;
+                let cursor = args
+                    .next_string()?
+                    .parse()
+                    .map_err(|_| Error::Msg("invalid cursor".to_string()))?;
+                let mut pattern = None;
+                let mut count = 10;
+                let mut value_type = None;
+                while args.remaining() > 0 {
+                    match args.next_token()?.as_str() {
+                        "MATCH" => pattern = Some(args.next_bytes()?),
+                        "COUNT" => {
+                            count = usize::try_from(args.next_integer()?)
+                                .map_err(|_| Error::NotAnInteger)?;
+                            if count < 1 {
+                                return Err(Error::Syntax);
+                            }
+                        }
+                        "TYPE" => value_type = Some(args.next_string()?.to_lowercase()),
+                        _ => return Err(Error::Syntax),
+                    }
+                }
+                Scan {
+                    cursor,
+                    pattern,
+                    count,
+                    value_type,
+                }
+            }
+            "object" => {
+                args.check_arity(-2)?;
+                let subcommand = args.next_string()?;
+                match subcommand.to_lowercase().as_str() {
+                    "encoding" | "refcount" if args.remaining() != 1 => {
+                        return Err(Error::Msg(format!(
+                            "unknown subcommand or wrong number of arguments for '{subcommand}'. Try OBJECT HELP."
+                        )));
+                    }
+                    "encoding" => ObjectEncoding {
+                        key: args.next_key()?,
+                    },
+                    "refcount" => ObjectRefcount {
+                        key: args.next_key()?,
+                    },
+                    _ => {
+                        return Err(Error::Msg(format!(
+                            "unknown subcommand '{subcommand}'. Try OBJECT HELP."
+                        )))
+                    }
+                }
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(command))
+    }
+}
+
+/// Commands coming in second and millisecond flavours have the latter prefixed with a `p`.
+pub(super) fn unit_from_prefix(name: &str) -> TimeUnit {
+    if name.starts_with('p') {
+        TimeUnit::Milliseconds
+    } else {
+        TimeUnit::Seconds
+    }
+}
@@ -0,0 +1,473 @@
+use std::time::Duration;
+
+use bytes::Bytes;
+
+use crate::{
+    db::{Db, ListEnd},
+    error::{Error, Result},
+    resp::RESP,
+};
+
+use super::{args::Args, BlockOn};
+
+#[derive(Debug, Clone)]
+pub enum ListCommand {
+    /// `LPUSH`, `RPUSH`, `LPUSHX` and `RPUSHX`.
+    Push {
+        key: Bytes,
+        elements: Vec<Bytes>,
+        end: ListEnd,
+        /// The `X` variants only push to lists that already exist.
+        only_if_exists: bool,
+    },
+    /// `LPOP` and `RPOP`.
+    Pop {
+        key: Bytes,
+        end: ListEnd,
+        /// With a count the reply is an array, even for a single element.
+        count: Option<usize>,
+    },
+    /// `LMPOP` and `BLMPOP`, along with `BLPOP` and `BRPOP` which pop a single
+    /// element.
+    MPop {
+        keys: Vec<Bytes>,
+        end: ListEnd,
+        /// `None` for `BLPOP` and `BRPOP` whose reply is the key and the element.
+        count: Option<usize>,
+        /// Set for the blocking variants, a zero timeout blocks forever.
+        timeout: Option<Duration>,
+    },
+    /// `LMOVE`, `BLMOVE`, `RPOPLPUSH` and `BRPOPLPUSH`.
+    Move {
+        source: Bytes,
+        destination: Bytes,
+        from: ListEnd,
+        to: ListEnd,
+        /// Set for the blocking variants, a zero timeout blocks forever.
+        timeout: Option<Duration>,
+    },
+    Len {
+        key: Bytes,
+    },
+    Range {
+        key: Bytes,
+        start: i64,
+        stop: i64,
+    },
+    Index {
+        key: Bytes,
+        index: i64,
+    },
+    Set {
+        key: Bytes,
+        index: i64,
+        element: Bytes,
+    },
+    Rem {
+        key: Bytes,
+        count: i64,
+        element: Bytes,
+    },
+    Trim {
+        key: Bytes,
+        start: i64,
+        stop: i64,
+    },
+    Insert {
+        key: Bytes,
+        before: bool,
+        pivot: Bytes,
+        element: Bytes,
+    },
+    Pos {
+        key: Bytes,
+        element: Bytes,
+        rank: i64,
+        /// With a count the reply is an array of indexes, `0` meaning all of them.
+        count: Option<usize>,
+        max_len: usize,
+    },
+}
+
+impl ListCommand {
+    pub(super) fn execute(self, db: &mut Db) -> Result<RESP> {
+        use ListCommand::*;
+        let resp = match self {
+            Push {
+                key,
+                elements,
+                end,
+                only_if_exists,
+            } => RESP::Integer(db.push(key, elements, end, only_if_exists)? as i64),
+            Pop { key, end, count } => match db.pop(&key, end, count.unwrap_or(1))? {
+                None if count.is_some() => RESP::NullArray,
+                None => RESP::Null,
+                Some(popped) if count.is_some() => {
+                    RESP::Array(popped.into_iter().map(RESP::Bulk).collect())
+                }
+                Some(popped) => popped.into_iter().next().map_or(RESP::Null, RESP::Bulk),
+            },
+            command @ MPop { .. } => command.try_execute(db)?.unwrap_or(RESP::NullArray),
+            command @ Move { .. } => command.try_execute(db)?.unwrap_or(RESP::Null),
+            Len { key } => RESP::Integer(db.llen(&key)? as i64),
+            Range { key, start, stop } => RESP::Array(
+                db.lrange(&key, start, stop)?
+                    .into_iter()
+                    .map(RESP::Bulk)
+                    .collect(),
+            ),
+            Index { key, index } => db.lindex(&key, index)?.map_or(RESP::Null, RESP::Bulk),
+            Set {
+                key,
+                index,
+                element,
+            } => {
+                db.lset(&key, index, element)?;
+                RESP::Simple("OK".to_string())
+            }
+            Rem {
+                key,
+                count,
+                element,
+            } => RESP::Integer(db.lrem(&key, count, &element)? as i64),
+            Trim { key, start, stop } => {
+                db.ltrim(&key, start, stop)?;
+                RESP::Simple("OK".to_string())
+            }
+            Insert {
+                key,
+                before,
+                pivot,
+                element,
+            } => RESP::Integer(db.linsert(&key, before, &pivot, element)?),
+            Pos {
+                key,
+                element,
+                rank,
+                count,
+                max_len,
+            } => {
+                let indexes = db.lpos(&key, &element, rank, count.unwrap_or(1), max_len)?;
+                let mut indexes = indexes.into_iter().map(|index| RESP::Integer(index as i64));
+                match count {
+                    Some(_) => RESP::Array(indexes.collect()),
+                    None => indexes.next().unwrap_or(RESP::Null),
+                }
+            }
+        };
+        Ok(resp)
+    }
+
+    /// Executes the command, `None` if it pops from lists that are all empty.
+    pub(super) fn try_execute(self, db: &mut Db) -> Result<Option<RESP>> {
+        use ListCommand::*;
+        let resp = match self {
+            MPop {
+                keys, end, count, ..
+            } => db
+                .mpop(&keys, end, count.unwrap_or(1))?
+                .map(|(key, popped)| {
+                    let mut popped = popped.into_iter().map(RESP::Bulk);
+                    let popped = match count {
+                        Some(_) => RESP::Array(popped.collect()),
+                        None => popped.next().unwrap_or(RESP::Null),
+                    };
+                    RESP::Array(vec![RESP::Bulk(key), popped])
+                }),
+            Move {
+                source,
+                destination,
+                from,
+                to,
+                ..
+            } => db.lmove(&source, destination, from, to)?.map(RESP::Bulk),
+            command => Some(command.execute(db)?),
+        };
+        Ok(resp)
+    }
+
+    /// What the command waits for if it's a blocking one.
+    pub(super) fn block_on(&self) -> Option<BlockOn> {
+        use ListCommand::*;
+        match self {
+            MPop {
+                keys,
+                timeout: Some(timeout),
+                ..
+            } => Some(BlockOn::new(keys.clone(), *timeout)),
+            Move {
+                source,
+                timeout: Some(timeout),
+                ..
+            } => Some(BlockOn::new(vec![source.clone()], *timeout)),
+            _ => None,
+        }
+    }
+
+    /// Parses the command if it's a list command, returns `None` otherwise.
+    pub(super) fn parse(args: &mut Args) -> Result<Option<ListCommand>> {
+        use ListCommand::*;
+        let command = match args.name() {
+            "lpush" | "rpush" | "lpushx" | "rpushx" => {
+                args.check_arity(-3)?;
+                Push {
+                    end: end_from_prefix(args.name()),
+                    only_if_exists: args.name().ends_with('x'),
+                    key: args.next_key()?,
+                    elements: args.rest_bytes()?,
+                }
+            }
+            "lpop" | "rpop" => {
+                args.check_arity(-2)?;
+                if args.remaining() > 2 {
+                    return Err(Error::WrongArity(args.name().to_string()));
+                }
+                let end = end_from_prefix(args.name());
+                let key = args.next_key()?;
+                let count = if args.remaining() > 0 {
+                    Some(args.next_count()?)
+                } else {
+                    None
+                };
+                Pop { key, end, count }
+            }
+            "blpop" | "brpop" => {
+                args.check_arity(-3)?;
+                let end = end_from_prefix(&args.name()[1..]);
+                let mut keys = Vec::with_capacity(args.remaining() - 1);
+                while args.remaining() > 1 {
+                    keys.push(args.next_key()?);
+                }
+                MPop {
+                    keys,
+                    end,
+                    count: None,
+                    timeout: Some(args.next_timeout()?),
+                }
+            }
+            "lmpop" | "blmpop" => {
+                let blocking = args.name() == "blmpop";
+                args.check_arity(if blocking { -5 } else { -4 })?;
+                let timeout = if blocking {
+                    Some(args.next_timeout()?)
+                } else {
+                    None
+                };
+                let num_keys = usize::try_from(args.next_integer()?)
+                    .ok()
+                    .filter(|&num_keys| num_keys > 0)
+                    .ok_or_else(|| Error::Msg("numkeys should be greater than 0".to_string()))?;
+                if num_keys >= args.remaining() {
+                    return Err(Error::Syntax);
+                }
+                let mut keys = Vec::with_capacity(num_keys);
+                for _ in 0..num_keys {
+                    keys.push(args.next_key()?);
+                }
+                let end = parse_end(args)?;
+                let mut count = 1;
+                while args.remaining() > 0 {
+                    match args.next_token()?.as_str() {
+                        "COUNT" => {
+                            count = usize::try_from(args.next_integer()?)
+                                .ok()
+                                .filter(|&count| count > 0)
+                                .ok_or_else(|| {
+                                    Error::Msg("count should be greater than 0".to_string())
+                                })?;
+                        }
+                        _ => return Err(Error::Syntax),
+                    }
+                }
+                MPop {
+                    keys,
+                    end,
+                    count: Some(count),
+                    timeout,
+                }
+            }
+            "lmove" | "blmove" => {
+                let blocking = args.name() == "blmove";
+                args.check_arity(if blocking { 6 } else { 5 })?;
+                Move {
+                    source: args.next_key()?,
+                    destination: args.next_key()?,
+                    from: parse_end(args)?,
+                    to: parse_end(args)?,
+                    timeout: if blocking {
+                        Some(args.next_timeout()?)
+                    } else {
+                        None
+                    },
+                }
+            }
+            // `RPOPLPUSH source destination` is the same as `LMOVE source destination RIGHT LEFT`.
+            "rpoplpush" | "brpoplpush" => {
+                let blocking = args.name() == "brpoplpush";
+                args.check_arity(if blocking { 4 } else { 3 })?;
+                Move {
+                    source: args.next_key()?,
+                    destination: args.next_key()?,
+                    from: ListEnd::Right,
+                    to: ListEnd::Left,
+                    timeout: if blocking {
+                        Some(args.next_timeout()?)
+                    } else {
+                        None
+                    },
+                }
+            }
+            "llen" => {
+                args.check_arity(2)?;
+                Len {
+                    key: args.next_key()?,
+                }
+            }
+            "lrange" => {
+                args.check_arity(4)?;
+                Range {
+                    key: args.next_key()?,
+                    start: args.next_integer()?,
+                    stop: args.next_integer()?,
+                }
+            }
+            "lindex" => {
+                args.check_arity(3)?;
+                Index {
+                    key: args.next_key()?,
+                    index: args.next_integer()?,
+                }
+            }
+            "lset" => {
+                args.check_arity(4)?;
+                Set {
+                    key: args.next_key()?,
+                    index: args.next_integer()?,
+                    element: args.next_bytes()?,
+                }
+            }
+            "lrem" => {
+                args.check_arity(4)?;
+                Rem {
+                    key: args.next_key()?,
+                    count: args.next_integer()?,
+                    element: args.next_bytes()?,
+                }
+            }
+            "ltrim" => {
+                args.check_arity(4)?;
+                Trim {
+                    key: args.next_key()?,
+                    start: args.next_integer()?,
+                    stop: args.next_integer()?,
+                }
+            }
+            "linsert" => {
+                args.check_arity(5)?;
+                let key = args.next_key()?;
+                let before = match args.next_token()?.as_str() {
+                    "BEFORE" => true,
+                    "AFTER" => false,
+                    _ => return Err(Error::Syntax),
+                };
+                Insert {
+                    key,
+                    before,
+                    pivot: args.next_bytes()?,
+                    element: args.next_bytes()?,
+                }
+            }
+            "lpos" => {
+                args.check_arity(-3)?;
+                let key = args.next_key()?;
+                let element = args.next_bytes()?;
+                let mut rank = 1;
+                let mut count = None;
+                let mut max_len = 0;
+                while args.remaining() > 0 {
+                    match args.next_token()?.as_str() {
+                        "RANK" => {
+                            rank = args.next_integer()?;
+                            if rank == 0 {
+                                return Err(Error::Msg(
+                                    "RANK can't be zero: use 1 to start from the first match, 2 from the second ... or use negative to start from the end of the list".to_string(),
+                                ));
+                            }
+                        }
+                        "COUNT" => {
+                            count =
+                                Some(usize::try_from(args.next_integer()?).map_err(|_| {
+                                    Error::Msg("COUNT can't be negative".to_string())
+                                })?);
+                        }
+                        "MAXLEN" => {
+                            max_len = usize::try_from(args.next_integer()?)
+                                .map_err(|_| Error::Msg("MAXLEN can't be negative".to_string()))?;
+                        }
+                        _ => return Err(Error::Syntax),
+                    }
+                }
+                Pos {
+                    key,
+                    element,
+                    rank,
+                    count,
+                    max_len,
+                }
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(command))
+    }
+}
+
+/// Parses a `LEFT` or `RIGHT` argument.
+fn parse_end(args: &mut Args) -> Result<ListEnd> {
+    match args.next_token()?.as_str() {
+        "LEFT" => Ok(ListEnd::Left),
+        "RIGHT" => Ok(ListEnd::Right),
+        _ => Err(Error::Syntax),
+    }
+}
+
+/// List commands working on the head are prefixed with an `l`, those working on the
+/// tail with an `r`.
+fn end_from_prefix(name: &str) -> ListEnd {
+    if name.starts_with('l') {
+        ListEnd::Left
+    } else {
+        ListEnd::Right
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        command::{bulks, run},
+        db::Db,
+        resp::RESP,
+    };
+
+    #[test]
+    fn pop_replies() {
+        let mut db = Db::new();
+        assert_eq!(run(&mut db, &["LPOP", "l"]), RESP::Null);
+        assert_eq!(run(&mut db, &["RPOP", "l", "2"]), RESP::NullArray);
+        assert_eq!(run(&mut db, &["BLPOP", "l", "0.01"]), RESP::NullArray);
+        run(&mut db, &["RPUSH", "l", "a", "b", "c"]);
+        assert_eq!(run(&mut db, &["LPOP", "l", "1"]), bulks(&["a"]));
+        assert_eq!(run(&mut db, &["RPOP", "l"]), RESP::Bulk("c".into()));
+        assert_eq!(run(&mut db, &["BLPOP", "l", "0"]), bulks(&["l", "b"]));
+        assert_eq!(
+            run(&mut db, &["LPOP", "l", "-1"]),
+            RESP::Error("ERR value is out of range, must be positive".to_string())
+        );
+        run(&mut db, &["SET", "s", "a"]);
+        assert_eq!(
+            run(&mut db, &["RPOP", "s", "1"]),
+            RESP::Error(
+                "WRONGTYPE Operation against a key holding the wrong kind of value".to_string()
+            )
+        );
+    }
+}
@@ -0,0 +1,197 @@
+use std::time::Duration;
+
+use bytes::Bytes;
+
+use crate::{
+    db::Db,
+    error::{Error, Result},
+    resp::RESP,
+};
+
+mod args;
+mod bitmap;
+mod hash;
+mod hyperloglog;
+mod keys;
+mod list;
+mod set;
+mod stream;
+mod string;
+mod zset;
+
+use args::Args;
+pub use bitmap::BitmapCommand;
+pub use hash::HashCommand;
+pub use hyperloglog::HyperLogLogCommand;
+pub use keys::{KeyCommand, TimeUnit};
+pub use list::ListCommand;
+pub use set::SetCommand;
+pub use stream::StreamCommand;
+pub use string::StringCommand;
+pub use zset::ZSetCommand;
+
+#[derive(Debug, Clone)]
+pub enum Command {
+    Ping { msg: Option<Bytes> },
+    Echo { msg: RESP },
+    Key(KeyCommand),
+    String(StringCommand),
+    Bitmap(BitmapCommand),
+    List(ListCommand),
+    Hash(HashCommand),
+    Set(SetCommand),
+    ZSet(ZSetCommand),
+    Stream(StreamCommand),
+    HyperLogLog(HyperLogLogCommand),
+}
+
+/// What a blocking command waits for when it can't be served right away.
+#[derive(Debug, Clone)]
+pub struct BlockOn {
+    /// The command is retried whenever one of these keys is written to.
+    pub keys: Vec<Bytes>,
+    /// How long to wait at most, `None` to wait forever.
+    pub timeout: Option<Duration>,
+}
+
+impl BlockOn {
+    /// Blocking commands take a zero `timeout` to mean forever.
+    fn new(keys: Vec<Bytes>, timeout: Duration) -> BlockOn {
+        BlockOn {
+            keys,
+            timeout: (!timeout.is_zero()).then_some(timeout),
+        }
+    }
+}
+
+impl Command {
+    pub async fn execute_cmd(self, db: &mut Db) -> Result<RESP> {
+        use Command::*;
+        match self {
+            Ping { msg } => {
+                if let Some(msg) = msg {
+                    Ok(RESP::Bulk(msg))
+                } else {
+                    Ok(RESP::Simple("PONG".to_string()))
+                }
+            }
+            Echo { msg } => Ok(msg),
+            Key(command) => command.execute(db),
+            String(command) => command.execute(db),
+            Bitmap(command) => command.execute(db),
+            List(command) => command.execute(db),
+            Hash(command) => command.execute(db),
+            Set(command) => command.execute(db),
+            ZSet(command) => command.execute(db),
+            Stream(command) => command.execute(db),
+            HyperLogLog(command) => command.execute(db),
+        }
+    }
+
+    /// What the command waits for if it's a blocking one. A blocking command that
+    /// can't be served yet gets `None` from `try_execute` and should be retried later.
+    pub fn block_on(&self) -> Option<BlockOn> {
+        match self {
+            Command::List(command) => command.block_on(),
+            Command::ZSet(command) => command.block_on(),
+            Command::Stream(command) => command.block_on(),
+            _ => None,
+        }
+    }
+
+    /// Executes the command, `None` if it's a blocking command that can't be served
+    /// yet.
+    pub async fn try_execute(self, db: &mut Db) -> Result<Option<RESP>> {
+        match self {
+            Command::List(command) => command.try_execute(db),
+            Command::ZSet(command) => command.try_execute(db),
+            Command::Stream(command) => command.try_execute(db),
+            command => command.execute_cmd(db).await.map(Some),
+        }
+    }
+
+    /// Pins down what a blocking command reads relative to the current state of the
+    /// db, like the `$` ID of `XREAD`, before it's first executed.
+    pub fn pin(&mut self, db: &mut Db) {
+        if let Command::Stream(command) = self {
+            command.pin(db);
+        }
+    }
+}
+
+/// Parses `args` as a command and executes it against `db`, an error being turned into
+/// the reply the client would get.
+#[cfg(test)]
+pub(crate) fn run(db: &mut Db, args: &[&str]) -> RESP {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    Command::try_from(bulks(args))
+        .and_then(|command| runtime.block_on(command.execute_cmd(db)))
+        .unwrap_or_else(RESP::from)
+}
+
+/// The array reply of bulk strings holding `items`.
+#[cfg(test)]
+pub(crate) fn bulks(items: &[&str]) -> RESP {
+    RESP::Array(
+        items
+            .iter()
+            .map(|item| RESP::Bulk(Bytes::copy_from_slice(item.as_bytes())))
+            .collect(),
+    )
+}
+
+impl TryFrom<RESP> for Command {
+    type Error = Error;
+    fn try_from(value: RESP) -> Result<Self> {
+        let mut args = Args::new(value)?;
+
+        let command = match args.name() {
+            "ping" => {
+                args.check_arity(-1)?;
+                if args.remaining() > 1 {
+                    return Err(Error::WrongArity("ping".to_string()));
+                }
+                let msg = if args.remaining() == 1 {
+                    Some(args.next_bytes()?)
+                } else {
+                    None
+                };
+                Command::Ping { msg }
+            }
+            "echo" => {
+                args.check_arity(2)?;
+                let msg = args.next_resp()?;
+                if !msg.is_string() {
+                    return Err(Error::Msg("Echo command only takes strings".to_string()));
+                }
+                Command::Echo { msg }
+            }
+            _ => {
+                if let Some(command) = KeyCommand::parse(&mut args)? {
+                    Command::Key(command)
+                } else if let Some(command) = StringCommand::parse(&mut args)? {
+                    Command::String(command)
+                } else if let Some(command) = BitmapCommand::parse(&mut args)? {
+                    Command::Bitmap(command)
+                } else if let Some(command) = ListCommand::parse(&mut args)? {
+                    Command::List(command)
+                } else if let Some(command) = HashCommand::parse(&mut args)? {
+                    Command::Hash(command)
+                } else if let Some(command) = SetCommand::parse(&mut args)? {
+                    Command::Set(command)
+                } else if let Some(command) = ZSetCommand::parse(&mut args)? {
+                    Command::ZSet(command)
+                } else if let Some(command) = StreamCommand::parse(&mut args)? {
+                    Command::Stream(command)
+                } else if let Some(command) = HyperLogLogCommand::parse(&mut args)? {
+                    Command::HyperLogLog(command)
+                } else {
+                    return Err(args.unknown_command());
+                }
+            }
+        };
+        Ok(command)
+    }
+}
@@ -0,0 +1,284 @@
+use bytes::Bytes;
+
+use crate::{
+    db::{Db, SetOp},
+    error::{Error, Result},
+    resp::RESP,
+};
+
+use super::args::Args;
+
+#[derive(Debug, Clone)]
+pub enum SetCommand {
+    Add {
+        key: Bytes,
+        members: Vec<Bytes>,
+    },
+    Rem {
+        key: Bytes,
+        members: Vec<Bytes>,
+    },
+    Members {
+        key: Bytes,
+    },
+    IsMember {
+        key: Bytes,
+        member: Bytes,
+    },
+    MIsMember {
+        key: Bytes,
+        members: Vec<Bytes>,
+    },
+    Card {
+        key: Bytes,
+    },
+    Pop {
+        key: Bytes,
+        /// With a count the reply is an array, even for a single member.
+        count: Option<usize>,
+    },
+    RandMember {
+        key: Bytes,
+        /// With a count the reply is an array, even for a single member.
+        count: Option<i64>,
+    },
+    Move {
+        source: Bytes,
+        destination: Bytes,
+        member: Bytes,
+    },
+    /// `SINTER`, `SUNION` and `SDIFF`.
+    Op {
+        op: SetOp,
+        keys: Vec<Bytes>,
+    },
+    /// `SINTERSTORE`, `SUNIONSTORE` and `SDIFFSTORE`.
+    OpStore {
+        op: SetOp,
+        destination: Bytes,
+        keys: Vec<Bytes>,
+    },
+    InterCard {
+        keys: Vec<Bytes>,
+        /// `0` means no limit.
+        limit: usize,
+    },
+}
+
+impl SetCommand {
+    pub(super) fn execute(self, db: &mut Db) -> Result<RESP> {
+        use SetCommand::*;
+        let resp = match self {
+            Add { key, members } => RESP::Integer(db.sadd(key, members)? as i64),
+            Rem { key, members } => RESP::Integer(db.srem(&key, &members)? as i64),
+            Members { key } => bulk_array(db.smembers(&key)?),
+            IsMember { key, member } => RESP::Integer(db.sismember(&key, &member)? as i64),
+            MIsMember { key, members } => RESP::Array(
+                db.smismember(&key, &members)?
+                    .into_iter()
+                    .map(|is_member| RESP::Integer(is_member as i64))
+                    .collect(),
+            ),
+            Card { key } => RESP::Integer(db.scard(&key)? as i64),
+            Pop { key, count } => {
+                let popped = db.spop(&key, count.unwrap_or(1))?;
+                match count {
+                    Some(_) => bulk_array(popped),
+                    None => popped.into_iter().next().map_or(RESP::Null, RESP::Bulk),
+                }
+            }
+            RandMember { key, count } => {
+                let members = db.srandmember(&key, count.unwrap_or(1))?;
+                match count {
+                    Some(_) => bulk_array(members),
+                    None => members.into_iter().next().map_or(RESP::Null, RESP::Bulk),
+                }
+            }
+            Move {
+                source,
+                destination,
+                member,
+            } => RESP::Integer(db.smove(&source, destination, member)? as i64),
+            Op { op, keys } => bulk_array(db.set_op(op, &keys, None)?),
+            OpStore {
+                op,
+                destination,
+                keys,
+            } => RESP::Integer(db.set_op_store(op, destination, &keys)? as i64),
+            InterCard { keys, limit } => {
+                let limit = (limit > 0).then_some(limit);
+                RESP::Integer(db.set_op(SetOp::Inter, &keys, limit)?.len() as i64)
+            }
+        };
+        Ok(resp)
+    }
+
+    /// Parses the command if it's a set command, returns `None` otherwise.
+    pub(super) fn parse(args: &mut Args) -> Result<Option<SetCommand>> {
+        use SetCommand::*;
+        let command = match args.name() {
+            "sadd" => {
+                args.check_arity(-3)?;
+                Add {
+                    key: args.next_key()?,
+                    members: args.rest_bytes()?,
+                }
+            }
+            "srem" => {
+                args.check_arity(-3)?;
+                Rem {
+                    key: args.next_key()?,
+                    members: args.rest_bytes()?,
+                }
+            }
+            "smembers" => {
+                args.check_arity(2)?;
+                Members {
+                    key: args.next_key()?,
+                }
+            }
+            "sismember" => {
+                args.check_arity(3)?;
+                IsMember {
+                    key: args.next_key()?,
+                    member: args.next_bytes()?,
+                }
+            }
+            "smismember" => {
+                args.check_arity(-3)?;
+                MIsMember {
+                    key: args.next_key()?,
+                    members: args.rest_bytes()?,
+                }
+            }
+            "scard" => {
+                args.check_arity(2)?;
+                Card {
+                    key: args.next_key()?,
+                }
+            }
+            "spop" => {
+                args.check_arity(-2)?;
+                if args.remaining() > 2 {
+                    return Err(Error::Syntax);
+                }
+                let key = args.next_key()?;
+                let count = if args.remaining() > 0 {
+                    Some(args.next_count()?)
+                } else {
+                    None
+                };
+                Pop { key, count }
+            }
+            "srandmember" => {
+                args.check_arity(-2)?;
+                if args.remaining() > 2 {
+                    return Err(Error::Syntax);
+                }
+                let key = args.next_key()?;
+                let count = if args.remaining() > 0 {
+                    Some(args.next_sample_count()?)
+                } else {
+                    None
+                };
+                RandMember { key, count }
+            }
+            "smove" => {
+                args.check_arity(4)?;
+                Move {
+                    source: args.next_key()?,
+                    destination: args.next_key()?,
+                    member: args.next_bytes()?,
+                }
+            }
+            "sinter" | "sunion" | "sdiff" => {
+                args.check_arity(-2)?;
+                Op {
+                    op: op_from_name(args.name()),
+                    keys: args.rest_keys()?,
+                }
+            }
+            "sinterstore" | "sunionstore" | "sdiffstore" => {
+                args.check_arity(-3)?;
+                OpStore {
+                    op: op_from_name(args.name()),
+                    destination: args.next_key()?,
+                    keys: args.rest_keys()?,
+                }
+            }
+            "sintercard" => {
+                args.check_arity(-3)?;
+                let num_keys = usize::try_from(args.next_integer()?)
+                    .ok()
+                    .filter(|&num_keys| num_keys > 0)
+                    .ok_or_else(|| Error::Msg("numkeys should be greater than 0".to_string()))?;
+                if num_keys > args.remaining() {
+                    return Err(Error::Msg(
+                        "Number of keys can't be greater than number of args".to_string(),
+                    ));
+                }
+                let mut keys = Vec::with_capacity(num_keys);
+                for _ in 0..num_keys {
+                    keys.push(args.next_key()?);
+                }
+                let mut limit = 0;
+                while args.remaining() > 0 {
+                    match args.next_token()?.as_str() {
+                        "LIMIT" => {
+                            limit = usize::try_from(args.next_integer()?)
+                                .map_err(|_| Error::Msg("LIMIT can't be negative".to_string()))?;
+                        }
+                        _ => return Err(Error::Syntax),
+                    }
+                }
+                InterCard { keys, limit }
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(command))
+    }
+}
+
+fn op_from_name(name: &str) -> SetOp {
+    if name.starts_with("sinter") {
+        SetOp::Inter
+    } else if name.starts_with("sunion") {
+        SetOp::Union
+    } else {
+        SetOp::Diff
+    }
+}
+
+fn bulk_array(members: Vec<Bytes>) -> RESP {
+    RESP::Array(members.into_iter().map(RESP::Bulk).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        command::{bulks, run},
+        db::Db,
+        resp::RESP,
+    };
+
+    #[test]
+    fn random_member_replies() {
+        let mut db = Db::new();
+        assert_eq!(run(&mut db, &["SRANDMEMBER", "s"]), RESP::Null);
+        assert_eq!(run(&mut db, &["SPOP", "s"]), RESP::Null);
+        assert_eq!(run(&mut db, &["SPOP", "s", "2"]), bulks(&[]));
+        run(&mut db, &["SADD", "s", "a"]);
+        assert_eq!(
+            run(&mut db, &["SRANDMEMBER", "s", "-2"]),
+            bulks(&["a", "a"])
+        );
+        assert_eq!(
+            run(&mut db, &["SRANDMEMBER", "s", "-9223372036854775808"]),
+            RESP::Error("ERR value is out of range".to_string())
+        );
+        assert_eq!(
+            run(&mut db, &["SPOP", "s", "-1"]),
+            RESP::Error("ERR value is out of range, must be positive".to_string())
+        );
+    }
+}
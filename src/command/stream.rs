@@ -0,0 +1,1158 @@
+use std::time::Duration;
+
+use bytes::Bytes;
+
+use crate::{
+    db::{Db, StreamFields, StreamId, StreamRead, StreamTrim, TrimStrategy, XAddId, XClaimOptions},
+    error::{Error, Result},
+    resp::RESP,
+    utils::parse_integer,
+};
+
+use super::{args::Args, BlockOn};
+
+#[derive(Debug, Clone)]
+pub enum StreamCommand {
+    Add {
+        key: Bytes,
+        id: XAddId,
+        fields: StreamFields,
+        /// `NOMKSTREAM`, don't create the stream if it doesn't exist.
+        no_mkstream: bool,
+        trim: Option<StreamTrim>,
+    },
+    /// `XRANGE` and `XREVRANGE`.
+    Range {
+        key: Bytes,
+        start: StreamId,
+        end: StreamId,
+        rev: bool,
+        count: Option<usize>,
+    },
+    Len {
+        key: Bytes,
+    },
+    Del {
+        key: Bytes,
+        ids: Vec<StreamId>,
+    },
+    Trim {
+        key: Bytes,
+        trim: StreamTrim,
+    },
+    GroupCreate {
+        key: Bytes,
+        group: Bytes,
+        /// `None` for `$`, the last ID of the stream.
+        id: Option<StreamId>,
+        /// `MKSTREAM`, create the stream if it doesn't exist.
+        mkstream: bool,
+        /// `ENTRIESREAD`, -1 meaning unknown.
+        entries_read: Option<i64>,
+    },
+    GroupSetId {
+        key: Bytes,
+        group: Bytes,
+        /// `None` for `$`, the last ID of the stream.
+        id: Option<StreamId>,
+        /// `ENTRIESREAD`, -1 meaning unknown.
+        entries_read: Option<i64>,
+    },
+    GroupDestroy {
+        key: Bytes,
+        group: Bytes,
+    },
+    GroupCreateConsumer {
+        key: Bytes,
+        group: Bytes,
+        consumer: Bytes,
+    },
+    GroupDelConsumer {
+        key: Bytes,
+        group: Bytes,
+        consumer: Bytes,
+    },
+    Read {
+        /// The streams along with the ID to read after, `None` for `$` until the
+        /// command is pinned.
+        streams: Vec<(Bytes, Option<StreamId>)>,
+        count: Option<usize>,
+        /// `BLOCK`, a zero timeout blocks forever.
+        block: Option<Duration>,
+    },
+    ReadGroup {
+        group: Bytes,
+        consumer: Bytes,
+        /// The streams along with the ID to read after, `None` for `>`.
+        streams: Vec<(Bytes, Option<StreamId>)>,
+        count: Option<usize>,
+        /// `NOACK`, don't add the entries read to the PEL.
+        noack: bool,
+        /// `BLOCK`, a zero timeout blocks forever.
+        block: Option<Duration>,
+    },
+    Ack {
+        key: Bytes,
+        group: Bytes,
+        ids: Vec<StreamId>,
+    },
+    /// `XPENDING key group`.
+    PendingSummary {
+        key: Bytes,
+        group: Bytes,
+    },
+    /// `XPENDING` with a range.
+    Pending {
+        key: Bytes,
+        group: Bytes,
+        min_idle: u64,
+        start: StreamId,
+        end: StreamId,
+        count: usize,
+        consumer: Option<Bytes>,
+    },
+    Claim {
+        key: Bytes,
+        group: Bytes,
+        consumer: Bytes,
+        min_idle: u64,
+        ids: Vec<StreamId>,
+        options: XClaimOptions,
+    },
+    AutoClaim {
+        key: Bytes,
+        group: Bytes,
+        consumer: Bytes,
+        min_idle: u64,
+        start: StreamId,
+        count: usize,
+        just_id: bool,
+    },
+    InfoStream {
+        key: Bytes,
+    },
+    InfoGroups {
+        key: Bytes,
+    },
+    InfoConsumers {
+        key: Bytes,
+        group: Bytes,
+    },
+}
+
+impl StreamCommand {
+    pub(super) fn execute(self, db: &mut Db) -> Result<RESP> {
+        use StreamCommand::*;
+        let resp = match self {
+            Add {
+                key,
+                id,
+                fields,
+                no_mkstream,
+                trim,
+            } => db
+                .xadd(key, id, fields, no_mkstream, trim)?
+                .map_or(RESP::Null, id_resp),
+            Range {
+                key,
+                start,
+                end,
+                rev,
+                count,
+            } => entries_array(db.xrange(&key, start, end, rev, count)?),
+            Len { key } => RESP::Integer(db.xlen(&key)? as i64),
+            Del { key, ids } => RESP::Integer(db.xdel(&key, &ids)? as i64),
+            Trim { key, trim } => RESP::Integer(db.xtrim(&key, trim)? as i64),
+            GroupCreate {
+                key,
+                group,
+                id,
+                mkstream,
+                entries_read,
+            } => {
+                db.xgroup_create(key, group, id, mkstream, entries_read)?;
+                RESP::Simple("OK".to_string())
+            }
+            GroupSetId {
+                key,
+                group,
+                id,
+                entries_read,
+            } => {
+                db.xgroup_setid(&key, &group, id, entries_read)?;
+                RESP::Simple("OK".to_string())
+            }
+            GroupDestroy { key, group } => RESP::Integer(db.xgroup_destroy(&key, &group)? as i64),
+            GroupCreateConsumer {
+                key,
+                group,
+                consumer,
+            } => RESP::Integer(db.xgroup_create_consumer(&key, &group, consumer)? as i64),
+            GroupDelConsumer {
+                key,
+                group,
+                consumer,
+            } => RESP::Integer(db.xgroup_del_consumer(&key, &group, &consumer)? as i64),
+            command @ (Read { .. } | ReadGroup { .. }) => {
+                command.try_execute(db)?.unwrap_or(RESP::NullArray)
+            }
+            Ack { key, group, ids } => RESP::Integer(db.xack(&key, &group, &ids)? as i64),
+            PendingSummary { key, group } => {
+                let summary = db.xpending_summary(&key, &group)?;
+                let (first, last) = match summary.bounds {
+                    Some((first, last)) => (id_resp(first), id_resp(last)),
+                    None => (RESP::Null, RESP::Null),
+                };
+                let consumers = if summary.consumers.is_empty() {
+                    RESP::NullArray
+                } else {
+                    RESP::Array(
+                        summary
+                            .consumers
+                            .into_iter()
+                            .map(|(name, count)| {
+                                RESP::Array(vec![
+                                    RESP::Bulk(name),
+                                    RESP::Bulk(count.to_string().into()),
+                                ])
+                            })
+                            .collect(),
+                    )
+                };
+                RESP::Array(vec![
+                    RESP::Integer(summary.count as i64),
+                    first,
+                    last,
+                    consumers,
+                ])
+            }
+            Pending {
+                key,
+                group,
+                min_idle,
+                start,
+                end,
+                count,
+                consumer,
+            } => {
+                let pending = db.xpending(
+                    &key,
+                    &group,
+                    min_idle,
+                    start,
+                    end,
+                    count,
+                    consumer.as_deref(),
+                )?;
+                RESP::Array(
+                    pending
+                        .into_iter()
+                        .map(|info| {
+                            RESP::Array(vec![
+                                id_resp(info.id),
+                                RESP::Bulk(info.consumer),
+                                RESP::Integer(info.idle as i64),
+                                RESP::Integer(info.deliveries as i64),
+                            ])
+                        })
+                        .collect(),
+                )
+            }
+            Claim {
+                key,
+                group,
+                consumer,
+                min_idle,
+                ids,
+                options,
+            } => {
+                let claimed = db.xclaim(&key, &group, &consumer, min_idle, &ids, options)?;
+                if options.just_id {
+                    RESP::Array(claimed.into_iter().map(|(id, _)| id_resp(id)).collect())
+                } else {
+                    entries_array(claimed)
+                }
+            }
+            AutoClaim {
+                key,
+                group,
+                consumer,
+                min_idle,
+                start,
+                count,
+                just_id,
+            } => {
+                let claim =
+                    db.xautoclaim(&key, &group, &consumer, min_idle, start, count, just_id)?;
+                let claimed = if just_id {
+                    RESP::Array(
+                        claim
+                            .claimed
+                            .into_iter()
+                            .map(|(id, _)| id_resp(id))
+                            .collect(),
+                    )
+                } else {
+                    entries_array(claim.claimed)
+                };
+                RESP::Array(vec![
+                    id_resp(claim.next),
+                    claimed,
+                    RESP::Array(claim.deleted.into_iter().map(id_resp).collect()),
+                ])
+            }
+            InfoStream { key } => {
+                let info = db.xinfo_stream(&key)?;
+                let entry = |entry: Option<(StreamId, StreamFields)>| {
+                    entry.map_or(RESP::Null, |(id, fields)| entry_resp(id, Some(fields)))
+                };
+                map_resp(vec![
+                    ("length", RESP::Integer(info.length as i64)),
+                    (
+                        "radix-tree-keys",
+                        RESP::Integer(info.radix_tree_keys as i64),
+                    ),
+                    ("last-generated-id", id_resp(info.last_generated_id)),
+                    ("max-deleted-entry-id", id_resp(info.max_deleted_id)),
+                    ("entries-added", RESP::Integer(info.entries_added as i64)),
+                    ("recorded-first-entry-id", id_resp(info.recorded_first_id)),
+                    ("groups", RESP::Integer(info.groups as i64)),
+                    ("first-entry", entry(info.first_entry)),
+                    ("last-entry", entry(info.last_entry)),
+                ])
+            }
+            InfoGroups { key } => RESP::Array(
+                db.xinfo_groups(&key)?
+                    .into_iter()
+                    .map(|info| {
+                        let optional = |count: Option<u64>| {
+                            count.map_or(RESP::Null, |count| RESP::Integer(count as i64))
+                        };
+                        map_resp(vec![
+                            ("name", RESP::Bulk(info.name)),
+                            ("consumers", RESP::Integer(info.consumers as i64)),
+                            ("pending", RESP::Integer(info.pending as i64)),
+                            ("last-delivered-id", id_resp(info.last_delivered)),
+                            ("entries-read", optional(info.entries_read)),
+                            ("lag", optional(info.lag)),
+                        ])
+                    })
+                    .collect(),
+            ),
+            InfoConsumers { key, group } => RESP::Array(
+                db.xinfo_consumers(&key, &group)?
+                    .into_iter()
+                    .map(|info| {
+                        map_resp(vec![
+                            ("name", RESP::Bulk(info.name)),
+                            ("pending", RESP::Integer(info.pending as i64)),
+                            ("idle", RESP::Integer(info.idle as i64)),
+                            (
+                                "inactive",
+                                RESP::Integer(info.inactive.map_or(-1, |inactive| inactive as i64)),
+                            ),
+                        ])
+                    })
+                    .collect(),
+            ),
+        };
+        Ok(resp)
+    }
+
+    /// Executes the command, `None` if it reads nothing.
+    pub(super) fn try_execute(self, db: &mut Db) -> Result<Option<RESP>> {
+        use StreamCommand::*;
+        let resp = match self {
+            Read { streams, count, .. } => {
+                let streams: Vec<_> = streams
+                    .into_iter()
+                    .map(|(key, id)| (key, id.unwrap_or(StreamId::MAX)))
+                    .collect();
+                reads_resp(db.xread(&streams, count)?)
+            }
+            ReadGroup {
+                group,
+                consumer,
+                streams,
+                count,
+                noack,
+                ..
+            } => reads_resp(db.xreadgroup(&group, &consumer, &streams, count, noack)?),
+            command => Some(command.execute(db)?),
+        };
+        Ok(resp)
+    }
+
+    /// What the command waits for if it's a blocking one, see `Command::block_on`.
+    pub(super) fn block_on(&self) -> Option<BlockOn> {
+        use StreamCommand::*;
+        match self {
+            Read {
+                streams,
+                block: Some(timeout),
+                ..
+            }
+            | ReadGroup {
+                streams,
+                block: Some(timeout),
+                ..
+            } => {
+                let keys = streams.iter().map(|(key, _)| key.clone()).collect();
+                Some(BlockOn::new(keys, *timeout))
+            }
+            _ => None,
+        }
+    }
+
+    /// Replaces the `$` IDs of `XREAD` with the last IDs of the streams, so that a
+    /// blocked read only gets the entries added after it was first executed.
+    pub(super) fn pin(&mut self, db: &mut Db) {
+        if let StreamCommand::Read { streams, .. } = self {
+            for (key, id) in streams {
+                if id.is_none() {
+                    // A wrong type is reported when the command is executed.
+                    *id = db.xlast_id(key).ok();
+                }
+            }
+        }
+    }
+
+    /// Parses the command if it's a stream command, returns `None` otherwise.
+    pub(super) fn parse(args: &mut Args) -> Result<Option<StreamCommand>> {
+        use StreamCommand::*;
+        let command = match args.name() {
+            "xadd" => {
+                args.check_arity(-5)?;
+                let key = args.next_key()?;
+                let mut no_mkstream = false;
+                let mut trim = None;
+                while let Some(option) = args.peek_token() {
+                    match option.as_str() {
+                        "NOMKSTREAM" => {
+                            args.next_resp()?;
+                            no_mkstream = true;
+                        }
+                        "MAXLEN" | "MINID" if trim.is_none() => trim = Some(parse_trim(args)?),
+                        "MAXLEN" | "MINID" => {
+                            return Err(Error::Msg(
+                                "syntax error, MAXLEN and MINID options at the same time are not compatible"
+                                    .to_string(),
+                            ))
+                        }
+                        _ => break,
+                    }
+                }
+                let id = parse_xadd_id(&args.next_bytes()?)?;
+                if args.remaining() == 0 || args.remaining() % 2 != 0 {
+                    return Err(Error::WrongArity(args.name().to_string()));
+                }
+                let mut fields = Vec::with_capacity(args.remaining() / 2);
+                while args.remaining() > 0 {
+                    fields.push((args.next_bytes()?, args.next_bytes()?));
+                }
+                Add {
+                    key,
+                    id,
+                    fields,
+                    no_mkstream,
+                    trim,
+                }
+            }
+            "xrange" | "xrevrange" => {
+                args.check_arity(-4)?;
+                let rev = args.name() == "xrevrange";
+                let key = args.next_key()?;
+                let (first, second) = (args.next_bytes()?, args.next_bytes()?);
+                // `XREVRANGE` takes the end of the range first.
+                let (start, end) = if rev {
+                    (second, first)
+                } else {
+                    (first, second)
+                };
+                let (start, end) = (parse_range_start(&start)?, parse_range_end(&end)?);
+                let mut count = None;
+                while args.remaining() > 0 {
+                    match args.next_token()?.as_str() {
+                        "COUNT" => count = Some(args.next_integer()?.max(0) as usize),
+                        _ => return Err(Error::Syntax),
+                    }
+                }
+                Range {
+                    key,
+                    start,
+                    end,
+                    rev,
+                    count,
+                }
+            }
+            "xlen" => {
+                args.check_arity(2)?;
+                Len {
+                    key: args.next_key()?,
+                }
+            }
+            "xdel" => {
+                args.check_arity(-3)?;
+                let key = args.next_key()?;
+                let mut ids = Vec::with_capacity(args.remaining());
+                while args.remaining() > 0 {
+                    ids.push(parse_id(&args.next_bytes()?, 0)?);
+                }
+                Del { key, ids }
+            }
+            "xtrim" => {
+                args.check_arity(-4)?;
+                let key = args.next_key()?;
+                let trim = match args.peek_token().as_deref() {
+                    Some("MAXLEN" | "MINID") => parse_trim(args)?,
+                    _ => return Err(Error::Syntax),
+                };
+                if args.remaining() > 0 {
+                    return Err(Error::Syntax);
+                }
+                Trim { key, trim }
+            }
+            "xgroup" => {
+                args.check_arity(-2)?;
+                let subcommand = args.next_string()?;
+                let lowercase = subcommand.to_lowercase();
+                let wrong_arity = match lowercase.as_str() {
+                    "create" => !(3..=6).contains(&args.remaining()),
+                    "setid" => !(3..=5).contains(&args.remaining()),
+                    "destroy" => args.remaining() != 2,
+                    "createconsumer" | "delconsumer" => args.remaining() != 3,
+                    _ => {
+                        return Err(Error::Msg(format!(
+                            "unknown subcommand '{subcommand}'. Try XGROUP HELP."
+                        )))
+                    }
+                };
+                if wrong_arity {
+                    return Err(Error::Msg(format!(
+                        "unknown subcommand or wrong number of arguments for '{subcommand}'. Try XGROUP HELP."
+                    )));
+                }
+                let key = args.next_key()?;
+                let group = args.next_bytes()?;
+                match lowercase.as_str() {
+                    "create" | "setid" => {
+                        let id = match &args.next_bytes()?[..] {
+                            b"$" => None,
+                            id => Some(parse_id(id, 0)?),
+                        };
+                        let mut mkstream = false;
+                        let mut entries_read = None;
+                        while args.remaining() > 0 {
+                            match args.next_token()?.as_str() {
+                                "MKSTREAM" if lowercase == "create" => mkstream = true,
+                                "ENTRIESREAD" if args.remaining() > 0 => {
+                                    let read = args.next_integer()?;
+                                    if read < -1 {
+                                        return Err(Error::Msg(
+                                            "value for ENTRIESREAD must be positive or -1"
+                                                .to_string(),
+                                        ));
+                                    }
+                                    entries_read = Some(read);
+                                }
+                                _ => return Err(Error::Syntax),
+                            }
+                        }
+                        if lowercase == "create" {
+                            GroupCreate {
+                                key,
+                                group,
+                                id,
+                                mkstream,
+                                entries_read,
+                            }
+                        } else {
+                            GroupSetId {
+                                key,
+                                group,
+                                id,
+                                entries_read,
+                            }
+                        }
+                    }
+                    "destroy" => GroupDestroy { key, group },
+                    "createconsumer" => GroupCreateConsumer {
+                        key,
+                        group,
+                        consumer: args.next_bytes()?,
+                    },
+                    _ => GroupDelConsumer {
+                        key,
+                        group,
+                        consumer: args.next_bytes()?,
+                    },
+                }
+            }
+            "xread" | "xreadgroup" => parse_read(args)?,
+            "xack" => {
+                args.check_arity(-4)?;
+                let key = args.next_key()?;
+                let group = args.next_bytes()?;
+                let mut ids = Vec::with_capacity(args.remaining());
+                while args.remaining() > 0 {
+                    ids.push(parse_id(&args.next_bytes()?, 0)?);
+                }
+                Ack { key, group, ids }
+            }
+            "xpending" => {
+                args.check_arity(-3)?;
+                let key = args.next_key()?;
+                let group = args.next_bytes()?;
+                if args.remaining() == 0 {
+                    return Ok(Some(PendingSummary { key, group }));
+                }
+                let mut min_idle = 0;
+                if args.peek_token().as_deref() == Some("IDLE") {
+                    args.next_resp()?;
+                    min_idle = args.next_integer()?.max(0) as u64;
+                }
+                if !(3..=4).contains(&args.remaining()) {
+                    return Err(Error::Syntax);
+                }
+                let start = parse_range_start(&args.next_bytes()?)?;
+                let end = parse_range_end(&args.next_bytes()?)?;
+                let count = args.next_integer()?.max(0) as usize;
+                let consumer = if args.remaining() > 0 {
+                    Some(args.next_bytes()?)
+                } else {
+                    None
+                };
+                Pending {
+                    key,
+                    group,
+                    min_idle,
+                    start,
+                    end,
+                    count,
+                    consumer,
+                }
+            }
+            "xclaim" => {
+                args.check_arity(-6)?;
+                let key = args.next_key()?;
+                let group = args.next_bytes()?;
+                let consumer = args.next_bytes()?;
+                let min_idle = parse_min_idle(args)?;
+                // The IDs go on until the first argument that isn't one.
+                let rest = args.rest_bytes()?;
+                let ids: Vec<_> = rest.iter().map_while(|arg| parse_id(arg, 0).ok()).collect();
+                let mut rest = rest.into_iter().skip(ids.len());
+                let mut options = XClaimOptions::default();
+                while let Some(option) = rest.next() {
+                    let name = String::from_utf8_lossy(&option).to_uppercase();
+                    let mut integer = || {
+                        rest.next()
+                            .and_then(|value| parse_integer(&value))
+                            .and_then(|value| u64::try_from(value).ok())
+                            .ok_or_else(|| {
+                                Error::Msg(format!("Invalid {name} option argument for XCLAIM"))
+                            })
+                    };
+                    match name.as_str() {
+                        "IDLE" => options.idle = Some(integer()?),
+                        "TIME" => options.time = Some(integer()?),
+                        "RETRYCOUNT" => options.retry_count = Some(integer()?),
+                        "FORCE" => options.force = true,
+                        "JUSTID" => options.just_id = true,
+                        "LASTID" => {
+                            let id = rest.next().ok_or(Error::Syntax)?;
+                            options.last_id = Some(parse_id(&id, 0)?);
+                        }
+                        _ => {
+                            return Err(Error::Msg(format!(
+                                "Unrecognized XCLAIM option '{}'",
+                                String::from_utf8_lossy(&option)
+                            )))
+                        }
+                    }
+                }
+                Claim {
+                    key,
+                    group,
+                    consumer,
+                    min_idle,
+                    ids,
+                    options,
+                }
+            }
+            "xautoclaim" => {
+                args.check_arity(-6)?;
+                let key = args.next_key()?;
+                let group = args.next_bytes()?;
+                let consumer = args.next_bytes()?;
+                let min_idle = parse_min_idle(args)?;
+                let start = parse_range_start(&args.next_bytes()?)?;
+                let mut count = 100;
+                let mut just_id = false;
+                while args.remaining() > 0 {
+                    match args.next_token()?.as_str() {
+                        "COUNT" => {
+                            count = usize::try_from(args.next_integer()?)
+                                .ok()
+                                .filter(|&count| count > 0)
+                                .ok_or_else(|| Error::Msg("COUNT must be > 0".to_string()))?;
+                        }
+                        "JUSTID" => just_id = true,
+                        _ => return Err(Error::Syntax),
+                    }
+                }
+                AutoClaim {
+                    key,
+                    group,
+                    consumer,
+                    min_idle,
+                    start,
+                    count,
+                    just_id,
+                }
+            }
+            "xinfo" => {
+                args.check_arity(-2)?;
+                let subcommand = args.next_string()?;
+                let lowercase = subcommand.to_lowercase();
+                let wrong_arity = match lowercase.as_str() {
+                    "stream" | "groups" => args.remaining() != 1,
+                    "consumers" => args.remaining() != 2,
+                    _ => {
+                        return Err(Error::Msg(format!(
+                            "unknown subcommand '{subcommand}'. Try XINFO HELP."
+                        )))
+                    }
+                };
+                if wrong_arity {
+                    return Err(Error::Msg(format!(
+                        "unknown subcommand or wrong number of arguments for '{subcommand}'. Try XINFO HELP."
+                    )));
+                }
+                let key = args.next_key()?;
+                match lowercase.as_str() {
+                    "stream" => InfoStream { key },
+                    "groups" => InfoGroups { key },
+                    _ => InfoConsumers {
+                        key,
+                        group: args.next_bytes()?,
+                    },
+                }
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(command))
+    }
+}
+
+/// Parses `XREAD` and `XREADGROUP`, which share their options and stream list.
+fn parse_read(args: &mut Args) -> Result<StreamCommand> {
+    let is_group = args.name() == "xreadgroup";
+    args.check_arity(if is_group { -7 } else { -4 })?;
+    let mut group = None;
+    let mut count = None;
+    let mut block = None;
+    let mut noack = false;
+    loop {
+        match args.next_token()?.as_str() {
+            "GROUP" if args.remaining() >= 2 => {
+                if !is_group {
+                    return Err(Error::Msg(
+                        "The GROUP option is only supported by XREADGROUP. You called XREAD instead."
+                            .to_string(),
+                    ));
+                }
+                group = Some((args.next_bytes()?, args.next_bytes()?));
+            }
+            "COUNT" => {
+                let limit = args.next_integer()?.max(0) as usize;
+                count = (limit > 0).then_some(limit);
+            }
+            "BLOCK" => block = Some(parse_block(args)?),
+            "NOACK" if is_group => noack = true,
+            "STREAMS" => break,
+            _ => return Err(Error::Syntax),
+        }
+    }
+    let group = match group {
+        Some(group) => Some(group),
+        None if is_group => {
+            return Err(Error::Msg(
+                "Missing GROUP option for XREADGROUP".to_string(),
+            ))
+        }
+        None => None,
+    };
+    if args.remaining() == 0 || args.remaining() % 2 != 0 {
+        let (name, new) = if is_group {
+            ("xreadgroup", '>')
+        } else {
+            ("xread", '$')
+        };
+        return Err(Error::Msg(format!(
+            "Unbalanced '{name}' list of streams: for each stream key an ID or '{new}' must be specified."
+        )));
+    }
+    let keys = (0..args.remaining() / 2)
+        .map(|_| args.next_key())
+        .collect::<Result<Vec<_>>>()?;
+    let mut streams = Vec::with_capacity(keys.len());
+    for key in keys {
+        let id = match (&args.next_bytes()?[..], is_group) {
+            (b">", true) | (b"$", false) => None,
+            (b">", false) => {
+                return Err(Error::Msg(
+                    "The > ID can be specified only when calling XREADGROUP using the GROUP <group> <consumer> option."
+                        .to_string(),
+                ))
+            }
+            (b"$", true) => {
+                return Err(Error::Msg(
+                    "The $ ID is meaningless in the context of XREADGROUP: you want to read the history of this consumer by specifying a proper ID, or use the > ID to get new messages. The $ ID would just return an empty result set."
+                        .to_string(),
+                ))
+            }
+            (id, _) => Some(parse_id(id, 0)?),
+        };
+        streams.push((key, id));
+    }
+    Ok(match group {
+        Some((group, consumer)) => StreamCommand::ReadGroup {
+            group,
+            consumer,
+            streams,
+            count,
+            noack,
+            block,
+        },
+        None => StreamCommand::Read {
+            streams,
+            count,
+            block,
+        },
+    })
+}
+
+/// Parses the milliseconds of `BLOCK`.
+fn parse_block(args: &mut Args) -> Result<Duration> {
+    let millis = parse_integer(&args.next_bytes()?)
+        .ok_or_else(|| Error::Msg("timeout is not an integer or out of range".to_string()))?;
+    if millis < 0 {
+        return Err(Error::Msg("timeout is negative".to_string()));
+    }
+    Ok(Duration::from_millis(millis as u64))
+}
+
+/// Parses `MAXLEN|MINID [=|~] threshold [LIMIT count]`.
+fn parse_trim(args: &mut Args) -> Result<StreamTrim> {
+    let strategy = args.next_token()?;
+    let approximate = match args.peek_token().as_deref() {
+        Some(operator @ ("~" | "=")) => {
+            let approximate = operator == "~";
+            args.next_resp()?;
+            approximate
+        }
+        _ => false,
+    };
+    let strategy = if strategy == "MAXLEN" {
+        let max_len = usize::try_from(args.next_integer()?)
+            .map_err(|_| Error::Msg("The MAXLEN argument must be >= 0.".to_string()))?;
+        TrimStrategy::MaxLen(max_len)
+    } else {
+        TrimStrategy::MinId(parse_id(&args.next_bytes()?, 0)?)
+    };
+
+    let mut limit = None;
+    if args.peek_token().as_deref() == Some("LIMIT") {
+        args.next_resp()?;
+        let count = usize::try_from(args.next_integer()?)
+            .map_err(|_| Error::Msg("The LIMIT argument must be >= 0.".to_string()))?;
+        if !approximate {
+            return Err(Error::Msg(
+                "syntax error, LIMIT cannot be used without the special ~ option".to_string(),
+            ));
+        }
+        limit = Some(count);
+    }
+    Ok(StreamTrim {
+        strategy,
+        approximate,
+        limit,
+    })
+}
+
+/// Parses `ms-seq`, or `ms` alone taking `missing_seq` as the sequence number.
+fn parse_id(id: &[u8], missing_seq: u64) -> Result<StreamId> {
+    let parse = |part: &str| part.parse::<u64>().ok();
+    let id = std::str::from_utf8(id).map_err(|_| Error::InvalidStreamId)?;
+    let parsed = match id.split_once('-') {
+        Some((ms, seq)) => parse(ms).zip(parse(seq)),
+        None => parse(id).map(|ms| (ms, missing_seq)),
+    };
+    let (ms, seq) = parsed.ok_or(Error::InvalidStreamId)?;
+    Ok(StreamId { ms, seq })
+}
+
+/// Parses the ID given to `XADD`: `*`, `ms-*` or an explicit ID.
+fn parse_xadd_id(id: &[u8]) -> Result<XAddId> {
+    if id == b"*" {
+        return Ok(XAddId::Auto);
+    }
+    match id.strip_suffix(b"-*") {
+        // Only the milliseconds may come before `-*`, not a whole ID.
+        Some(ms) => std::str::from_utf8(ms)
+            .ok()
+            .and_then(|ms| ms.parse().ok())
+            .map(XAddId::AutoSeq)
+            .ok_or(Error::InvalidStreamId),
+        None => Ok(XAddId::Explicit(parse_id(id, 0)?)),
+    }
+}
+
+/// Parses the start of a range: `-`, an ID missing its sequence number meaning the
+/// first one of that millisecond, or an ID prefixed with `(` to exclude it.
+fn parse_range_start(id: &[u8]) -> Result<StreamId> {
+    match id {
+        b"-" => Ok(StreamId::MIN),
+        b"+" => Ok(StreamId::MAX),
+        _ => match id.strip_prefix(b"(") {
+            Some(id) => parse_id(id, 0)?
+                .next()
+                .ok_or_else(|| Error::Msg("invalid start ID for the interval".to_string())),
+            None => parse_id(id, 0),
+        },
+    }
+}
+
+/// Parses the end of a range like `parse_range_start`, an ID missing its sequence
+/// number meaning the last one of that millisecond.
+fn parse_range_end(id: &[u8]) -> Result<StreamId> {
+    match id {
+        b"-" => Ok(StreamId::MIN),
+        b"+" => Ok(StreamId::MAX),
+        _ => match id.strip_prefix(b"(") {
+            Some(id) => parse_id(id, u64::MAX)?
+                .previous()
+                .ok_or_else(|| Error::Msg("invalid end ID for the interval".to_string())),
+            None => parse_id(id, u64::MAX),
+        },
+    }
+}
+
+/// Parses the minimum idle time of `XCLAIM` and `XAUTOCLAIM` in milliseconds.
+fn parse_min_idle(args: &mut Args) -> Result<u64> {
+    let min_idle = args.next_integer().map_err(|_| {
+        Error::Msg(format!(
+            "Invalid min-idle-time argument for {}",
+            args.name().to_uppercase()
+        ))
+    })?;
+    Ok(min_idle.max(0) as u64)
+}
+
+fn id_resp(id: StreamId) -> RESP {
+    RESP::Bulk(id.to_string().into())
+}
+
+/// Renders an entry as its ID along with its fields and values, `None` fields
+/// standing for an entry since deleted.
+fn entry_resp(id: StreamId, fields: Option<StreamFields>) -> RESP {
+    let fields = match fields {
+        Some(fields) => RESP::Array(
+            fields
+                .into_iter()
+                .flat_map(|(field, value)| [RESP::Bulk(field), RESP::Bulk(value)])
+                .collect(),
+        ),
+        None => RESP::NullArray,
+    };
+    RESP::Array(vec![id_resp(id), fields])
+}
+
+/// Renders entries as an array of their ID along with their fields and values.
+fn entries_array(entries: Vec<(StreamId, StreamFields)>) -> RESP {
+    RESP::Array(
+        entries
+            .into_iter()
+            .map(|(id, fields)| entry_resp(id, Some(fields)))
+            .collect(),
+    )
+}
+
+/// The reply of `XREAD` and `XREADGROUP`, `None` if nothing was read.
+fn reads_resp(reads: Vec<StreamRead>) -> Option<RESP> {
+    if reads.is_empty() {
+        return None;
+    }
+    Some(RESP::Array(
+        reads
+            .into_iter()
+            .map(|(key, entries)| {
+                let entries = entries
+                    .into_iter()
+                    .map(|(id, fields)| entry_resp(id, fields))
+                    .collect();
+                RESP::Array(vec![RESP::Bulk(key), RESP::Array(entries)])
+            })
+            .collect(),
+    ))
+}
+
+/// Renders a map the way RESP2 does, as an array alternating its keys and values.
+fn map_resp(pairs: Vec<(&str, RESP)>) -> RESP {
+    RESP::Array(
+        pairs
+            .into_iter()
+            .flat_map(|(key, value)| [RESP::Bulk(Bytes::copy_from_slice(key.as_bytes())), value])
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        command::{bulks, run},
+        db::Db,
+        resp::RESP,
+    };
+
+    fn error(message: &str) -> RESP {
+        RESP::Error(message.to_string())
+    }
+
+    #[test]
+    fn add_and_range_replies() {
+        let mut db = Db::new();
+        assert_eq!(
+            run(&mut db, &["XADD", "s", "NOMKSTREAM", "*", "a", "1"]),
+            RESP::Null
+        );
+        assert_eq!(run(&mut db, &["XRANGE", "s", "-", "+"]), bulks(&[]));
+        assert_eq!(
+            run(&mut db, &["XADD", "s", "0-0", "a", "1"]),
+            error("ERR The ID specified in XADD must be greater than 0-0")
+        );
+        run(&mut db, &["XADD", "s", "5-1", "a", "1"]);
+        assert_eq!(
+            run(&mut db, &["XADD", "s", "5-1", "a", "1"]),
+            error(
+                "ERR The ID specified in XADD is equal or smaller than the target stream top item"
+            )
+        );
+        assert_eq!(
+            run(&mut db, &["XADD", "s", "5-*-1", "a", "1"]),
+            error("ERR Invalid stream ID specified as stream command argument")
+        );
+        assert_eq!(
+            run(&mut db, &["XADD", "s", "MAXLEN", "1", "5-*", "b", "2"]),
+            RESP::Bulk("5-2".into())
+        );
+        assert_eq!(
+            run(&mut db, &["XRANGE", "s", "-", "+"]),
+            RESP::Array(vec![RESP::Array(vec![
+                RESP::Bulk("5-2".into()),
+                bulks(&["b", "2"])
+            ])])
+        );
+    }
+
+    #[test]
+    fn group_replies() {
+        let mut db = Db::new();
+        let read_new = ["XREADGROUP", "GROUP", "g", "c", "STREAMS", "s", ">"];
+        assert_eq!(
+            run(&mut db, &["XGROUP", "CREATE", "s", "g", "$"]),
+            error(
+                "ERR The XGROUP subcommand requires the key to exist. Note that for CREATE you \
+                 may want to use the MKSTREAM option to create an empty stream automatically."
+            )
+        );
+        assert_eq!(
+            run(&mut db, &read_new),
+            error("NOGROUP No such key 's' or consumer group 'g' in XREADGROUP with GROUP option")
+        );
+        run(&mut db, &["XGROUP", "CREATE", "s", "g", "$", "MKSTREAM"]);
+        assert_eq!(
+            run(&mut db, &["XGROUP", "CREATE", "s", "g", "$"]),
+            error("BUSYGROUP Consumer Group name already exists")
+        );
+        assert_eq!(run(&mut db, &read_new), RESP::NullArray);
+        assert_eq!(
+            run(&mut db, &["XPENDING", "s", "g"]),
+            RESP::Array(vec![
+                RESP::Integer(0),
+                RESP::Null,
+                RESP::Null,
+                RESP::NullArray
+            ])
+        );
+
+        // A delivered entry deleted since is read back from the PEL without fields.
+        run(&mut db, &["XADD", "s", "1-1", "a", "1"]);
+        run(&mut db, &read_new);
+        run(&mut db, &["XDEL", "s", "1-1"]);
+        let entry = RESP::Array(vec![RESP::Bulk("1-1".into()), RESP::NullArray]);
+        assert_eq!(
+            run(
+                &mut db,
+                &["XREADGROUP", "GROUP", "g", "c", "STREAMS", "s", "0"]
+            ),
+            RESP::Array(vec![RESP::Array(vec![
+                RESP::Bulk("s".into()),
+                RESP::Array(vec![entry])
+            ])])
+        );
+    }
+
+    #[test]
+    fn read_replies() {
+        let mut db = Db::new();
+        run(&mut db, &["XADD", "s", "1-1", "a", "1"]);
+        assert_eq!(
+            run(&mut db, &["XREAD", "STREAMS", "s", "$"]),
+            RESP::NullArray
+        );
+        assert_eq!(
+            run(&mut db, &["XREAD", "BLOCK", "10", "STREAMS", "s", "1-1"]),
+            RESP::NullArray
+        );
+        assert_eq!(
+            run(
+                &mut db,
+                &["XREAD", "COUNT", "1", "STREAMS", "s", "missing", "0", "0"]
+            ),
+            RESP::Array(vec![RESP::Array(vec![
+                RESP::Bulk("s".into()),
+                RESP::Array(vec![RESP::Array(vec![
+                    RESP::Bulk("1-1".into()),
+                    bulks(&["a", "1"])
+                ])])
+            ])])
+        );
+        assert_eq!(
+            run(&mut db, &["XREAD", "STREAMS", "s", ">"]),
+            error(
+                "ERR The > ID can be specified only when calling XREADGROUP using the GROUP \
+                 <group> <consumer> option."
+            )
+        );
+        assert_eq!(
+            run(
+                &mut db,
+                &["XREADGROUP", "GROUP", "g", "c", "STREAMS", "s", "$"]
+            ),
+            error(
+                "ERR The $ ID is meaningless in the context of XREADGROUP: you want to read the \
+                 history of this consumer by specifying a proper ID, or use the > ID to get new \
+                 messages. The $ ID would just return an empty result set."
+            )
+        );
+        assert_eq!(
+            run(&mut db, &["XREAD", "STREAMS", "s", "t", "0"]),
+            error(
+                "ERR Unbalanced 'xread' list of streams: for each stream key an ID or '$' must \
+                 be specified."
+            )
+        );
+    }
+}
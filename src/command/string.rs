@@ -0,0 +1,341 @@
+use bytes::Bytes;
+
+use crate::{
+    db::{Db, SetCondition, SetExpiry},
+    error::{Error, Result},
+    resp::RESP,
+};
+
+use super::{args::Args, TimeUnit};
+
+#[derive(Debug, Clone)]
+pub enum StringCommand {
+    Set {
+        key: Bytes,
+        value: Bytes,
+        condition: SetCondition,
+        expiry: SetExpiry,
+        /// `GET`, reply with the previous value instead of `OK`.
+        get: bool,
+    },
+    Get {
+        key: Bytes,
+    },
+    /// `INCR`, `DECR`, `INCRBY` and `DECRBY`.
+    IncrBy {
+        key: Bytes,
+        delta: i64,
+    },
+    IncrByFloat {
+        key: Bytes,
+        delta: f64,
+    },
+    Append {
+        key: Bytes,
+        value: Bytes,
+    },
+    Strlen {
+        key: Bytes,
+    },
+    GetRange {
+        key: Bytes,
+        start: i64,
+        end: i64,
+    },
+    SetRange {
+        key: Bytes,
+        offset: usize,
+        value: Bytes,
+    },
+    GetDel {
+        key: Bytes,
+    },
+    GetEx {
+        key: Bytes,
+        /// `SetExpiry::Keep` when no option was given.
+        expiry: SetExpiry,
+    },
+    /// `SETNX`, replies with whether the key was set rather than `OK`.
+    SetNx {
+        key: Bytes,
+        value: Bytes,
+    },
+    MGet {
+        keys: Vec<Bytes>,
+    },
+    /// `MSET` and `MSETNX`.
+    MSet {
+        pairs: Vec<(Bytes, Bytes)>,
+        /// `MSETNX`, only set the keys if none of them exist.
+        only_if_none_exist: bool,
+    },
+}
+
+impl StringCommand {
+    pub(super) fn execute(self, db: &mut Db) -> Result<RESP> {
+        use StringCommand::*;
+        let resp = match self {
+            Set {
+                key,
+                value,
+                condition,
+                expiry,
+                get,
+            } => {
+                let outcome = db.set(key, value, condition, expiry, get)?;
+                if get {
+                    outcome.previous.map_or(RESP::Null, RESP::Bulk)
+                } else if outcome.written {
+                    RESP::Simple("OK".to_string())
+                } else {
+                    RESP::Null
+                }
+            }
+            Get { key } => db.get(&key)?.map_or(RESP::Null, RESP::Bulk),
+            IncrBy { key, delta } => RESP::Integer(db.incr_by(&key, delta)?),
+            IncrByFloat { key, delta } => RESP::Bulk(db.incr_by_float(&key, delta)?),
+            Append { key, value } => RESP::Integer(db.append(&key, &value)? as i64),
+            Strlen { key } => RESP::Integer(db.strlen(&key)? as i64),
+            GetRange { key, start, end } => RESP::Bulk(db.getrange(&key, start, end)?),
+            SetRange { key, offset, value } => {
+                RESP::Integer(db.setrange(&key, offset, &value)? as i64)
+            }
+            GetDel { key } => db.getdel(&key)?.map_or(RESP::Null, RESP::Bulk),
+            GetEx { key, expiry } => db.getex(&key, expiry)?.map_or(RESP::Null, RESP::Bulk),
+            SetNx { key, value } => {
+                let outcome = db.set(
+                    key,
+                    value,
+                    SetCondition::IfMissing,
+                    SetExpiry::Persist,
+                    false,
+                )?;
+                RESP::Integer(outcome.written as i64)
+            }
+            MGet { keys } => RESP::Array(
+                db.mget(&keys)
+                    .into_iter()
+                    .map(|data| data.map_or(RESP::Null, RESP::Bulk))
+                    .collect(),
+            ),
+            MSet {
+                pairs,
+                only_if_none_exist,
+            } => {
+                let written = db.mset(pairs, only_if_none_exist);
+                if only_if_none_exist {
+                    RESP::Integer(written as i64)
+                } else {
+                    RESP::Simple("OK".to_string())
+                }
+            }
+        };
+        Ok(resp)
+    }
+
+    /// Parses the command if it's a string command, returns `None` otherwise.
+    pub(super) fn parse(args: &mut Args) -> Result<Option<StringCommand>> {
+        use StringCommand::*;
+        let command = match args.name() {
+            "set" => {
+                args.check_arity(-3)?;
+                let key = args.next_key()?;
+                let value = args.next_bytes()?;
+
+                let mut condition = SetCondition::Always;
+                let mut expiry = SetExpiry::Persist;
+                let mut get = false;
+                while args.remaining() > 0 {
+                    let option = args.next_token()?;
+                    match option.as_str() {
+                        "NX" if condition == SetCondition::Always => {
+                            condition = SetCondition::IfMissing
+                        }
+                        "XX" if condition == SetCondition::Always => {
+                            condition = SetCondition::IfExists
+                        }
+                        "GET" if !get => get = true,
+                        "KEEPTTL" if expiry == SetExpiry::Persist => expiry = SetExpiry::Keep,
+                        "EX" | "PX" | "EXAT" | "PXAT" if expiry == SetExpiry::Persist => {
+                            expiry = parse_expiry(&option, args)?;
+                        }
+                        _ => return Err(Error::Syntax),
+                    }
+                }
+
+                Set {
+                    key,
+                    value,
+                    condition,
+                    expiry,
+                    get,
+                }
+            }
+            // `GETSET key value` is the same as `SET key value GET`.
+            "getset" => {
+                args.check_arity(3)?;
+                Set {
+                    key: args.next_key()?,
+                    value: args.next_bytes()?,
+                    condition: SetCondition::Always,
+                    expiry: SetExpiry::Persist,
+                    get: true,
+                }
+            }
+            "get" => {
+                args.check_arity(2)?;
+                Get {
+                    key: args.next_key()?,
+                }
+            }
+            "incr" | "decr" => {
+                args.check_arity(2)?;
+                IncrBy {
+                    delta: if args.name() == "incr" { 1 } else { -1 },
+                    key: args.next_key()?,
+                }
+            }
+            "incrby" => {
+                args.check_arity(3)?;
+                IncrBy {
+                    key: args.next_key()?,
+                    delta: args.next_integer()?,
+                }
+            }
+            "decrby" => {
+                args.check_arity(3)?;
+                let key = args.next_key()?;
+                let delta = args
+                    .next_integer()?
+                    .checked_neg()
+                    .ok_or_else(|| Error::Msg("decrement would overflow".to_string()))?;
+                IncrBy { key, delta }
+            }
+            "incrbyfloat" => {
+                args.check_arity(3)?;
+                IncrByFloat {
+                    key: args.next_key()?,
+                    delta: args.next_float()?,
+                }
+            }
+            "append" => {
+                args.check_arity(3)?;
+                Append {
+                    key: args.next_key()?,
+                    value: args.next_bytes()?,
+                }
+            }
+            "strlen" => {
+                args.check_arity(2)?;
+                Strlen {
+                    key: args.next_key()?,
+                }
+            }
+            "getrange" | "substr" => {
+                args.check_arity(4)?;
+                GetRange {
+                    key: args.next_key()?,
+                    start: args.next_integer()?,
+                    end: args.next_integer()?,
+                }
+            }
+            "setrange" => {
+                args.check_arity(4)?;
+                let key = args.next_key()?;
+                let offset = usize::try_from(args.next_integer()?)
+                    .map_err(|_| Error::OutOfRange("offset is out of range".to_string()))?;
+                SetRange {
+                    key,
+                    offset,
+                    value: args.next_bytes()?,
+                }
+            }
+            "getdel" => {
+                args.check_arity(2)?;
+                GetDel {
+                    key: args.next_key()?,
+                }
+            }
+            "getex" => {
+                args.check_arity(-2)?;
+                let key = args.next_key()?;
+                let mut expiry = SetExpiry::Keep;
+                while args.remaining() > 0 {
+                    let option = args.next_token()?;
+                    match option.as_str() {
+                        "PERSIST" if expiry == SetExpiry::Keep => expiry = SetExpiry::Persist,
+                        "EX" | "PX" | "EXAT" | "PXAT" if expiry == SetExpiry::Keep => {
+                            expiry = parse_expiry(&option, args)?;
+                        }
+                        _ => return Err(Error::Syntax),
+                    }
+                }
+                GetEx { key, expiry }
+            }
+            "setnx" => {
+                args.check_arity(3)?;
+                SetNx {
+                    key: args.next_key()?,
+                    value: args.next_bytes()?,
+                }
+            }
+            "setex" | "psetex" => {
+                args.check_arity(4)?;
+                let key = args.next_key()?;
+                let option = if args.name() == "setex" { "EX" } else { "PX" };
+                let expiry = parse_expiry(option, args)?;
+                Set {
+                    key,
+                    value: args.next_bytes()?,
+                    condition: SetCondition::Always,
+                    expiry,
+                    get: false,
+                }
+            }
+            "mget" => {
+                args.check_arity(-2)?;
+                MGet {
+                    keys: args.rest_keys()?,
+                }
+            }
+            "mset" | "msetnx" => {
+                args.check_arity(-3)?;
+                if args.remaining() % 2 != 0 {
+                    return Err(Error::WrongArity(args.name().to_string()));
+                }
+                let only_if_none_exist = args.name() == "msetnx";
+                let mut pairs = Vec::with_capacity(args.remaining() / 2);
+                while args.remaining() > 0 {
+                    pairs.push((args.next_key()?, args.next_bytes()?));
+                }
+                MSet {
+                    pairs,
+                    only_if_none_exist,
+                }
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(command))
+    }
+}
+
+/// Parses the time following an `EX`, `PX`, `EXAT` or `PXAT` `option`.
+fn parse_expiry(option: &str, args: &mut Args) -> Result<SetExpiry> {
+    let name = args.name().to_string();
+    let invalid_expire_time = || Error::InvalidExpireTime(name.clone());
+    let time = args.next_integer()?;
+    if time <= 0 {
+        return Err(invalid_expire_time());
+    }
+    let unit = if option.starts_with('E') {
+        TimeUnit::Seconds
+    } else {
+        TimeUnit::Milliseconds
+    };
+    let millis = unit.to_millis(time).ok_or_else(invalid_expire_time)? as u64;
+    if option.ends_with("AT") {
+        Ok(SetExpiry::At(millis))
+    } else {
+        Ok(SetExpiry::In(millis))
+    }
+}
@@ -0,0 +1,771 @@
+use std::time::Duration;
+
+use bytes::Bytes;
+
+use crate::{
+    db::{Db, LexBound, ScoreBound, SetOp, ZAddFlags, ZAggregate, ZRange},
+    error::{Error, Result},
+    resp::RESP,
+    utils::{format_double, parse_float, parse_integer},
+};
+
+use super::{args::Args, BlockOn};
+
+#[derive(Debug, Clone)]
+pub enum ZSetCommand {
+    /// `ZADD` and `ZINCRBY`, which is `ZADD key INCR increment member`.
+    Add {
+        key: Bytes,
+        pairs: Vec<(f64, Bytes)>,
+        flags: ZAddFlags,
+    },
+    Score {
+        key: Bytes,
+        member: Bytes,
+    },
+    MScore {
+        key: Bytes,
+        members: Vec<Bytes>,
+    },
+    Card {
+        key: Bytes,
+    },
+    Rem {
+        key: Bytes,
+        members: Vec<Bytes>,
+    },
+    /// `ZRANK` and `ZREVRANK`.
+    Rank {
+        key: Bytes,
+        member: Bytes,
+        rev: bool,
+        /// `WITHSCORE`, reply with the score along with the rank.
+        with_score: bool,
+    },
+    /// `ZCOUNT` and `ZLEXCOUNT`.
+    Count {
+        key: Bytes,
+        range: ZRange,
+    },
+    /// `ZRANGE` along with the older `ZREVRANGE`, `ZRANGEBYSCORE`, `ZREVRANGEBYSCORE`,
+    /// `ZRANGEBYLEX` and `ZREVRANGEBYLEX`.
+    Range {
+        key: Bytes,
+        range: ZRange,
+        rev: bool,
+        /// `LIMIT offset count`, a negative offset matching nothing and a negative
+        /// count everything.
+        limit: Option<(i64, i64)>,
+        with_scores: bool,
+    },
+    /// `ZREMRANGEBYRANK`, `ZREMRANGEBYSCORE` and `ZREMRANGEBYLEX`.
+    RemRange {
+        key: Bytes,
+        range: ZRange,
+    },
+    /// `ZUNION`, `ZINTER` and `ZDIFF`.
+    Op {
+        op: SetOp,
+        keys: Vec<Bytes>,
+        weights: Option<Vec<f64>>,
+        aggregate: ZAggregate,
+        with_scores: bool,
+    },
+    /// `ZUNIONSTORE`, `ZINTERSTORE` and `ZDIFFSTORE`.
+    OpStore {
+        op: SetOp,
+        destination: Bytes,
+        keys: Vec<Bytes>,
+        weights: Option<Vec<f64>>,
+        aggregate: ZAggregate,
+    },
+    /// `ZPOPMIN` and `ZPOPMAX`.
+    Pop {
+        key: Bytes,
+        rev: bool,
+        count: Option<usize>,
+    },
+    /// `ZMPOP`, `BZMPOP`, `BZPOPMIN` and `BZPOPMAX`.
+    MPop {
+        keys: Vec<Bytes>,
+        /// Pop the highest scores rather than the lowest.
+        rev: bool,
+        /// `None` for `BZPOPMIN` and `BZPOPMAX` whose reply is the key, the member
+        /// and its score.
+        count: Option<usize>,
+        /// Set for the blocking variants, a zero timeout blocks forever.
+        timeout: Option<Duration>,
+    },
+}
+
+/// How the bounds of a range are interpreted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RangeBy {
+    Rank,
+    Score,
+    Lex,
+}
+
+impl ZSetCommand {
+    pub(super) fn execute(self, db: &mut Db) -> Result<RESP> {
+        use ZSetCommand::*;
+        let resp = match self {
+            Add { key, pairs, flags } if flags.incr => {
+                let (delta, member) = pairs.into_iter().next().unwrap();
+                db.zincrby(key, member, delta, flags)?
+                    .map_or(RESP::Null, double)
+            }
+            Add { key, pairs, flags } => RESP::Integer(db.zadd(key, pairs, flags)? as i64),
+            Score { key, member } => db.zscore(&key, &member)?.map_or(RESP::Null, double),
+            MScore { key, members } => RESP::Array(
+                db.zmscore(&key, &members)?
+                    .into_iter()
+                    .map(|score| score.map_or(RESP::Null, double))
+                    .collect(),
+            ),
+            Card { key } => RESP::Integer(db.zcard(&key)? as i64),
+            Rem { key, members } => RESP::Integer(db.zrem(&key, &members)? as i64),
+            Rank {
+                key,
+                member,
+                rev,
+                with_score,
+            } => match db.zrank(&key, &member, rev)? {
+                Some((rank, score)) if with_score => {
+                    RESP::Array(vec![RESP::Integer(rank as i64), double(score)])
+                }
+                Some((rank, _)) => RESP::Integer(rank as i64),
+                None => RESP::Null,
+            },
+            Count { key, range } => RESP::Integer(db.zcount(&key, &range)? as i64),
+            Range {
+                key,
+                range,
+                rev,
+                limit,
+                with_scores,
+            } => {
+                let (offset, count) = limit.unwrap_or((0, -1));
+                let members = match usize::try_from(offset) {
+                    Ok(offset) => {
+                        db.zrange(&key, &range, rev, offset, usize::try_from(count).ok())?
+                    }
+                    Err(_) => Vec::new(),
+                };
+                let mut resp = Vec::with_capacity(members.len() * (1 + with_scores as usize));
+                for (member, score) in members {
+                    resp.push(RESP::Bulk(member));
+                    if with_scores {
+                        resp.push(double(score));
+                    }
+                }
+                RESP::Array(resp)
+            }
+            RemRange { key, range } => RESP::Integer(db.zremrange(&key, &range)? as i64),
+            Op {
+                op,
+                keys,
+                weights,
+                aggregate,
+                with_scores,
+            } => {
+                let members = db.zset_op(op, &keys, weights.as_deref(), aggregate)?;
+                if with_scores {
+                    RESP::Array(with_scores_array(members))
+                } else {
+                    RESP::Array(
+                        members
+                            .into_iter()
+                            .map(|(member, _)| RESP::Bulk(member))
+                            .collect(),
+                    )
+                }
+            }
+            OpStore {
+                op,
+                destination,
+                keys,
+                weights,
+                aggregate,
+            } => RESP::Integer(db.zset_op_store(
+                op,
+                destination,
+                &keys,
+                weights.as_deref(),
+                aggregate,
+            )? as i64),
+            Pop { key, rev, count } => {
+                let popped = db.zpop(&key, rev, count.unwrap_or(1))?;
+                RESP::Array(with_scores_array(popped.unwrap_or_default()))
+            }
+            command @ MPop { .. } => command.try_execute(db)?.unwrap_or(RESP::NullArray),
+        };
+        Ok(resp)
+    }
+
+    /// Executes the command, `None` if it pops from sorted sets that are all empty.
+    pub(super) fn try_execute(self, db: &mut Db) -> Result<Option<RESP>> {
+        use ZSetCommand::*;
+        let resp = match self {
+            MPop {
+                keys, rev, count, ..
+            } => db
+                .zmpop(&keys, rev, count.unwrap_or(1))?
+                .map(|(key, popped)| match count {
+                    Some(_) => RESP::Array(vec![
+                        RESP::Bulk(key),
+                        RESP::Array(
+                            popped
+                                .into_iter()
+                                .map(|pair| RESP::Array(with_scores_array(vec![pair])))
+                                .collect(),
+                        ),
+                    ]),
+                    None => {
+                        let mut resp = vec![RESP::Bulk(key)];
+                        resp.extend(with_scores_array(popped));
+                        RESP::Array(resp)
+                    }
+                }),
+            command => Some(command.execute(db)?),
+        };
+        Ok(resp)
+    }
+
+    /// What the command waits for if it's a blocking one, see `Command::block_on`.
+    pub(super) fn block_on(&self) -> Option<BlockOn> {
+        match self {
+            ZSetCommand::MPop {
+                keys,
+                timeout: Some(timeout),
+                ..
+            } => Some(BlockOn::new(keys.clone(), *timeout)),
+            _ => None,
+        }
+    }
+
+    /// Parses the command if it's a sorted set command, returns `None` otherwise.
+    pub(super) fn parse(args: &mut Args) -> Result<Option<ZSetCommand>> {
+        use ZSetCommand::*;
+        let command = match args.name() {
+            "zadd" => {
+                args.check_arity(-4)?;
+                let key = args.next_key()?;
+                let mut flags = ZAddFlags::default();
+                while let Some(option) = args.peek_token() {
+                    match option.as_str() {
+                        "NX" => flags.nx = true,
+                        "XX" => flags.xx = true,
+                        "GT" => flags.gt = true,
+                        "LT" => flags.lt = true,
+                        "CH" => flags.ch = true,
+                        "INCR" => flags.incr = true,
+                        _ => break,
+                    }
+                    args.next_resp()?;
+                }
+                if args.remaining() == 0 || args.remaining() % 2 != 0 {
+                    return Err(Error::Syntax);
+                }
+                if flags.nx && flags.xx {
+                    return Err(Error::Msg(
+                        "XX and NX options at the same time are not compatible".to_string(),
+                    ));
+                }
+                if [flags.gt, flags.lt, flags.nx]
+                    .iter()
+                    .filter(|&&flag| flag)
+                    .count()
+                    > 1
+                {
+                    return Err(Error::Msg(
+                        "GT, LT, and/or NX options at the same time are not compatible".to_string(),
+                    ));
+                }
+                if flags.incr && args.remaining() > 2 {
+                    return Err(Error::Msg(
+                        "INCR option supports a single increment-element pair".to_string(),
+                    ));
+                }
+                let mut pairs = Vec::with_capacity(args.remaining() / 2);
+                while args.remaining() > 0 {
+                    pairs.push((args.next_float()?, args.next_bytes()?));
+                }
+                Add { key, pairs, flags }
+            }
+            "zincrby" => {
+                args.check_arity(4)?;
+                let key = args.next_key()?;
+                let delta = args.next_float()?;
+                let flags = ZAddFlags {
+                    incr: true,
+                    ..Default::default()
+                };
+                Add {
+                    key,
+                    pairs: vec![(delta, args.next_bytes()?)],
+                    flags,
+                }
+            }
+            "zscore" => {
+                args.check_arity(3)?;
+                Score {
+                    key: args.next_key()?,
+                    member: args.next_bytes()?,
+                }
+            }
+            "zmscore" => {
+                args.check_arity(-3)?;
+                MScore {
+                    key: args.next_key()?,
+                    members: args.rest_bytes()?,
+                }
+            }
+            "zcard" => {
+                args.check_arity(2)?;
+                Card {
+                    key: args.next_key()?,
+                }
+            }
+            "zrem" => {
+                args.check_arity(-3)?;
+                Rem {
+                    key: args.next_key()?,
+                    members: args.rest_bytes()?,
+                }
+            }
+            "zrank" | "zrevrank" => {
+                args.check_arity(-3)?;
+                let rev = args.name() == "zrevrank";
+                let key = args.next_key()?;
+                let member = args.next_bytes()?;
+                let with_score = match args.remaining() {
+                    0 => false,
+                    1 if args.next_token()? == "WITHSCORE" => true,
+                    _ => return Err(Error::Syntax),
+                };
+                Rank {
+                    key,
+                    member,
+                    rev,
+                    with_score,
+                }
+            }
+            "zcount" | "zlexcount" => {
+                args.check_arity(4)?;
+                let by = if args.name() == "zcount" {
+                    RangeBy::Score
+                } else {
+                    RangeBy::Lex
+                };
+                let key = args.next_key()?;
+                let (min, max) = (args.next_bytes()?, args.next_bytes()?);
+                Count {
+                    key,
+                    range: parse_range(by, &min, &max)?,
+                }
+            }
+            "zrange" => parse_range_command(args, None, false)?,
+            "zrevrange" => parse_range_command(args, Some(RangeBy::Rank), true)?,
+            "zrangebyscore" => parse_range_command(args, Some(RangeBy::Score), false)?,
+            "zrevrangebyscore" => parse_range_command(args, Some(RangeBy::Score), true)?,
+            "zrangebylex" => parse_range_command(args, Some(RangeBy::Lex), false)?,
+            "zrevrangebylex" => parse_range_command(args, Some(RangeBy::Lex), true)?,
+            "zremrangebyrank" | "zremrangebyscore" | "zremrangebylex" => {
+                args.check_arity(4)?;
+                let by = match args.name() {
+                    "zremrangebyrank" => RangeBy::Rank,
+                    "zremrangebyscore" => RangeBy::Score,
+                    _ => RangeBy::Lex,
+                };
+                let key = args.next_key()?;
+                let (min, max) = (args.next_bytes()?, args.next_bytes()?);
+                RemRange {
+                    key,
+                    range: parse_range(by, &min, &max)?,
+                }
+            }
+            "zunion" | "zinter" | "zdiff" => {
+                args.check_arity(-3)?;
+                let op = op_from_name(args.name());
+                let keys = parse_num_keys(args)?;
+                let (weights, aggregate, with_scores) =
+                    parse_op_options(args, op, keys.len(), true)?;
+                Op {
+                    op,
+                    keys,
+                    weights,
+                    aggregate,
+                    with_scores,
+                }
+            }
+            "zunionstore" | "zinterstore" | "zdiffstore" => {
+                args.check_arity(-4)?;
+                let op = op_from_name(args.name());
+                let destination = args.next_key()?;
+                let keys = parse_num_keys(args)?;
+                let (weights, aggregate, _) = parse_op_options(args, op, keys.len(), false)?;
+                OpStore {
+                    op,
+                    destination,
+                    keys,
+                    weights,
+                    aggregate,
+                }
+            }
+            "zpopmin" | "zpopmax" => {
+                args.check_arity(-2)?;
+                if args.remaining() > 2 {
+                    return Err(Error::Syntax);
+                }
+                let rev = args.name() == "zpopmax";
+                let key = args.next_key()?;
+                let count = if args.remaining() > 0 {
+                    Some(args.next_count()?)
+                } else {
+                    None
+                };
+                Pop { key, rev, count }
+            }
+            "bzpopmin" | "bzpopmax" => {
+                args.check_arity(-3)?;
+                let rev = args.name() == "bzpopmax";
+                let mut keys = Vec::with_capacity(args.remaining() - 1);
+                while args.remaining() > 1 {
+                    keys.push(args.next_key()?);
+                }
+                MPop {
+                    keys,
+                    rev,
+                    count: None,
+                    timeout: Some(args.next_timeout()?),
+                }
+            }
+            "zmpop" | "bzmpop" => {
+                let blocking = args.name() == "bzmpop";
+                args.check_arity(if blocking { -5 } else { -4 })?;
+                let timeout = if blocking {
+                    Some(args.next_timeout()?)
+                } else {
+                    None
+                };
+                let num_keys = usize::try_from(args.next_integer()?)
+                    .ok()
+                    .filter(|&num_keys| num_keys > 0)
+                    .ok_or_else(|| Error::Msg("numkeys should be greater than 0".to_string()))?;
+                if num_keys >= args.remaining() {
+                    return Err(Error::Syntax);
+                }
+                let mut keys = Vec::with_capacity(num_keys);
+                for _ in 0..num_keys {
+                    keys.push(args.next_key()?);
+                }
+                let rev = match args.next_token()?.as_str() {
+                    "MIN" => false,
+                    "MAX" => true,
+                    _ => return Err(Error::Syntax),
+                };
+                let mut count = 1;
+                while args.remaining() > 0 {
+                    match args.next_token()?.as_str() {
+                        "COUNT" => {
+                            count = usize::try_from(args.next_integer()?)
+                                .ok()
+                                .filter(|&count| count > 0)
+                                .ok_or_else(|| {
+                                    Error::Msg("count should be greater than 0".to_string())
+                                })?;
+                        }
+                        _ => return Err(Error::Syntax),
+                    }
+                }
+                MPop {
+                    keys,
+                    rev,
+                    count: Some(count),
+                    timeout,
+                }
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(command))
+    }
+}
+
+/// Parses `ZRANGE` when `by` is `None`, otherwise one of the older commands it
+/// replaces which only take the `WITHSCORES` and `LIMIT` options.
+fn parse_range_command(args: &mut Args, by: Option<RangeBy>, mut rev: bool) -> Result<ZSetCommand> {
+    args.check_arity(-4)?;
+    let key = args.next_key()?;
+    let (start, stop) = (args.next_bytes()?, args.next_bytes()?);
+
+    let mut range_by = by.unwrap_or(RangeBy::Rank);
+    let mut limit = None;
+    let mut with_scores = false;
+    while args.remaining() > 0 {
+        match args.next_token()?.as_str() {
+            "WITHSCORES" => with_scores = true,
+            "LIMIT" if args.remaining() >= 2 => {
+                limit = Some((args.next_integer()?, args.next_integer()?));
+            }
+            "BYSCORE" if by.is_none() => range_by = RangeBy::Score,
+            "BYLEX" if by.is_none() => range_by = RangeBy::Lex,
+            "REV" if by.is_none() => rev = true,
+            _ => return Err(Error::Syntax),
+        }
+    }
+    if limit.is_some() && range_by == RangeBy::Rank {
+        return Err(Error::Msg(
+            "syntax error, LIMIT is only supported in combination with either BYSCORE or BYLEX"
+                .to_string(),
+        ));
+    }
+    if with_scores && range_by == RangeBy::Lex {
+        return Err(Error::Msg(
+            "syntax error, WITHSCORES not supported in combination with BYLEX".to_string(),
+        ));
+    }
+
+    // Reversed score and lex ranges take the maximum first.
+    let range = if rev && range_by != RangeBy::Rank {
+        parse_range(range_by, &stop, &start)?
+    } else {
+        parse_range(range_by, &start, &stop)?
+    };
+    Ok(ZSetCommand::Range {
+        key,
+        range,
+        rev,
+        limit,
+        with_scores,
+    })
+}
+
+fn op_from_name(name: &str) -> SetOp {
+    if name.starts_with("zinter") {
+        SetOp::Inter
+    } else if name.starts_with("zunion") {
+        SetOp::Union
+    } else {
+        SetOp::Diff
+    }
+}
+
+/// Parses the `numkeys` argument followed by that many keys.
+fn parse_num_keys(args: &mut Args) -> Result<Vec<Bytes>> {
+    let num_keys = args.next_integer()?;
+    if num_keys < 1 {
+        return Err(Error::Msg(format!(
+            "at least 1 input key is needed for '{}' command",
+            args.name()
+        )));
+    }
+    if num_keys as usize > args.remaining() {
+        return Err(Error::Syntax);
+    }
+    let mut keys = Vec::with_capacity(num_keys as usize);
+    for _ in 0..num_keys {
+        keys.push(args.next_key()?);
+    }
+    Ok(keys)
+}
+
+/// Parses the `WEIGHTS` and `AGGREGATE` options of a union or intersection of
+/// `num_keys` sets, along with `WITHSCORES` if the result isn't stored.
+fn parse_op_options(
+    args: &mut Args,
+    op: SetOp,
+    num_keys: usize,
+    allow_with_scores: bool,
+) -> Result<(Option<Vec<f64>>, ZAggregate, bool)> {
+    let mut weights = None;
+    let mut aggregate = ZAggregate::Sum;
+    let mut with_scores = false;
+    while args.remaining() > 0 {
+        match args.next_token()?.as_str() {
+            "WEIGHTS" if op != SetOp::Diff && args.remaining() >= num_keys => {
+                let mut parsed = Vec::with_capacity(num_keys);
+                for _ in 0..num_keys {
+                    let weight = parse_float(&args.next_bytes()?)
+                        .ok_or_else(|| Error::Msg("weight value is not a float".to_string()))?;
+                    parsed.push(weight);
+                }
+                weights = Some(parsed);
+            }
+            "AGGREGATE" if op != SetOp::Diff && args.remaining() > 0 => {
+                aggregate = match args.next_token()?.as_str() {
+                    "SUM" => ZAggregate::Sum,
+                    "MIN" => ZAggregate::Min,
+                    "MAX" => ZAggregate::Max,
+                    _ => return Err(Error::Syntax),
+                };
+            }
+            "WITHSCORES" if allow_with_scores => with_scores = true,
+            _ => return Err(Error::Syntax),
+        }
+    }
+    Ok((weights, aggregate, with_scores))
+}
+
+fn parse_range(by: RangeBy, min: &[u8], max: &[u8]) -> Result<ZRange> {
+    let range = match by {
+        RangeBy::Rank => ZRange::Rank {
+            start: parse_integer(min).ok_or(Error::NotAnInteger)?,
+            stop: parse_integer(max).ok_or(Error::NotAnInteger)?,
+        },
+        RangeBy::Score => {
+            let parse = |bound| {
+                parse_score_bound(bound)
+                    .ok_or_else(|| Error::Msg("min or max is not a float".to_string()))
+            };
+            ZRange::Score {
+                min: parse(min)?,
+                max: parse(max)?,
+            }
+        }
+        RangeBy::Lex => {
+            let parse = |bound| {
+                parse_lex_bound(bound)
+                    .ok_or_else(|| Error::Msg("min or max not valid string range item".to_string()))
+            };
+            ZRange::Lex {
+                min: parse(min)?,
+                max: parse(max)?,
+            }
+        }
+    };
+    Ok(range)
+}
+
+/// Parses a score, exclusive when prefixed with `(`.
+fn parse_score_bound(bound: &[u8]) -> Option<ScoreBound> {
+    match bound.strip_prefix(b"(") {
+        Some(score) => Some(ScoreBound {
+            score: parse_float(score)?,
+            exclusive: true,
+        }),
+        None => Some(ScoreBound {
+            score: parse_float(bound)?,
+            exclusive: false,
+        }),
+    }
+}
+
+/// Parses `-`, `+`, or a member prefixed with `[` or `(` for an inclusive or
+/// exclusive bound.
+fn parse_lex_bound(bound: &[u8]) -> Option<LexBound> {
+    match bound.first()? {
+        b'-' if bound.len() == 1 => Some(LexBound::Min),
+        b'+' if bound.len() == 1 => Some(LexBound::Max),
+        b'[' => Some(LexBound::Inclusive(Bytes::copy_from_slice(&bound[1..]))),
+        b'(' => Some(LexBound::Exclusive(Bytes::copy_from_slice(&bound[1..]))),
+        _ => None,
+    }
+}
+
+/// The members alternating with their scores, like the replies `WITHSCORES` asks for.
+fn with_scores_array(members: Vec<(Bytes, f64)>) -> Vec<RESP> {
+    let mut resp = Vec::with_capacity(members.len() * 2);
+    for (member, score) in members {
+        resp.push(RESP::Bulk(member));
+        resp.push(double(score));
+    }
+    resp
+}
+
+fn double(score: f64) -> RESP {
+    RESP::Bulk(format_double(score).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        command::{bulks, run},
+        db::Db,
+        resp::RESP,
+    };
+
+    #[test]
+    fn range_replies() {
+        let mut db = Db::new();
+        assert_eq!(run(&mut db, &["ZSCORE", "z", "a"]), RESP::Null);
+        assert_eq!(run(&mut db, &["ZRANK", "z", "a"]), RESP::Null);
+        assert_eq!(run(&mut db, &["ZRANGE", "z", "0", "-1"]), bulks(&[]));
+        run(&mut db, &["ZADD", "z", "1", "a", "2", "b", "2", "c"]);
+        assert_eq!(
+            run(
+                &mut db,
+                &[
+                    "ZRANGE",
+                    "z",
+                    "(1",
+                    "+inf",
+                    "BYSCORE",
+                    "LIMIT",
+                    "1",
+                    "1",
+                    "WITHSCORES"
+                ]
+            ),
+            bulks(&["c", "2"])
+        );
+        assert_eq!(
+            run(&mut db, &["ZRANGEBYLEX", "z", "[b", "+"]),
+            bulks(&["b", "c"])
+        );
+        assert_eq!(
+            run(&mut db, &["ZRANGE", "z", "0", "-1", "LIMIT", "0", "1"]),
+            RESP::Error(
+                "ERR syntax error, LIMIT is only supported in combination with either BYSCORE or \
+                 BYLEX"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            run(&mut db, &["ZRANGE", "z", "-", "+", "BYLEX", "WITHSCORES"]),
+            RESP::Error(
+                "ERR syntax error, WITHSCORES not supported in combination with BYLEX".to_string()
+            )
+        );
+        assert_eq!(
+            run(&mut db, &["ZRANGEBYLEX", "z", "b", "+"]),
+            RESP::Error("ERR min or max not valid string range item".to_string())
+        );
+    }
+
+    #[test]
+    fn aggregate_and_pop_replies() {
+        let mut db = Db::new();
+        assert_eq!(run(&mut db, &["ZPOPMIN", "z"]), bulks(&[]));
+        assert_eq!(run(&mut db, &["ZMPOP", "1", "z", "MIN"]), RESP::NullArray);
+        assert_eq!(run(&mut db, &["BZPOPMAX", "z", "0.01"]), RESP::NullArray);
+        run(&mut db, &["ZADD", "z", "1", "a", "2", "b"]);
+        run(&mut db, &["ZADD", "y", "5", "a"]);
+        assert_eq!(
+            run(
+                &mut db,
+                &["ZUNION", "2", "z", "y", "WEIGHTS", "1", "-1", "WITHSCORES"]
+            ),
+            bulks(&["a", "-4", "b", "2"])
+        );
+        assert_eq!(
+            run(&mut db, &["ZMPOP", "2", "x", "z", "MAX", "COUNT", "5"]),
+            RESP::Array(vec![
+                RESP::Bulk("z".into()),
+                RESP::Array(vec![bulks(&["b", "2"]), bulks(&["a", "1"])]),
+            ])
+        );
+        assert_eq!(
+            run(&mut db, &["ZINTER", "0", "z"]),
+            RESP::Error("ERR at least 1 input key is needed for 'zinter' command".to_string())
+        );
+        assert_eq!(
+            run(&mut db, &["ZUNIONSTORE", "d", "1", "y", "WITHSCORES"]),
+            RESP::Error("ERR syntax error".to_string())
+        );
+        assert_eq!(
+            run(&mut db, &["ZMPOP", "1", "y", "LEFT"]),
+            RESP::Error("ERR syntax error".to_string())
+        );
+    }
+}
@@ -44,33 +44,50 @@ impl Connection {
             }
         }
     }
+    /// Waits for the peer to close the connection, buffering whatever it sends in the
+    /// meantime for the following `read_frame`s.
+    pub async fn closed(&mut self) -> Result<()> {
+        loop {
+            let message_length = self
+                .stream
+                .read_buf(&mut self.buffer)
+                .await
+                .map_err(Error::Io)?;
+            if message_length == 0 {
+                return Ok(());
+            }
+        }
+    }
+
     fn parse_frame(&mut self) -> Result<Option<RESP>> {
         match RESP::parse(&self.buffer) {
             Ok((resp, offset)) => {
                 self.buffer.advance(offset);
-                return Ok(Some(resp));
+                Ok(Some(resp))
             }
-                ,
             // If there's not enough data to parse a frame return None.
-            Err(Error::IncompleteRequestData) => return Ok(None),
-            Err(e) => return Err(e),
+            Err(Error::IncompleteRequestData) => Ok(None),
+            Err(e) => Err(e),
         }
     }
 
     pub async fn write_frame(&mut self, frame: &RESP) -> Result<()> {
-        match frame {
-            RESP::Array(elements) => {
-                self.stream.write_u8(b'*').await.map_err(Error::Io)?;
-                self.write_decimal(elements.len() as i64)
-                    .await
-                    .map_err(Error::Io)?;
-                self.write_crlf().await.map_err(Error::Io)?;
+        // Arrays can be nested, the frames left to write are kept on a stack rather
+        // than recursing.
+        let mut pending = vec![frame];
+        while let Some(frame) = pending.pop() {
+            match frame {
+                RESP::Array(elements) => {
+                    self.stream.write_u8(b'*').await.map_err(Error::Io)?;
+                    self.write_decimal(elements.len() as i64)
+                        .await
+                        .map_err(Error::Io)?;
+                    self.write_crlf().await.map_err(Error::Io)?;
 
-                for element in elements {
-                    self.write_value(element).await.map_err(Error::Io)?;
+                    pending.extend(elements.iter().rev());
                 }
+                _ => self.write_value(frame).await.map_err(Error::Io)?,
             }
-            _ => self.write_value(frame).await.map_err(Error::Io)?,
         }
         self.stream.flush().await.map_err(Error::Io)?;
 
@@ -98,10 +115,11 @@ impl Connection {
                 self.stream.write_u8(b'$').await?;
                 self.write_decimal(body.len() as i64).await?;
                 self.write_crlf().await?;
-                self.stream.write_all(&body).await?;
+                self.stream.write_all(body).await?;
                 self.write_crlf().await?;
             }
             RESP::Null => self.stream.write_all(b"$-1\r\n").await?,
+            RESP::NullArray => self.stream.write_all(b"*-1\r\n").await?,
             RESP::Array(_) => unreachable!(),
         }
 
@@ -0,0 +1,500 @@
+use bytes::Bytes;
+
+use crate::error::Result;
+
+use super::{Db, Entry, Value};
+
+/// Whether the offsets of a `BITCOUNT` or `BITPOS` range count bytes or bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitUnit {
+    Byte,
+    Bit,
+}
+
+/// The inclusive range of `BITCOUNT` and `BITPOS`, negative offsets counting from
+/// the end of the string.
+#[derive(Debug, Clone, Copy)]
+pub struct BitRange {
+    pub start: i64,
+    /// `None` for the end of the string.
+    pub end: Option<i64>,
+    pub unit: BitUnit,
+}
+
+/// The bitwise operations of `BITOP`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitOp {
+    And,
+    Or,
+    Xor,
+    Not,
+}
+
+/// An integer type of `BITFIELD` like `i5` or `u16`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BitFieldType {
+    pub signed: bool,
+    /// Between 1 and 64 for signed types, 63 for unsigned ones.
+    pub bits: u32,
+}
+
+/// What `BITFIELD` does when a `SET` or `INCRBY` doesn't fit in its type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitFieldOverflow {
+    /// Wrap around like two's complement arithmetic does.
+    Wrap,
+    /// Saturate to the smallest or largest value of the type.
+    Sat,
+    /// Leave the field unchanged and reply `nil`.
+    Fail,
+}
+
+/// A subcommand of `BITFIELD`, the offsets are in bits.
+#[derive(Debug, Clone, Copy)]
+pub enum BitFieldOp {
+    Get {
+        ty: BitFieldType,
+        offset: u64,
+    },
+    Set {
+        ty: BitFieldType,
+        offset: u64,
+        value: i64,
+        overflow: BitFieldOverflow,
+    },
+    IncrBy {
+        ty: BitFieldType,
+        offset: u64,
+        increment: i64,
+        overflow: BitFieldOverflow,
+    },
+}
+
+impl BitFieldType {
+    /// Reads the field at `offset`, bits past the end of `data` being zeros.
+    fn read(&self, data: &[u8], offset: u64) -> i64 {
+        let mut value = 0u64;
+        for pos in offset..offset + self.bits as u64 {
+            value = (value << 1) | get_bit(data, pos) as u64;
+        }
+        if self.signed && self.bits < 64 && value & (1 << (self.bits - 1)) != 0 {
+            // Sign extends the field.
+            value |= u64::MAX << self.bits;
+        }
+        value as i64
+    }
+
+    /// Writes the field at `offset`, `data` must be long enough to hold it.
+    fn write(&self, data: &mut [u8], offset: u64, value: i64) {
+        for i in 0..self.bits as u64 {
+            let bit = (value as u64 >> (self.bits as u64 - 1 - i)) & 1 == 1;
+            set_bit(data, offset + i, bit);
+        }
+    }
+
+    /// Adds `increment` to `value` following `overflow`, `None` if it doesn't fit and
+    /// `overflow` is `Fail`. A `SET` is checked as an increment of zero.
+    fn add(&self, value: i64, increment: i64, overflow: BitFieldOverflow) -> Option<i64> {
+        let (above, below, max, min) = if self.signed {
+            let max = if self.bits == 64 {
+                i64::MAX
+            } else {
+                (1 << (self.bits - 1)) - 1
+            };
+            let sum = value as i128 + increment as i128;
+            (sum > max as i128, sum < -(max as i128) - 1, max, -max - 1)
+        } else {
+            // Unsigned fields look at the bits of `value`, so a negative one is huge.
+            let value = value as u64;
+            let max = (1u64 << self.bits) - 1;
+            let above = value > max || (increment > 0 && increment as u64 > max - value);
+            let below = increment < 0 && increment.unsigned_abs() > value;
+            (above, below, max as i64, 0)
+        };
+        let wrapped = self.truncate((value as u64).wrapping_add(increment as u64));
+        if !above && !below {
+            return Some(wrapped);
+        }
+        match overflow {
+            BitFieldOverflow::Wrap => Some(wrapped),
+            BitFieldOverflow::Sat => Some(if above { max } else { min }),
+            BitFieldOverflow::Fail => None,
+        }
+    }
+
+    /// Keeps the low `bits` of `value`, sign extending them for signed types.
+    fn truncate(&self, value: u64) -> i64 {
+        if self.bits == 64 {
+            return value as i64;
+        }
+        let high = u64::MAX << self.bits;
+        if self.signed && value & (1 << (self.bits - 1)) != 0 {
+            (value | high) as i64
+        } else {
+            (value & !high) as i64
+        }
+    }
+}
+
+impl Db {
+    /// Sets the bit at `offset` of the string at `key`, growing it with zero bytes if
+    /// it's too short. Returns the bit it replaced.
+    pub fn setbit(&mut self, key: &[u8], offset: u64, bit: bool) -> Result<bool> {
+        self.with_string_mut(key, |data| {
+            let needed = (offset / 8) as usize + 1;
+            if data.len() < needed {
+                data.resize(needed, 0);
+            }
+            let previous = get_bit(data, offset);
+            set_bit(data, offset, bit);
+            Ok(previous)
+        })
+    }
+
+    /// The bit at `offset` of the string at `key`, bits past its end being zeros.
+    pub fn getbit(&mut self, key: &[u8], offset: u64) -> Result<bool> {
+        Ok(self
+            .string(key)?
+            .map_or(false, |data| get_bit(data, offset)))
+    }
+
+    /// Counts the bits set in `range` of the string at `key`, all of it without one.
+    pub fn bitcount(&mut self, key: &[u8], range: Option<BitRange>) -> Result<u64> {
+        let data = match self.string(key)? {
+            Some(data) => data,
+            None => return Ok(0),
+        };
+        let (first, last) = match bit_span(data.len(), range) {
+            Some(span) => span,
+            None => return Ok(0),
+        };
+        let count = (first / 8..=last / 8)
+            .map(|i| (data[i as usize] & byte_mask(i, first, last)).count_ones() as u64)
+            .sum();
+        Ok(count)
+    }
+
+    /// The position of the first bit set to `bit` in `range` of the string at `key`,
+    /// -1 if there's none. Without an explicit end, the bits past the end of the string
+    /// count as zeros.
+    pub fn bitpos(&mut self, key: &[u8], bit: bool, range: Option<BitRange>) -> Result<i64> {
+        let data = match self.string(key)? {
+            Some(data) => data,
+            None => return Ok(if bit { -1 } else { 0 }),
+        };
+        let (first, last) = match bit_span(data.len(), range) {
+            Some(span) => span,
+            None => return Ok(-1),
+        };
+        for i in first / 8..=last / 8 {
+            // Looks for a one in the byte, complemented when looking for a zero.
+            let byte = if bit {
+                data[i as usize]
+            } else {
+                !data[i as usize]
+            };
+            let found = byte & byte_mask(i, first, last);
+            if found != 0 {
+                return Ok((i * 8) as i64 + found.leading_zeros() as i64);
+            }
+        }
+        let end_given = matches!(range, Some(BitRange { end: Some(_), .. }));
+        if bit || end_given {
+            Ok(-1)
+        } else {
+            Ok(last as i64 + 1)
+        }
+    }
+
+    /// Stores at `destination` the result of `op` between the strings at `keys`, the
+    /// shorter ones being padded with zero bytes. `destination` is deleted if the
+    /// result is empty. Returns the length of the result.
+    pub fn bitop(&mut self, op: BitOp, destination: Bytes, keys: &[Bytes]) -> Result<usize> {
+        let mut sources = Vec::with_capacity(keys.len());
+        for key in keys {
+            sources.push(self.string(key)?.cloned().unwrap_or_default());
+        }
+        let length = sources.iter().map(|data| data.len()).max().unwrap_or(0);
+        let byte = |data: &Bytes, i: usize| data.get(i).copied().unwrap_or(0);
+        let result: Vec<u8> = (0..length)
+            .map(|i| {
+                let mut bytes = sources.iter().map(|data| byte(data, i));
+                match op {
+                    BitOp::And => bytes.fold(0xff, |result, byte| result & byte),
+                    BitOp::Or => bytes.fold(0, |result, byte| result | byte),
+                    BitOp::Xor => bytes.fold(0, |result, byte| result ^ byte),
+                    BitOp::Not => !bytes.next().unwrap_or(0),
+                }
+            })
+            .collect();
+
+        if result.is_empty() {
+            self.remove_entry(&destination);
+        } else {
+            let entry = Entry {
+                value: Value::String(result.into()),
+                expires_at: None,
+            };
+            self.insert_entry(destination, entry);
+        }
+        Ok(length)
+    }
+
+    /// Runs the `BITFIELD` subcommands in order against the string at `key`, which is
+    /// only created or grown if one of them writes. Returns the reply of each
+    /// subcommand, `None` for writes that failed with `BitFieldOverflow::Fail`.
+    pub fn bitfield(&mut self, key: &[u8], ops: &[BitFieldOp]) -> Result<Vec<Option<i64>>> {
+        let needed = ops
+            .iter()
+            .filter_map(|op| match *op {
+                BitFieldOp::Get { .. } => None,
+                BitFieldOp::Set { ty, offset, .. } | BitFieldOp::IncrBy { ty, offset, .. } => {
+                    Some(((offset + ty.bits as u64 - 1) / 8) as usize + 1)
+                }
+            })
+            .max();
+        let needed = match needed {
+            Some(needed) => needed,
+            None => {
+                let data = self.string(key)?.map_or(&[][..], |data| &data[..]);
+                return Ok(ops
+                    .iter()
+                    .map(|op| match *op {
+                        BitFieldOp::Get { ty, offset } => Some(ty.read(data, offset)),
+                        _ => unreachable!("only reads are left"),
+                    })
+                    .collect());
+            }
+        };
+
+        self.with_string_mut(key, |data| {
+            if data.len() < needed {
+                data.resize(needed, 0);
+            }
+            Ok(ops
+                .iter()
+                .map(|op| match *op {
+                    BitFieldOp::Get { ty, offset } => Some(ty.read(data, offset)),
+                    BitFieldOp::Set {
+                        ty,
+                        offset,
+                        value,
+                        overflow,
+                    } => {
+                        let previous = ty.read(data, offset);
+                        let value = ty.add(value, 0, overflow)?;
+                        ty.write(data, offset, value);
+                        Some(previous)
+                    }
+                    BitFieldOp::IncrBy {
+                        ty,
+                        offset,
+                        increment,
+                        overflow,
+                    } => {
+                        let value = ty.add(ty.read(data, offset), increment, overflow)?;
+                        ty.write(data, offset, value);
+                        Some(value)
+                    }
+                })
+                .collect())
+        })
+    }
+}
+
+/// The first and last bits of `range` in a string of `length` bytes, `None` if the
+/// range is empty.
+fn bit_span(length: usize, range: Option<BitRange>) -> Option<(u64, u64)> {
+    let range = range.unwrap_or(BitRange {
+        start: 0,
+        end: None,
+        unit: BitUnit::Byte,
+    });
+    let total = match range.unit {
+        BitUnit::Byte => length as i64,
+        BitUnit::Bit => length as i64 * 8,
+    };
+    let from_end = |offset: i64| if offset < 0 { total + offset } else { offset };
+    let start = from_end(range.start).max(0);
+    let end = from_end(range.end.unwrap_or(-1)).max(0).min(total - 1);
+    if start > end {
+        return None;
+    }
+    let (start, end) = (start as u64, end as u64);
+    match range.unit {
+        BitUnit::Byte => Some((start * 8, end * 8 + 7)),
+        BitUnit::Bit => Some((start, end)),
+    }
+}
+
+/// The bits of byte `i` that fall between the bits `first` and `last`.
+fn byte_mask(i: u64, first: u64, last: u64) -> u8 {
+    let mut mask = 0xff;
+    if i == first / 8 {
+        mask &= 0xff >> (first % 8);
+    }
+    if i == last / 8 {
+        mask &= 0xff << (7 - last % 8);
+    }
+    mask
+}
+
+/// Bits are numbered from the most significant bit of the first byte.
+fn get_bit(data: &[u8], offset: u64) -> bool {
+    let byte = data.get((offset / 8) as usize).copied().unwrap_or(0);
+    byte & (0x80 >> (offset % 8)) != 0
+}
+
+fn set_bit(data: &mut [u8], offset: u64, bit: bool) {
+    let mask = 0x80 >> (offset % 8);
+    let byte = &mut data[(offset / 8) as usize];
+    if bit {
+        *byte |= mask;
+    } else {
+        *byte &= !mask;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BitFieldOp, BitFieldOverflow, BitFieldType, BitRange, BitUnit};
+    use crate::db::{Db, SetCondition, SetExpiry};
+
+    #[test]
+    fn counts_and_positions_in_ranges() {
+        let mut db = Db::new();
+        db.set(
+            "s".into(),
+            "foobar".into(),
+            SetCondition::Always,
+            SetExpiry::Persist,
+            false,
+        )
+        .unwrap();
+        let range = |start, end, unit| {
+            Some(BitRange {
+                start,
+                end: Some(end),
+                unit,
+            })
+        };
+        assert_eq!(db.bitcount(b"s", None).unwrap(), 26);
+        assert_eq!(db.bitcount(b"s", range(1, 1, BitUnit::Byte)).unwrap(), 6);
+        assert_eq!(db.bitcount(b"s", range(5, 30, BitUnit::Bit)).unwrap(), 17);
+        assert_eq!(db.bitcount(b"s", range(-1, -5, BitUnit::Byte)).unwrap(), 0);
+
+        db.set(
+            "p".into(),
+            b"\xff\xf0\x00"[..].into(),
+            SetCondition::Always,
+            SetExpiry::Persist,
+            false,
+        )
+        .unwrap();
+        assert_eq!(db.bitpos(b"p", false, None).unwrap(), 12);
+        assert_eq!(
+            db.bitpos(b"p", true, range(2, -1, BitUnit::Byte)).unwrap(),
+            -1
+        );
+        assert_eq!(
+            db.bitpos(b"p", true, range(7, 15, BitUnit::Bit)).unwrap(),
+            7
+        );
+        assert!(db.setbit(b"p", 23, true).is_ok());
+        assert_eq!(
+            db.bitpos(b"p", true, range(2, -1, BitUnit::Byte)).unwrap(),
+            23
+        );
+        db.set(
+            "ones".into(),
+            b"\xff"[..].into(),
+            SetCondition::Always,
+            SetExpiry::Persist,
+            false,
+        )
+        .unwrap();
+        assert_eq!(db.bitpos(b"ones", false, None).unwrap(), 8);
+        assert_eq!(
+            db.bitpos(b"ones", false, range(0, -1, BitUnit::Byte))
+                .unwrap(),
+            -1
+        );
+        assert_eq!(db.bitpos(b"missing", false, None).unwrap(), 0);
+    }
+
+    #[test]
+    fn bit_writes_leave_earlier_reads_alone() {
+        let mut db = Db::new();
+        db.set(
+            "s".into(),
+            "a".into(),
+            SetCondition::Always,
+            SetExpiry::In(10_000),
+            false,
+        )
+        .unwrap();
+        let before = db.get(b"s").unwrap().unwrap();
+        assert!(!db.setbit(b"s", 6, true).unwrap());
+        assert_eq!(db.setrange(b"s", 1, b"b").unwrap(), 2);
+        assert_eq!(before, "a");
+        assert_eq!(db.get(b"s").unwrap(), Some("cb".into()));
+        assert!(matches!(db.deadline(b"s"), Some(Some(_))));
+    }
+
+    #[test]
+    fn bitfields_handle_overflows() {
+        let mut db = Db::new();
+        let u2 = BitFieldType {
+            signed: false,
+            bits: 2,
+        };
+        let i8 = BitFieldType {
+            signed: true,
+            bits: 8,
+        };
+        let incr = |ty, increment, overflow| BitFieldOp::IncrBy {
+            ty,
+            offset: 100,
+            increment,
+            overflow,
+        };
+        let ops = [
+            incr(u2, 1, BitFieldOverflow::Wrap),
+            incr(u2, 3, BitFieldOverflow::Wrap),
+            incr(u2, 5, BitFieldOverflow::Sat),
+            incr(u2, 1, BitFieldOverflow::Fail),
+            incr(u2, -4, BitFieldOverflow::Sat),
+        ];
+        assert_eq!(
+            db.bitfield(b"f", &ops).unwrap(),
+            vec![Some(1), Some(0), Some(3), None, Some(0)]
+        );
+        assert_eq!(db.strlen(b"f").unwrap(), 13);
+
+        let set = |value, overflow| BitFieldOp::Set {
+            ty: i8,
+            offset: 0,
+            value,
+            overflow,
+        };
+        let ops = [
+            set(200, BitFieldOverflow::Wrap),
+            set(-200, BitFieldOverflow::Sat),
+            set(127, BitFieldOverflow::Fail),
+            BitFieldOp::IncrBy {
+                ty: i8,
+                offset: 0,
+                increment: 1,
+                overflow: BitFieldOverflow::Wrap,
+            },
+            BitFieldOp::Get { ty: i8, offset: 0 },
+        ];
+        assert_eq!(
+            db.bitfield(b"g", &ops).unwrap(),
+            vec![Some(0), Some(-56), Some(-128), Some(-128), Some(-128)]
+        );
+        let get = [BitFieldOp::Get { ty: u2, offset: 0 }];
+        assert_eq!(db.bitfield(b"missing", &get).unwrap(), vec![Some(0)]);
+        assert_eq!(db.type_of(b"missing"), "none");
+    }
+}
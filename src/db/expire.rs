@@ -0,0 +1,173 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+
+use super::{Db, Value};
+
+/// How often the Database Task runs an active expiry cycle, Redis' default `hz` of 10.
+pub const ACTIVE_EXPIRE_INTERVAL: Duration = Duration::from_millis(100);
+/// Number of volatile keys looked at per loop of a cycle.
+const KEYS_PER_LOOP: usize = 20;
+/// A cycle keeps looping while more than this percentage of the looked at keys had
+/// expired, as it's likely there are a lot more of them.
+const ACCEPTABLE_STALE_PERCENT: usize = 10;
+/// Upper bound on the time spent in one cycle so other clients aren't stalled,
+/// a quarter of `ACTIVE_EXPIRE_INTERVAL`.
+const CYCLE_TIME_LIMIT: Duration = Duration::from_millis(25);
+
+/// The keys of `Db` that have a time to live, kept apart so the active expiry cycle
+/// doesn't have to go through the keys that never expire.
+#[derive(Debug, Default)]
+pub(super) struct VolatileKeys {
+    keys: Vec<Bytes>,
+    /// Position of each key in `keys`, for constant time removal.
+    positions: HashMap<Bytes, usize>,
+    /// Where the next loop of the active expiry cycle resumes from.
+    cursor: usize,
+}
+
+impl VolatileKeys {
+    pub fn insert(&mut self, key: Bytes) {
+        if !self.positions.contains_key(&key) {
+            self.positions.insert(key.clone(), self.keys.len());
+            self.keys.push(key);
+        }
+    }
+
+    pub fn remove(&mut self, key: &[u8]) {
+        if let Some(position) = self.positions.remove(key) {
+            self.keys.swap_remove(position);
+            if let Some(moved) = self.keys.get(position) {
+                self.positions.insert(moved.clone(), position);
+            }
+        }
+    }
+
+    /// The next `count` keys in round robin order.
+    fn next_batch(&mut self, count: usize) -> Vec<Bytes> {
+        if self.keys.is_empty() {
+            return Vec::new();
+        }
+        let count = count.min(self.keys.len());
+        let batch = (0..count)
+            .map(|offset| self.keys[(self.cursor + offset) % self.keys.len()].clone())
+            .collect();
+        self.cursor = (self.cursor + count) % self.keys.len();
+        batch
+    }
+}
+
+impl Db {
+    /// Removes expired keys that were never read again, after Redis' `activeExpireCycle`,
+    /// then does the same with the expired fields of hashes.
+    /// Goes through the volatile keys `KEYS_PER_LOOP` at a time and keeps going while
+    /// enough of them turn out to be expired, within `CYCLE_TIME_LIMIT`.
+    /// Returns the number of keys removed, hashes whose last field expired included.
+    pub fn active_expire_cycle(&mut self) -> usize {
+        let started_at = Instant::now();
+        let mut expired_total = 0;
+
+        loop {
+            let batch = self.volatile_keys.next_batch(KEYS_PER_LOOP);
+            if batch.is_empty() {
+                break;
+            }
+            let now = self.now();
+            let mut expired = 0;
+            for key in &batch {
+                let is_expired = self
+                    .values
+                    .get(key)
+                    .map_or(false, |entry| entry.is_expired(now));
+                if is_expired {
+                    self.remove_entry(key);
+                    expired += 1;
+                }
+            }
+            expired_total += expired;
+
+            if expired * 100 <= batch.len() * ACCEPTABLE_STALE_PERCENT
+                || started_at.elapsed() > CYCLE_TIME_LIMIT
+            {
+                break;
+            }
+        }
+
+        expired_total + self.active_expire_fields(started_at)
+    }
+
+    /// The active expiry of hash fields, going through the hashes with volatile fields
+    /// the same way `active_expire_cycle` goes through volatile keys. Returns the
+    /// number of hashes removed because their last field expired.
+    fn active_expire_fields(&mut self, started_at: Instant) -> usize {
+        let mut removed_total = 0;
+
+        loop {
+            let batch = self.volatile_hashes.next_batch(KEYS_PER_LOOP);
+            if batch.is_empty() || started_at.elapsed() > CYCLE_TIME_LIMIT {
+                break;
+            }
+            let now = self.now();
+            let mut with_expired_fields = 0;
+            for key in &batch {
+                let hash = match self.values.get_mut(key).map(|entry| &mut entry.value) {
+                    Some(Value::Hash(hash)) => hash,
+                    _ => continue,
+                };
+                if hash.remove_expired(now) > 0 {
+                    with_expired_fields += 1;
+                }
+                if hash.is_empty() {
+                    self.remove_entry(key);
+                    removed_total += 1;
+                } else if !hash.has_volatile_fields() {
+                    self.volatile_hashes.remove(key);
+                }
+            }
+
+            if with_expired_fields * 100 <= batch.len() * ACCEPTABLE_STALE_PERCENT {
+                break;
+            }
+        }
+
+        removed_total
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        db::{Db, SetCondition, SetExpiry},
+        utils::FakeClock,
+    };
+
+    #[test]
+    fn active_expiry_removes_unread_keys() {
+        let clock = FakeClock::new(1000);
+        let mut db = Db::with_clock(Box::new(clock.clone()));
+        for i in 0..100 {
+            let expiry = if i % 2 == 0 {
+                SetExpiry::In(1)
+            } else {
+                SetExpiry::Persist
+            };
+            db.set(
+                i.to_string().into(),
+                "v".into(),
+                SetCondition::Always,
+                expiry,
+                false,
+            )
+            .unwrap();
+        }
+        assert_eq!(db.volatile_keys.keys.len(), 50);
+
+        clock.advance(2);
+        assert_eq!(db.active_expire_cycle(), 50);
+        assert_eq!(db.volatile_keys.keys.len(), 0);
+        assert_eq!(db.values.len(), 50);
+    }
+}
@@ -0,0 +1,487 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use bytes::Bytes;
+
+use crate::{
+    error::{Error, Result},
+    utils::{format_float, glob_match, parse_float, parse_integer, random_below},
+};
+
+use super::{deadline_passed, scan::KeySlots, Db, Entry, ExpireFlags, Value};
+
+/// The fields of a hash, along with the slots `HSCAN` cursors point into and the
+/// deadlines of the fields that have a time to live.
+#[derive(Debug, Clone, Default)]
+pub struct Hash {
+    fields: HashMap<Bytes, Field>,
+    slots: KeySlots,
+    /// The fields that have a time to live, soonest deadline first.
+    deadlines: BTreeSet<(u64, Bytes)>,
+}
+
+#[derive(Debug, Clone)]
+struct Field {
+    value: Bytes,
+    /// The unix time in milliseconds the field expires at.
+    expires_at: Option<u64>,
+}
+
+impl Hash {
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Bytes, &Bytes)> {
+        self.fields
+            .iter()
+            .map(|(field, entry)| (field, &entry.value))
+    }
+
+    /// Whether some fields have a time to live.
+    pub fn has_volatile_fields(&self) -> bool {
+        !self.deadlines.is_empty()
+    }
+
+    /// Removes the fields whose deadline has passed, returns how many there were.
+    pub fn remove_expired(&mut self, now: u64) -> usize {
+        let mut removed = 0;
+        while let Some((deadline, field)) = self.deadlines.first() {
+            if !deadline_passed(*deadline, now) {
+                break;
+            }
+            let field = field.clone();
+            self.remove(&field);
+            removed += 1;
+        }
+        removed
+    }
+
+    /// A field picked at random, along with its value.
+    fn random(&self) -> Option<(&Bytes, &Bytes)> {
+        let field = self.slots.random()?;
+        self.fields
+            .get_key_value(field)
+            .map(|(field, entry)| (field, &entry.value))
+    }
+
+    fn get(&self, field: &[u8]) -> Option<&Bytes> {
+        self.fields.get(field).map(|entry| &entry.value)
+    }
+
+    /// Sets `field` to `value` clearing its time to live, returns whether the field is
+    /// new.
+    fn insert(&mut self, field: Bytes, value: Bytes) -> bool {
+        self.set_expiry(&field, None);
+        self.slots.insert(field.clone());
+        let entry = Field {
+            value,
+            expires_at: None,
+        };
+        self.fields.insert(field, entry).is_none()
+    }
+
+    /// Sets `field` to `value` keeping its time to live.
+    fn update(&mut self, field: Bytes, value: Bytes) {
+        match self.fields.get_mut(&field) {
+            Some(entry) => entry.value = value,
+            None => {
+                self.insert(field, value);
+            }
+        }
+    }
+
+    /// Removes `field`, returns whether it existed.
+    fn remove(&mut self, field: &[u8]) -> bool {
+        self.set_expiry(field, None);
+        self.slots.remove(field);
+        self.fields.remove(field).is_some()
+    }
+
+    /// Changes the expiry of `field` if it exists, keeping `deadlines` up to date.
+    fn set_expiry(&mut self, field: &[u8], expires_at: Option<u64>) {
+        let entry = match self.fields.get_mut(field) {
+            Some(entry) => entry,
+            None => return,
+        };
+        if let Some(deadline) = entry.expires_at {
+            self.deadlines
+                .remove(&(deadline, Bytes::copy_from_slice(field)));
+        }
+        if let Some(deadline) = expires_at {
+            self.deadlines
+                .insert((deadline, Bytes::copy_from_slice(field)));
+        }
+        entry.expires_at = expires_at;
+    }
+}
+
+impl Db {
+    /// Sets the field value `pairs` of the hash at `key`, creating it if needed. With
+    /// `only_if_missing` existing fields are left untouched. Returns the number of
+    /// fields added.
+    pub fn hset(
+        &mut self,
+        key: Bytes,
+        pairs: Vec<(Bytes, Bytes)>,
+        only_if_missing: bool,
+    ) -> Result<usize> {
+        let hash = self.hash_or_insert(key)?;
+        let mut added = 0;
+        for (field, value) in pairs {
+            if only_if_missing && hash.get(&field).is_some() {
+                continue;
+            }
+            added += hash.insert(field, value) as usize;
+        }
+        Ok(added)
+    }
+
+    pub fn hget(&mut self, key: &[u8], field: &[u8]) -> Result<Option<Bytes>> {
+        Ok(self.hash(key)?.and_then(|hash| hash.get(field).cloned()))
+    }
+
+    pub fn hmget(&mut self, key: &[u8], fields: &[Bytes]) -> Result<Vec<Option<Bytes>>> {
+        let hash = self.hash(key)?;
+        Ok(fields
+            .iter()
+            .map(|field| hash.and_then(|hash| hash.get(field).cloned()))
+            .collect())
+    }
+
+    /// Removes `fields` from the hash at `key`, deleting the key once it's empty.
+    /// Returns the number of fields removed.
+    pub fn hdel(&mut self, key: &[u8], fields: &[Bytes]) -> Result<usize> {
+        let hash = match self.hash_mut(key)? {
+            Some(hash) => hash,
+            None => return Ok(0),
+        };
+        let removed = fields.iter().filter(|field| hash.remove(field)).count();
+        self.remove_if_empty(key);
+        Ok(removed)
+    }
+
+    /// The field value pairs of the hash at `key`.
+    pub fn hgetall(&mut self, key: &[u8]) -> Result<Vec<(Bytes, Bytes)>> {
+        Ok(self.hash(key)?.map_or_else(Vec::new, |hash| {
+            hash.iter()
+                .map(|(field, value)| (field.clone(), value.clone()))
+                .collect()
+        }))
+    }
+
+    pub fn hlen(&mut self, key: &[u8]) -> Result<usize> {
+        Ok(self.hash(key)?.map_or(0, |hash| hash.len()))
+    }
+
+    pub fn hexists(&mut self, key: &[u8], field: &[u8]) -> Result<bool> {
+        Ok(self.hget(key, field)?.is_some())
+    }
+
+    pub fn hstrlen(&mut self, key: &[u8], field: &[u8]) -> Result<usize> {
+        Ok(self.hget(key, field)?.map_or(0, |value| value.len()))
+    }
+
+    /// Adds `delta` to the integer in `field`, a missing field counting as `0`.
+    /// Returns the new value.
+    pub fn hincrby(&mut self, key: Bytes, field: Bytes, delta: i64) -> Result<i64> {
+        let current = match self.hget(&key, &field)? {
+            Some(value) => parse_integer(&value)
+                .ok_or_else(|| Error::Msg("hash value is not an integer".to_string()))?,
+            None => 0,
+        };
+        let updated = current.checked_add(delta).ok_or(Error::Overflow)?;
+        self.hset_keep_ttl(key, field, updated.to_string().into())?;
+        Ok(updated)
+    }
+
+    /// Adds `delta` to the float in `field`, a missing field counting as `0`.
+    /// Returns the new value as stored.
+    pub fn hincrbyfloat(&mut self, key: Bytes, field: Bytes, delta: f64) -> Result<Bytes> {
+        let current = match self.hget(&key, &field)? {
+            Some(value) => parse_float(&value)
+                .ok_or_else(|| Error::Msg("hash value is not a float".to_string()))?,
+            None => 0.0,
+        };
+        let updated = current + delta;
+        if !updated.is_finite() {
+            return Err(Error::Msg(
+                "increment would produce NaN or Infinity".to_string(),
+            ));
+        }
+        let updated: Bytes = format_float(updated).into();
+        self.hset_keep_ttl(key, field, updated.clone())?;
+        Ok(updated)
+    }
+
+    /// Random field value pairs of the hash at `key`. A positive `count` picks up to
+    /// that many distinct fields, a negative one picks exactly `-count` fields that may
+    /// repeat. Only the picked fields are copied.
+    pub fn hrandfield(&mut self, key: &[u8], count: i64) -> Result<Vec<(Bytes, Bytes)>> {
+        let hash = match self.hash(key)? {
+            Some(hash) => hash,
+            None => return Ok(Vec::new()),
+        };
+        let pick = |(field, value): (&Bytes, &Bytes)| (field.clone(), value.clone());
+        if count < 0 {
+            return Ok((0..count.unsigned_abs())
+                .filter_map(|_| hash.random().map(pick))
+                .collect());
+        }
+
+        let count = (count as usize).min(hash.len());
+        let mut sample = Vec::with_capacity(count);
+        if count * 3 > hash.len() {
+            // Most of the fields are picked, each one is kept with the odds of the
+            // picks left over the fields left.
+            let mut left = hash.len();
+            for pair in hash.iter() {
+                if random_below(left) < count - sample.len() {
+                    sample.push(pick(pair));
+                }
+                left -= 1;
+            }
+        } else {
+            let mut picked = HashSet::with_capacity(count);
+            while sample.len() < count {
+                let (field, value) = hash.random().unwrap();
+                if picked.insert(field) {
+                    sample.push(pick((field, value)));
+                }
+            }
+        }
+        Ok(sample)
+    }
+
+    /// Goes through `count` field slots of the hash at `key` from `cursor` and returns
+    /// the field value pairs whose field matches `pattern`, along with the cursor to
+    /// continue from.
+    pub fn hscan(
+        &mut self,
+        key: &[u8],
+        cursor: usize,
+        count: usize,
+        pattern: Option<&[u8]>,
+    ) -> Result<(Vec<(Bytes, Bytes)>, usize)> {
+        let hash = match self.hash(key)? {
+            Some(hash) => hash,
+            None => return Ok((Vec::new(), 0)),
+        };
+        let (fields, next_cursor) = hash.slots.scan(cursor, count);
+        let pairs = fields
+            .into_iter()
+            .filter(|field| pattern.map_or(true, |pattern| glob_match(pattern, field)))
+            .filter_map(|field| {
+                let value = hash.get(&field)?.clone();
+                Some((field, value))
+            })
+            .collect();
+        Ok((pairs, next_cursor))
+    }
+
+    /// Sets the expiry of `fields` to the unix time `deadline` in milliseconds, for the
+    /// fields whose current expiry `flags` allow it. A deadline that has already passed
+    /// deletes the field. Returns for each field `-2` if it doesn't exist, `0` if
+    /// `flags` didn't allow it, `1` if the expiry was set and `2` if it was deleted.
+    pub fn hexpire(
+        &mut self,
+        key: &[u8],
+        fields: &[Bytes],
+        deadline: i64,
+        flags: ExpireFlags,
+    ) -> Result<Vec<i64>> {
+        let now = self.now();
+        let hash = match self.hash_mut(key)? {
+            Some(hash) => hash,
+            None => return Ok(vec![-2; fields.len()]),
+        };
+        let replies = fields
+            .iter()
+            .map(|field| {
+                let current = match hash.fields.get(field) {
+                    Some(entry) => entry.expires_at,
+                    None => return -2,
+                };
+                if !flags.allows(current, deadline) {
+                    0
+                } else if deadline_passed(deadline.max(0) as u64, now) {
+                    hash.remove(field);
+                    2
+                } else {
+                    hash.set_expiry(field, Some(deadline as u64));
+                    1
+                }
+            })
+            .collect();
+        if hash.has_volatile_fields() {
+            self.volatile_hashes.insert(Bytes::copy_from_slice(key));
+        }
+        self.remove_if_empty(key);
+        Ok(replies)
+    }
+
+    /// Removes the expiry of `fields`. Returns for each field `-2` if it doesn't exist,
+    /// `-1` if it has no expiry and `1` if its expiry was removed.
+    pub fn hpersist(&mut self, key: &[u8], fields: &[Bytes]) -> Result<Vec<i64>> {
+        let hash = match self.hash_mut(key)? {
+            Some(hash) => hash,
+            None => return Ok(vec![-2; fields.len()]),
+        };
+        Ok(fields
+            .iter()
+            .map(|field| match hash.fields.get(field) {
+                None => -2,
+                Some(entry) if entry.expires_at.is_none() => -1,
+                Some(_) => {
+                    hash.set_expiry(field, None);
+                    1
+                }
+            })
+            .collect())
+    }
+
+    /// The unix time in milliseconds each of `fields` expires at, `None` if the field
+    /// doesn't exist and `Some(None)` if it doesn't expire.
+    pub fn hdeadlines(&mut self, key: &[u8], fields: &[Bytes]) -> Result<Vec<Option<Option<u64>>>> {
+        let hash = self.hash(key)?;
+        Ok(fields
+            .iter()
+            .map(|field| {
+                let entry = hash?.fields.get(field)?;
+                Some(entry.expires_at)
+            })
+            .collect())
+    }
+
+    /// Sets `field` of the hash at `key` to `value` keeping the field's time to live.
+    fn hset_keep_ttl(&mut self, key: Bytes, field: Bytes, value: Bytes) -> Result<()> {
+        self.hash_or_insert(key)?.update(field, value);
+        Ok(())
+    }
+
+    /// The hash at `key`, created empty if the key doesn't exist.
+    fn hash_or_insert(&mut self, key: Bytes) -> Result<&mut Hash> {
+        if self.hash_mut(&key)?.is_none() {
+            let entry = Entry {
+                value: Value::Hash(Hash::default()),
+                expires_at: None,
+            };
+            self.insert_entry(key.clone(), entry);
+        }
+        Ok(self.hash_mut(&key)?.unwrap())
+    }
+
+    /// The hash at `key`, fails with `WRONGTYPE` if the key holds another type.
+    fn hash(&mut self, key: &[u8]) -> Result<Option<&Hash>> {
+        self.live_entry(key)
+            .map(|entry| entry.value.as_hash())
+            .transpose()
+    }
+
+    /// Mutable version of `hash`.
+    fn hash_mut(&mut self, key: &[u8]) -> Result<Option<&mut Hash>> {
+        self.live_entry_mut(key)
+            .map(|entry| entry.value.as_hash_mut())
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use crate::{
+        db::{Db, ExpireFlags},
+        utils::FakeClock,
+    };
+
+    fn pairs(pairs: &[(&'static str, &'static str)]) -> Vec<(Bytes, Bytes)> {
+        pairs
+            .iter()
+            .map(|(field, value)| (Bytes::from(*field), Bytes::from(*value)))
+            .collect()
+    }
+
+    #[test]
+    fn set_get_and_delete_fields() {
+        let mut db = Db::new();
+        let added = db.hset("h".into(), pairs(&[("a", "1"), ("b", "2")]), false);
+        assert_eq!(added.unwrap(), 2);
+        let added = db.hset("h".into(), pairs(&[("a", "3"), ("c", "4")]), true);
+        assert_eq!(added.unwrap(), 1);
+        assert_eq!(db.hget(b"h", b"a").unwrap(), Some("1".into()));
+        assert_eq!(
+            db.hmget(b"h", &["c".into(), "d".into()]).unwrap(),
+            vec![Some("4".into()), None]
+        );
+        assert_eq!(db.hincrby("h".into(), "a".into(), 5).unwrap(), 6);
+        assert!(db.hincrby("h".into(), "a".into(), i64::MAX).is_err());
+
+        assert_eq!(
+            db.hdel(b"h", &["a".into(), "b".into(), "x".into()])
+                .unwrap(),
+            2
+        );
+        assert_eq!(db.hlen(b"h").unwrap(), 1);
+        db.hdel(b"h", &["c".into()]).unwrap();
+        assert_eq!(db.type_of(b"h"), "none");
+    }
+
+    #[test]
+    fn random_fields() {
+        let mut db = Db::new();
+        db.hset(
+            "h".into(),
+            pairs(&[("a", "1"), ("b", "2"), ("c", "3")]),
+            false,
+        )
+        .unwrap();
+        let mut fields = db.hrandfield(b"h", 10).unwrap();
+        fields.sort();
+        assert_eq!(fields, pairs(&[("a", "1"), ("b", "2"), ("c", "3")]));
+        assert_eq!(db.hrandfield(b"h", 2).unwrap().len(), 2);
+        assert_eq!(db.hrandfield(b"h", -5).unwrap().len(), 5);
+
+        // Fields removed leave free slots behind that picks must skip.
+        db.hdel(b"h", &["a".into(), "b".into()]).unwrap();
+        assert_eq!(db.hrandfield(b"h", -3).unwrap(), pairs(&[("c", "3"); 3]));
+        assert_eq!(db.hrandfield(b"h", 1).unwrap(), pairs(&[("c", "3")]));
+        assert!(db.hrandfield(b"missing", -3).unwrap().is_empty());
+    }
+
+    #[test]
+    fn fields_expire_on_their_own() {
+        let clock = FakeClock::new(1000);
+        let mut db = Db::with_clock(Box::new(clock.clone()));
+        db.hset("h".into(), pairs(&[("a", "1"), ("b", "2")]), false)
+            .unwrap();
+        let fields = ["a".into(), "x".into()];
+        let replies = db.hexpire(b"h", &fields, 1100, ExpireFlags::default());
+        assert_eq!(replies.unwrap(), vec![1, -2]);
+        let nx = ExpireFlags {
+            nx: true,
+            ..Default::default()
+        };
+        assert_eq!(db.hexpire(b"h", &fields[..1], 2000, nx).unwrap(), vec![0]);
+        // Incrementing keeps the time to live while setting clears it.
+        db.hincrby("h".into(), "a".into(), 1).unwrap();
+        assert_eq!(
+            db.hdeadlines(b"h", &fields[..1]).unwrap(),
+            vec![Some(Some(1100))]
+        );
+
+        clock.advance(101);
+        assert_eq!(db.hget(b"h", b"a").unwrap(), None);
+        assert_eq!(db.hlen(b"h").unwrap(), 1);
+
+        // The key goes away with its last field, even if it's never read again.
+        let replies = db.hexpire(b"h", &["b".into()], 1200, ExpireFlags::default());
+        assert_eq!(replies.unwrap(), vec![1]);
+        clock.advance(100);
+        assert_eq!(db.active_expire_cycle(), 1);
+        assert!(db.values.is_empty());
+    }
+}
@@ -0,0 +1,587 @@
+use std::{iter, mem};
+
+use bytes::Bytes;
+
+use crate::error::{Error, Result};
+
+use super::Db;
+
+/// Number of bits of the hash that select a register.
+const HLL_P: u32 = 14;
+/// Number of bits of the hash whose leading run of zeros is counted.
+const HLL_Q: u32 = 64 - HLL_P;
+const REGISTERS: usize = 1 << HLL_P;
+const REGISTER_BITS: usize = 6;
+/// The magic, the encoding, three unused bytes and the cached cardinality.
+const HEADER_SIZE: usize = 16;
+const DENSE_SIZE: usize = HEADER_SIZE + (REGISTERS * REGISTER_BITS + 7) / 8;
+const DENSE: u8 = 0;
+const SPARSE: u8 = 1;
+/// Sparse representations growing past this are converted to the dense one, Redis'
+/// default `hll-sparse-max-bytes`.
+const SPARSE_MAX_BYTES: usize = 3000;
+const SPARSE_VAL_MAX_VALUE: u8 = 32;
+const SPARSE_VAL_MAX_LEN: usize = 4;
+const SPARSE_ZERO_MAX_LEN: usize = 64;
+const SPARSE_XZERO_MAX_LEN: usize = 16384;
+/// Redis' `HLL_ALPHA_INF`, 0.5 / ln(2).
+const ALPHA_INF: f64 = 0.721_347_520_444_481_7;
+
+/// A HyperLogLog laid out byte for byte like Redis' so the strings can be moved
+/// between the two. It starts with the sparse representation, a run-length encoding of
+/// the registers, and switches to the dense one of 6 bits per register once a register
+/// gets too big or the encoding too long. Reads work on a borrowed string, writes on
+/// the string's own buffer.
+struct HyperLogLog<D = Vec<u8>>(D);
+
+/// A run of registers of the sparse representation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Run {
+    /// Encoded as `ZERO` (`00xxxxxx`) up to 64 registers, `XZERO`
+    /// (`01xxxxxx yyyyyyyy`) above.
+    Zero(usize),
+    /// Encoded as `VAL` (`1vvvvvxx`), a value up to 32 for up to 4 registers.
+    Value(u8, usize),
+}
+
+impl Run {
+    fn len(&self) -> usize {
+        match *self {
+            Run::Zero(len) | Run::Value(_, len) => len,
+        }
+    }
+
+    /// Decodes the opcode at the start of `bytes`, along with its size.
+    fn decode(bytes: &[u8]) -> Option<(Run, usize)> {
+        let byte = *bytes.first()?;
+        Some(match byte & 0xc0 {
+            0x00 => (Run::Zero((byte & 0x3f) as usize + 1), 1),
+            0x40 => {
+                let low = bytes.get(1).copied().unwrap_or(0) as usize;
+                (Run::Zero((((byte & 0x3f) as usize) << 8 | low) + 1), 2)
+            }
+            _ => {
+                let value = ((byte >> 2) & 0x1f) + 1;
+                (Run::Value(value, (byte & 0x03) as usize + 1), 1)
+            }
+        })
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        match *self {
+            Run::Zero(len) if len > SPARSE_ZERO_MAX_LEN => {
+                let len = len - 1;
+                out.extend([(len >> 8) as u8 | 0x40, len as u8]);
+            }
+            Run::Zero(len) => out.push(len as u8 - 1),
+            Run::Value(value, len) => out.push(0x80 | (value - 1) << 2 | (len as u8 - 1)),
+        }
+    }
+}
+
+impl<D: AsRef<[u8]>> HyperLogLog<D> {
+    /// Checks that `data` looks like a HyperLogLog, its registers are only checked
+    /// when they're read.
+    fn parse(data: D) -> Result<HyperLogLog<D>> {
+        let bytes = data.as_ref();
+        let valid = bytes.len() >= HEADER_SIZE
+            && bytes.starts_with(b"HYLL")
+            && (bytes[4] == SPARSE || (bytes[4] == DENSE && bytes.len() == DENSE_SIZE));
+        if valid {
+            Ok(HyperLogLog(data))
+        } else {
+            Err(Error::InvalidHll)
+        }
+    }
+
+    fn is_dense(&self) -> bool {
+        self.0.as_ref()[4] == DENSE
+    }
+
+    fn registers(&self) -> &[u8] {
+        &self.0.as_ref()[HEADER_SIZE..]
+    }
+
+    /// The cardinality computed last, if nothing was added since.
+    fn cached_count(&self) -> Option<u64> {
+        let card = u64::from_le_bytes(self.0.as_ref()[8..16].try_into().unwrap());
+        // The most significant bit flags a stale cardinality.
+        (card >> 63 == 0).then_some(card)
+    }
+
+    /// Raises each of the `max` registers to the matching register of `self`.
+    fn merge_into(&self, max: &mut [u8]) -> Result<()> {
+        if self.is_dense() {
+            for (index, register) in max.iter_mut().enumerate() {
+                *register = (*register).max(dense_get(self.registers(), index));
+            }
+            Ok(())
+        } else {
+            for_each_run(self.registers(), |first, run| {
+                if let Run::Value(value, len) = run {
+                    for register in &mut max[first..first + len] {
+                        *register = (*register).max(value);
+                    }
+                }
+            })
+        }
+    }
+
+    /// Estimates the number of distinct elements added.
+    fn count(&self) -> Result<u64> {
+        let mut histogram = [0; 64];
+        if self.is_dense() {
+            for index in 0..REGISTERS {
+                histogram[dense_get(self.registers(), index) as usize] += 1;
+            }
+        } else {
+            for_each_run(self.registers(), |_, run| match run {
+                Run::Zero(len) => histogram[0] += len,
+                Run::Value(value, len) => histogram[value as usize] += len,
+            })?;
+        }
+        Ok(estimate(&histogram))
+    }
+}
+
+impl HyperLogLog {
+    /// An empty HyperLogLog, sparse with every register at zero.
+    fn new() -> HyperLogLog {
+        let mut data = vec![0; HEADER_SIZE];
+        data[..4].copy_from_slice(b"HYLL");
+        data[4] = SPARSE;
+        let mut left = REGISTERS;
+        while left > 0 {
+            let len = left.min(SPARSE_XZERO_MAX_LEN);
+            Run::Zero(len).encode(&mut data);
+            left -= len;
+        }
+        HyperLogLog(data)
+    }
+
+    fn cache_count(&mut self, count: u64) {
+        self.0[8..16].copy_from_slice(&count.to_le_bytes());
+    }
+
+    fn invalidate_cache(&mut self) {
+        self.0[15] |= 0x80;
+    }
+
+    /// Adds `element`, returns whether a register changed.
+    fn add(&mut self, element: &[u8]) -> Result<bool> {
+        let (index, count) = pattern(element);
+        self.set(index, count)
+    }
+
+    /// Raises the register at `index` to `count`, returns whether it changed.
+    fn set(&mut self, index: usize, count: u8) -> Result<bool> {
+        if self.is_dense() {
+            Ok(dense_set(&mut self.0[HEADER_SIZE..], index, count))
+        } else {
+            self.sparse_set(index, count)
+        }
+    }
+
+    /// A port of Redis' `hllSparseSet`, the encoding it leaves must be the same.
+    fn sparse_set(&mut self, index: usize, count: u8) -> Result<bool> {
+        if count > SPARSE_VAL_MAX_VALUE {
+            return self.promote(index, count);
+        }
+
+        // Finds the run holding the register, along with the opcode before it.
+        let mut position = HEADER_SIZE;
+        let mut first = 0;
+        let mut previous = None;
+        let (run, size) = loop {
+            let (run, size) = self
+                .0
+                .get(position..)
+                .and_then(Run::decode)
+                .ok_or(Error::CorruptedHll)?;
+            if index < first + run.len() {
+                break (run, size);
+            }
+            previous = Some(position);
+            position += size;
+            first += run.len();
+        };
+
+        if let Run::Value(value, _) = run {
+            if value >= count {
+                return Ok(false);
+            }
+        }
+        // Splits the run in up to three around the register.
+        let last = first + run.len() - 1;
+        let split = |len| match run {
+            Run::Zero(_) => Run::Zero(len),
+            Run::Value(value, _) => Run::Value(value, len),
+        };
+        let mut runs = Vec::with_capacity(5);
+        if index != first {
+            split(index - first).encode(&mut runs);
+        }
+        Run::Value(count, 1).encode(&mut runs);
+        if index != last {
+            split(last - index).encode(&mut runs);
+        }
+        if runs.len() > size && self.0.len() + runs.len() - size > SPARSE_MAX_BYTES {
+            return self.promote(index, count);
+        }
+        self.0.splice(position..position + size, runs);
+
+        // Merges the adjacent runs of the same value the change may have left, looking
+        // at up to 5 opcodes from the one before the change.
+        let mut position = previous.unwrap_or(HEADER_SIZE);
+        let mut scan = 5;
+        while position < self.0.len() && scan > 0 {
+            scan -= 1;
+            let (run, size) = Run::decode(&self.0[position..]).unwrap();
+            if let Run::Value(value, len) = run {
+                let next = self.0.get(position + 1..).and_then(Run::decode);
+                if let Some((Run::Value(next_value, next_len), _)) = next {
+                    if value == next_value && len + next_len <= SPARSE_VAL_MAX_LEN {
+                        let mut merged = Vec::with_capacity(1);
+                        Run::Value(value, len + next_len).encode(&mut merged);
+                        self.0.splice(position..position + 2, merged);
+                        // The merged run may merge again with the next one.
+                        continue;
+                    }
+                }
+            }
+            position += size;
+        }
+        Ok(true)
+    }
+
+    /// Switches to the dense representation to raise the register at `index`.
+    fn promote(&mut self, index: usize, count: u8) -> Result<bool> {
+        self.make_dense()?;
+        Ok(dense_set(&mut self.0[HEADER_SIZE..], index, count))
+    }
+
+    /// Switches to the dense representation, keeping the header.
+    fn make_dense(&mut self) -> Result<()> {
+        if self.is_dense() {
+            return Ok(());
+        }
+        let mut dense = vec![0; DENSE_SIZE];
+        dense[..HEADER_SIZE].copy_from_slice(&self.0[..HEADER_SIZE]);
+        dense[4] = DENSE;
+        for_each_run(self.registers(), |first, run| {
+            if let Run::Value(value, len) = run {
+                for index in first..first + len {
+                    dense_set(&mut dense[HEADER_SIZE..], index, value);
+                }
+            }
+        })?;
+        self.0 = dense;
+        Ok(())
+    }
+}
+
+/// Calls `f` with each run of a sparse representation and the index of its first
+/// register, checking that the runs cover exactly all the registers.
+fn for_each_run(mut sparse: &[u8], mut f: impl FnMut(usize, Run)) -> Result<()> {
+    let mut index = 0;
+    while let Some((run, size)) = Run::decode(sparse) {
+        if index + run.len() > REGISTERS {
+            return Err(Error::CorruptedHll);
+        }
+        f(index, run);
+        index += run.len();
+        sparse = sparse.get(size..).unwrap_or_default();
+    }
+    if index == REGISTERS {
+        Ok(())
+    } else {
+        Err(Error::CorruptedHll)
+    }
+}
+
+/// Registers are packed 6 bits each, starting from the least significant bits.
+fn dense_get(registers: &[u8], index: usize) -> u8 {
+    let byte = index * REGISTER_BITS / 8;
+    let shift = index * REGISTER_BITS % 8;
+    let low = registers[byte] as u16;
+    let high = registers.get(byte + 1).copied().unwrap_or(0) as u16;
+    ((low | high << 8) >> shift) as u8 & 0x3f
+}
+
+/// Raises the register at `index` to `count`, returns whether it changed.
+fn dense_set(registers: &mut [u8], index: usize, count: u8) -> bool {
+    if dense_get(registers, index) >= count {
+        return false;
+    }
+    let byte = index * REGISTER_BITS / 8;
+    let shift = index * REGISTER_BITS % 8;
+    let mask = 0x3fu16 << shift;
+    let value = (count as u16) << shift;
+    registers[byte] = (registers[byte] & !(mask as u8)) | value as u8;
+    if let Some(high) = registers.get_mut(byte + 1) {
+        *high = (*high & !((mask >> 8) as u8)) | (value >> 8) as u8;
+    }
+    true
+}
+
+/// The register an element goes to and the value it offers: the length of the run
+/// of zeros in its hash, plus one.
+fn pattern(element: &[u8]) -> (usize, u8) {
+    let hash = murmur_hash64a(element, 0xadc8_3b19);
+    let index = hash as usize & (REGISTERS - 1);
+    // The set bit bounds the count to `HLL_Q + 1`.
+    let hash = (hash >> HLL_P) | 1 << HLL_Q;
+    (index, hash.trailing_zeros() as u8 + 1)
+}
+
+/// The cardinality estimated from the histogram of the register values, with the
+/// estimator of Otmar Ertl's "New cardinality estimation algorithms for HyperLogLog
+/// sketches" like Redis.
+fn estimate(histogram: &[usize; 64]) -> u64 {
+    let m = REGISTERS as f64;
+    let q = HLL_Q as usize;
+    let mut z = m * tau((m - histogram[q + 1] as f64) / m);
+    for count in histogram[1..=q].iter().rev() {
+        z += *count as f64;
+        z *= 0.5;
+    }
+    z += m * sigma(histogram[0] as f64 / m);
+    (ALPHA_INF * m * m / z).round() as u64
+}
+
+fn sigma(mut x: f64) -> f64 {
+    if x == 1.0 {
+        return f64::INFINITY;
+    }
+    let mut y = 1.0;
+    let mut z = x;
+    loop {
+        x *= x;
+        let previous = z;
+        z += x * y;
+        y += y;
+        if z == previous {
+            return z;
+        }
+    }
+}
+
+fn tau(mut x: f64) -> f64 {
+    if x == 0.0 || x == 1.0 {
+        return 0.0;
+    }
+    let mut y = 1.0;
+    let mut z = 1.0 - x;
+    loop {
+        x = x.sqrt();
+        let previous = z;
+        y *= 0.5;
+        z -= (1.0 - x).powi(2) * y;
+        if z == previous {
+            return z / 3.0;
+        }
+    }
+}
+
+/// Austin Appleby's MurmurHash64A, reading blocks as little endian like Redis does.
+fn murmur_hash64a(data: &[u8], seed: u64) -> u64 {
+    const M: u64 = 0xc6a4_a793_5bd1_e995;
+    const R: u32 = 47;
+    let mut h = seed ^ (data.len() as u64).wrapping_mul(M);
+    let mut blocks = data.chunks_exact(8);
+    for block in &mut blocks {
+        let mut k = u64::from_le_bytes(block.try_into().unwrap());
+        k = k.wrapping_mul(M);
+        k ^= k >> R;
+        k = k.wrapping_mul(M);
+        h ^= k;
+        h = h.wrapping_mul(M);
+    }
+    let tail = blocks.remainder();
+    if !tail.is_empty() {
+        for (i, byte) in tail.iter().enumerate() {
+            h ^= (*byte as u64) << (8 * i);
+        }
+        h = h.wrapping_mul(M);
+    }
+    h ^= h >> R;
+    h = h.wrapping_mul(M);
+    h ^= h >> R;
+    h
+}
+
+impl Db {
+    /// Adds `elements` to the HyperLogLog at `key`, creating it if needed. Returns
+    /// whether the estimated cardinality may have changed, which it may for a key just
+    /// created.
+    pub fn pfadd(&mut self, key: &[u8], elements: &[Bytes]) -> Result<bool> {
+        let mut updated = self.hyperloglog(key)?.is_none();
+        self.hyperloglog_mut(key, |hll| {
+            for element in elements {
+                updated |= hll.add(element)?;
+            }
+            if updated {
+                hll.invalidate_cache();
+            }
+            Ok(updated)
+        })
+    }
+
+    /// The estimated number of distinct elements added to the HyperLogLogs at `keys`,
+    /// merged on the fly when there are more than one. The cardinality of a single
+    /// HyperLogLog is cached in its header.
+    pub fn pfcount(&mut self, keys: &[Bytes]) -> Result<u64> {
+        if let [key] = keys {
+            let count = match self.hyperloglog(key)? {
+                Some(hll) => match hll.cached_count() {
+                    Some(count) => return Ok(count),
+                    None => hll.count()?,
+                },
+                None => return Ok(0),
+            };
+            return self.hyperloglog_mut(key, |hll| {
+                hll.cache_count(count);
+                Ok(count)
+            });
+        }
+
+        let mut max = vec![0; REGISTERS];
+        for key in keys {
+            if let Some(hll) = self.hyperloglog(key)? {
+                hll.merge_into(&mut max)?;
+            }
+        }
+        let mut histogram = [0; 64];
+        for register in max {
+            histogram[register as usize] += 1;
+        }
+        Ok(estimate(&histogram))
+    }
+
+    /// Merges the HyperLogLogs at `keys` into the one at `destination`, which is
+    /// created if needed. The result is dense if any of them is.
+    pub fn pfmerge(&mut self, destination: &[u8], keys: &[Bytes]) -> Result<()> {
+        let mut max = vec![0; REGISTERS];
+        let mut use_dense = false;
+        // The destination takes part in the merge too.
+        for key in iter::once(destination).chain(keys.iter().map(|key| &key[..])) {
+            if let Some(hll) = self.hyperloglog(key)? {
+                use_dense |= hll.is_dense();
+                hll.merge_into(&mut max)?;
+            }
+        }
+
+        self.hyperloglog_mut(destination, |hll| {
+            if use_dense {
+                hll.make_dense()?;
+            }
+            for (index, &register) in max.iter().enumerate() {
+                if register > 0 {
+                    hll.set(index, register)?;
+                }
+            }
+            hll.invalidate_cache();
+            Ok(())
+        })
+    }
+
+    /// The HyperLogLog at `key`, fails with `WRONGTYPE` if the key holds another type
+    /// or a string that isn't a HyperLogLog.
+    fn hyperloglog(&mut self, key: &[u8]) -> Result<Option<HyperLogLog<Bytes>>> {
+        self.string(key)?
+            .map(|data| HyperLogLog::parse(data.clone()))
+            .transpose()
+    }
+
+    /// Lets `change` edit the HyperLogLog at `key` in the string's own buffer,
+    /// creating it if needed.
+    fn hyperloglog_mut<T>(
+        &mut self,
+        key: &[u8],
+        change: impl FnOnce(&mut HyperLogLog) -> Result<T>,
+    ) -> Result<T> {
+        // Checked before `with_string_mut` creates a missing key.
+        self.hyperloglog(key)?;
+        self.with_string_mut(key, |data| {
+            let mut hll = if data.is_empty() {
+                HyperLogLog::new()
+            } else {
+                HyperLogLog(mem::take(data))
+            };
+            let result = change(&mut hll);
+            *data = hll.0;
+            result
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use super::{murmur_hash64a, HyperLogLog, DENSE_SIZE};
+    use crate::{
+        db::{Db, SetCondition, SetExpiry},
+        error::Error,
+    };
+
+    #[test]
+    fn hashes_match_redis() {
+        assert_eq!(murmur_hash64a(b"", 0xadc83b19), 0xd8df_ea65_85bc_9732);
+        assert_eq!(murmur_hash64a(b"hello", 0xadc83b19), 0x0f65_6f01_eecf_e400);
+        assert_eq!(
+            murmur_hash64a(b"0123456789abcdef!", 0xadc83b19),
+            0xb917_c99b_031f_7674
+        );
+    }
+
+    #[test]
+    fn sparse_grows_into_dense() {
+        let mut db = Db::new();
+        assert!(db.pfadd(b"h", &[]).unwrap());
+        let empty = db.get(b"h").unwrap().unwrap();
+        assert_eq!(&empty[..], b"HYLL\x01\0\0\0\0\0\0\0\0\0\0\x80\x7f\xff");
+        assert!(!db.pfadd(b"h", &[]).unwrap());
+
+        let elements: Vec<Bytes> = (0..1000).map(|i| i.to_string().into()).collect();
+        assert!(db.pfadd(b"h", &elements[..100]).unwrap());
+        assert!(!db.pfadd(b"h", &elements[..100]).unwrap());
+        let sparse = db.get(b"h").unwrap().unwrap();
+        assert!(sparse.len() < 3000 && sparse[4] == 1);
+        let count = db.pfcount(&["h".into()]).unwrap();
+        assert!((95..=105).contains(&count));
+        let cached = db.get(b"h").unwrap().unwrap();
+        assert_eq!(cached[8..16], count.to_le_bytes());
+        assert_eq!(db.pfcount(&["h".into()]).unwrap(), count);
+
+        db.pfadd(b"other", &elements[100..]).unwrap();
+        db.pfmerge(b"merged", &["h".into(), "other".into()])
+            .unwrap();
+        let merged = db.pfcount(&["merged".into()]).unwrap();
+        assert!((970..=1030).contains(&merged));
+        assert_eq!(
+            db.pfcount(&["h".into(), "other".into(), "missing".into()])
+                .unwrap(),
+            merged
+        );
+
+        // Runs covering one register short of all of them.
+        db.set(
+            "corrupt".into(),
+            b"HYLL\x01\0\0\0\0\0\0\0\0\0\0\x80\x7f\xfe"[..].into(),
+            SetCondition::Always,
+            SetExpiry::Persist,
+            false,
+        )
+        .unwrap();
+        assert!(matches!(
+            db.pfcount(&["corrupt".into()]),
+            Err(Error::CorruptedHll)
+        ));
+
+        let mut dense = HyperLogLog::new();
+        dense.make_dense().unwrap();
+        assert_eq!(dense.0.len(), DENSE_SIZE);
+        assert_eq!(dense.count().unwrap(), 0);
+    }
+}
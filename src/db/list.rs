@@ -0,0 +1,373 @@
+use std::collections::VecDeque;
+
+use bytes::Bytes;
+
+use crate::error::{Error, Result};
+
+use super::{Db, Entry, Value};
+
+/// Which end of a list an element is pushed to or popped from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListEnd {
+    Left,
+    Right,
+}
+
+impl Db {
+    /// Pushes `elements` one after the other at the `end` of the list at `key`,
+    /// creating it unless `only_if_exists` is set. Returns the length of the list.
+    pub fn push(
+        &mut self,
+        key: Bytes,
+        elements: Vec<Bytes>,
+        end: ListEnd,
+        only_if_exists: bool,
+    ) -> Result<usize> {
+        let list = match self.list_mut(&key)? {
+            Some(list) => list,
+            None if only_if_exists => return Ok(0),
+            None => {
+                let entry = Entry {
+                    value: Value::List(VecDeque::new()),
+                    expires_at: None,
+                };
+                self.insert_entry(key.clone(), entry);
+                self.list_mut(&key)?.unwrap()
+            }
+        };
+        for element in elements {
+            match end {
+                ListEnd::Left => list.push_front(element),
+                ListEnd::Right => list.push_back(element),
+            }
+        }
+        let length = list.len();
+        self.signal_ready(&key);
+        Ok(length)
+    }
+
+    /// Pops up to `count` elements from the `end` of the list at `key`, deleting the
+    /// key once it's empty. Returns `None` if the key doesn't exist.
+    pub fn pop(&mut self, key: &[u8], end: ListEnd, count: usize) -> Result<Option<Vec<Bytes>>> {
+        let list = match self.list_mut(key)? {
+            Some(list) => list,
+            None => return Ok(None),
+        };
+        let count = count.min(list.len());
+        let popped = match end {
+            ListEnd::Left => list.drain(..count).collect(),
+            ListEnd::Right => list.drain(list.len() - count..).rev().collect(),
+        };
+        self.remove_if_empty(key);
+        Ok(Some(popped))
+    }
+
+    /// Pops up to `count` elements from the `end` of the first non-empty list among
+    /// `keys`, returned along with its key.
+    pub fn mpop(
+        &mut self,
+        keys: &[Bytes],
+        end: ListEnd,
+        count: usize,
+    ) -> Result<Option<(Bytes, Vec<Bytes>)>> {
+        for key in keys {
+            if let Some(popped) = self.pop(key, end, count)? {
+                return Ok(Some((key.clone(), popped)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Pops an element from the `from` end of the list at `source` and pushes it to the
+    /// `to` end of the list at `destination`. Returns the element moved, `None` if
+    /// `source` doesn't exist.
+    pub fn lmove(
+        &mut self,
+        source: &[u8],
+        destination: Bytes,
+        from: ListEnd,
+        to: ListEnd,
+    ) -> Result<Option<Bytes>> {
+        if self.list(source)?.is_none() {
+            return Ok(None);
+        }
+        // Nothing is popped if it can't be pushed.
+        self.list(&destination)?;
+        let element = match self.pop(source, from, 1)? {
+            Some(mut popped) => popped.remove(0),
+            None => return Ok(None),
+        };
+        self.push(destination, vec![element.clone()], to, false)?;
+        Ok(Some(element))
+    }
+
+    pub fn llen(&mut self, key: &[u8]) -> Result<usize> {
+        Ok(self.list(key)?.map_or(0, |list| list.len()))
+    }
+
+    /// The elements between the inclusive indexes `start` and `stop`, negative indexes
+    /// counting from the end of the list.
+    pub fn lrange(&mut self, key: &[u8], start: i64, stop: i64) -> Result<Vec<Bytes>> {
+        let list = match self.list(key)? {
+            Some(list) => list,
+            None => return Ok(Vec::new()),
+        };
+        Ok(match index_range(start, stop, list.len()) {
+            Some((start, stop)) => list.range(start..=stop).cloned().collect(),
+            None => Vec::new(),
+        })
+    }
+
+    pub fn lindex(&mut self, key: &[u8], index: i64) -> Result<Option<Bytes>> {
+        let list = match self.list(key)? {
+            Some(list) => list,
+            None => return Ok(None),
+        };
+        Ok(list_index(index, list.len()).map(|index| list[index].clone()))
+    }
+
+    pub fn lset(&mut self, key: &[u8], index: i64, element: Bytes) -> Result<()> {
+        let list = self.list_mut(key)?.ok_or(Error::NoSuchKey)?;
+        let index = list_index(index, list.len())
+            .ok_or_else(|| Error::OutOfRange("index out of range".to_string()))?;
+        list[index] = element;
+        Ok(())
+    }
+
+    /// Removes the first `count` occurrences of `element`, the last ones if `count` is
+    /// negative and all of them if it's `0`. Returns the number of elements removed.
+    pub fn lrem(&mut self, key: &[u8], count: i64, element: &[u8]) -> Result<usize> {
+        let list = match self.list_mut(key)? {
+            Some(list) => list,
+            None => return Ok(0),
+        };
+        let limit = match count {
+            0 => usize::MAX,
+            count => count.unsigned_abs() as usize,
+        };
+        // Going from the tail is the same as going from the head of the reversed list.
+        if count < 0 {
+            list.make_contiguous().reverse();
+        }
+        let mut removed = 0;
+        list.retain(|candidate| {
+            let remove = removed < limit && candidate == element;
+            removed += remove as usize;
+            !remove
+        });
+        if count < 0 {
+            list.make_contiguous().reverse();
+        }
+        self.remove_if_empty(key);
+        Ok(removed)
+    }
+
+    /// Only keeps the elements between the inclusive indexes `start` and `stop`.
+    pub fn ltrim(&mut self, key: &[u8], start: i64, stop: i64) -> Result<()> {
+        let list = match self.list_mut(key)? {
+            Some(list) => list,
+            None => return Ok(()),
+        };
+        match index_range(start, stop, list.len()) {
+            Some((start, stop)) => {
+                list.truncate(stop + 1);
+                list.drain(..start);
+            }
+            None => list.clear(),
+        }
+        self.remove_if_empty(key);
+        Ok(())
+    }
+
+    /// Inserts `element` next to the first occurrence of `pivot`, before it if `before`
+    /// is set. Returns the length of the list, `0` if the key doesn't exist and `-1` if
+    /// `pivot` wasn't found.
+    pub fn linsert(
+        &mut self,
+        key: &[u8],
+        before: bool,
+        pivot: &[u8],
+        element: Bytes,
+    ) -> Result<i64> {
+        let list = match self.list_mut(key)? {
+            Some(list) => list,
+            None => return Ok(0),
+        };
+        let position = match list.iter().position(|candidate| candidate == pivot) {
+            Some(position) => position,
+            None => return Ok(-1),
+        };
+        list.insert(if before { position } else { position + 1 }, element);
+        Ok(list.len() as i64)
+    }
+
+    /// The indexes of `element` in the list at `key`, starting from the `rank`th match
+    /// and going backwards from the tail if `rank` is negative. At most `count`
+    /// indexes are returned, all of them if it's `0`, and only the first `max_len`
+    /// elements are compared unless it's `0`.
+    pub fn lpos(
+        &mut self,
+        key: &[u8],
+        element: &[u8],
+        rank: i64,
+        count: usize,
+        max_len: usize,
+    ) -> Result<Vec<usize>> {
+        let list = match self.list(key)? {
+            Some(list) => list,
+            None => return Ok(Vec::new()),
+        };
+        let count = if count == 0 { usize::MAX } else { count };
+        let max_len = if max_len == 0 { usize::MAX } else { max_len };
+        let indexes: Box<dyn Iterator<Item = usize>> = if rank > 0 {
+            Box::new(0..list.len())
+        } else {
+            Box::new((0..list.len()).rev())
+        };
+        Ok(indexes
+            .take(max_len)
+            .filter(|&index| list[index] == element)
+            .skip(rank.unsigned_abs() as usize - 1)
+            .take(count)
+            .collect())
+    }
+
+    /// The list at `key`, fails with `WRONGTYPE` if the key holds another type.
+    fn list(&mut self, key: &[u8]) -> Result<Option<&VecDeque<Bytes>>> {
+        self.live_entry(key)
+            .map(|entry| entry.value.as_list())
+            .transpose()
+    }
+
+    /// Mutable version of `list`.
+    fn list_mut(&mut self, key: &[u8]) -> Result<Option<&mut VecDeque<Bytes>>> {
+        self.live_entry_mut(key)
+            .map(|entry| entry.value.as_list_mut())
+            .transpose()
+    }
+}
+
+/// Turns the inclusive range `start..=stop` of a sequence of `len` elements, where
+/// negative indexes count from the end, into a valid range of indexes. `None` if it
+/// doesn't hold any element.
+pub(super) fn index_range(start: i64, stop: i64, len: usize) -> Option<(usize, usize)> {
+    let len = len as i64;
+    let start = if start < 0 { len + start } else { start }.max(0);
+    let stop = if stop < 0 { len + stop } else { stop }.min(len - 1);
+    if start > stop || start >= len {
+        return None;
+    }
+    Some((start as usize, stop as usize))
+}
+
+/// Turns `index`, negative if counting from the end, into an index of a sequence of
+/// `len` elements.
+fn list_index(index: i64, len: usize) -> Option<usize> {
+    let index = if index < 0 { len as i64 + index } else { index };
+    (0..len as i64).contains(&index).then_some(index as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use super::ListEnd;
+    use crate::db::Db;
+
+    fn list(elements: &[&'static str]) -> Vec<Bytes> {
+        elements
+            .iter()
+            .map(|element| Bytes::from(*element))
+            .collect()
+    }
+
+    #[test]
+    fn push_pop_and_range() {
+        let mut db = Db::new();
+        assert_eq!(
+            db.push("l".into(), list(&["a", "b"]), ListEnd::Left, true)
+                .unwrap(),
+            0
+        );
+        db.push("l".into(), list(&["b", "a"]), ListEnd::Left, false)
+            .unwrap();
+        db.push("l".into(), list(&["c", "d"]), ListEnd::Right, false)
+            .unwrap();
+        assert_eq!(db.lrange(b"l", 0, -1).unwrap(), list(&["a", "b", "c", "d"]));
+        assert_eq!(db.lrange(b"l", -3, 1).unwrap(), list(&["b"]));
+        assert_eq!(db.lrange(b"l", 2, 100).unwrap(), list(&["c", "d"]));
+        assert_eq!(db.lrange(b"l", -100, -5).unwrap(), list(&[]));
+        assert_eq!(db.lindex(b"l", -1).unwrap(), Some("d".into()));
+        assert_eq!(db.lindex(b"l", 4).unwrap(), None);
+
+        assert_eq!(
+            db.pop(b"l", ListEnd::Right, 3).unwrap(),
+            Some(list(&["d", "c", "b"]))
+        );
+        assert_eq!(db.pop(b"l", ListEnd::Left, 3).unwrap(), Some(list(&["a"])));
+        // The emptied list is deleted.
+        assert_eq!(db.pop(b"l", ListEnd::Left, 1).unwrap(), None);
+        assert_eq!(db.type_of(b"l"), "none");
+    }
+
+    #[test]
+    fn lrem_trim_and_lpos() {
+        let mut db = Db::new();
+        let elements = list(&["a", "b", "a", "c", "a", "b"]);
+        db.push("l".into(), elements, ListEnd::Right, false)
+            .unwrap();
+        assert_eq!(db.lpos(b"l", b"a", 1, 0, 0).unwrap(), vec![0, 2, 4]);
+        assert_eq!(db.lpos(b"l", b"a", -2, 1, 0).unwrap(), vec![2]);
+        assert_eq!(db.lpos(b"l", b"a", 1, 0, 3).unwrap(), vec![0, 2]);
+
+        assert_eq!(db.lrem(b"l", -2, b"a").unwrap(), 2);
+        assert_eq!(db.lrange(b"l", 0, -1).unwrap(), list(&["a", "b", "c", "b"]));
+        assert_eq!(db.lrem(b"l", 1, b"b").unwrap(), 1);
+        assert_eq!(db.lrange(b"l", 0, -1).unwrap(), list(&["a", "c", "b"]));
+
+        db.ltrim(b"l", 1, -1).unwrap();
+        assert_eq!(db.lrange(b"l", 0, -1).unwrap(), list(&["c", "b"]));
+        db.ltrim(b"l", 5, 10).unwrap();
+        assert_eq!(db.llen(b"l").unwrap(), 0);
+        assert_eq!(db.type_of(b"l"), "none");
+    }
+
+    #[test]
+    fn lmove_and_mpop_signal_the_keys_written_to() {
+        let mut db = Db::new();
+        for key in ["a", "b"] {
+            db.add_blocked_key(key.into());
+        }
+        db.push("a".into(), list(&["1", "2"]), ListEnd::Right, false)
+            .unwrap();
+        assert_eq!(db.take_ready_keys(), list(&["a"]));
+
+        let moved = db.lmove(b"a", "b".into(), ListEnd::Left, ListEnd::Right);
+        assert_eq!(moved.unwrap(), Some("1".into()));
+        assert_eq!(db.take_ready_keys(), list(&["b"]));
+        assert_eq!(
+            db.lmove(b"c", "b".into(), ListEnd::Left, ListEnd::Right)
+                .unwrap(),
+            None
+        );
+
+        let keys = list(&["c", "b", "a"]);
+        assert_eq!(
+            db.mpop(&keys, ListEnd::Left, 5).unwrap(),
+            Some(("b".into(), list(&["1"])))
+        );
+        assert!(db.take_ready_keys().is_empty());
+
+        // Writes to keys no client is blocked on aren't recorded.
+        db.remove_blocked_key(b"b");
+        db.push("b".into(), list(&["1"]), ListEnd::Right, false)
+            .unwrap();
+        db.push("c".into(), list(&["1"]), ListEnd::Right, false)
+            .unwrap();
+        db.push("a".into(), list(&["1"]), ListEnd::Right, false)
+            .unwrap();
+        db.push("a".into(), list(&["2"]), ListEnd::Right, false)
+            .unwrap();
+        assert_eq!(db.take_ready_keys(), list(&["a"]));
+    }
+}
@@ -0,0 +1,538 @@
+use std::collections::HashMap;
+
+use bytes::Bytes;
+
+mod bitmap;
+mod expire;
+mod hash;
+mod hyperloglog;
+mod list;
+mod scan;
+mod set;
+mod skiplist;
+mod stream;
+mod string;
+mod value;
+mod zset;
+
+pub use bitmap::{BitFieldOp, BitFieldOverflow, BitFieldType, BitOp, BitRange, BitUnit};
+use expire::VolatileKeys;
+pub use expire::ACTIVE_EXPIRE_INTERVAL;
+pub use list::ListEnd;
+use scan::KeySlots;
+pub use set::SetOp;
+pub use stream::{
+    StreamFields, StreamId, StreamRead, StreamTrim, TrimStrategy, XAddId, XClaimOptions,
+};
+pub use string::{SetCondition, SetExpiry};
+pub use value::Value;
+pub use zset::{LexBound, ScoreBound, ZAddFlags, ZAggregate, ZRange};
+
+use crate::{
+    error::{Error, Result},
+    utils::{Clock, SystemClock},
+};
+
+pub struct Db {
+    values: HashMap<Bytes, Entry>,
+    /// Index of the keys in `values` that have a time to live.
+    volatile_keys: VolatileKeys,
+    /// Index of the keys in `values` holding hashes with fields that have a time to
+    /// live, it may also hold keys whose fields no longer do.
+    volatile_hashes: VolatileKeys,
+    /// Stable positions of the keys in `values` for `SCAN`.
+    key_slots: KeySlots,
+    /// Every time lookup goes through here so tests can control time.
+    clock: Box<dyn Clock>,
+    /// The keys clients are blocked on, along with whether they were written to since
+    /// the last `take_ready_keys`.
+    blocked_keys: HashMap<Bytes, bool>,
+    /// The blocked keys written to since the last `take_ready_keys`, clients blocked on
+    /// them may now be served.
+    ready_keys: Vec<Bytes>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Entry {
+    value: Value,
+    /// The unix time in milliseconds the entry expires at.
+    expires_at: Option<u64>,
+}
+
+/// The `NX`, `XX`, `GT` and `LT` flags of `EXPIRE` and friends.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExpireFlags {
+    /// Only set the expiry if the key has none.
+    pub nx: bool,
+    /// Only set the expiry if the key already has one.
+    pub xx: bool,
+    /// Only set the expiry if it's later than the current one.
+    pub gt: bool,
+    /// Only set the expiry if it's sooner than the current one.
+    pub lt: bool,
+}
+
+impl ExpireFlags {
+    /// Whether the flags allow replacing the `current` expiry with `deadline`.
+    fn allows(&self, current: Option<u64>, deadline: i64) -> bool {
+        match current {
+            // No expiry counts as an infinite time to live for `GT` and `LT`.
+            None => !self.xx && !self.gt,
+            Some(current) => {
+                let current = current as i64;
+                !self.nx && (!self.gt || deadline > current) && (!self.lt || deadline < current)
+            }
+        }
+    }
+}
+
+/// Whether the unix time `deadline` in milliseconds has passed at `now`. Keys and hash
+/// fields live through their deadline, whether it's checked on a read or a write.
+fn deadline_passed(deadline: u64, now: u64) -> bool {
+    now > deadline
+}
+
+impl Entry {
+    fn is_expired(&self, now: u64) -> bool {
+        self.expires_at
+            .map_or(false, |expires_at| deadline_passed(expires_at, now))
+    }
+}
+
+impl Db {
+    pub fn new() -> Db {
+        Self::with_clock(Box::new(SystemClock))
+    }
+
+    /// Creates a database reading the time from `clock`.
+    pub fn with_clock(clock: Box<dyn Clock>) -> Db {
+        Db {
+            values: HashMap::new(),
+            volatile_keys: VolatileKeys::default(),
+            volatile_hashes: VolatileKeys::default(),
+            key_slots: KeySlots::default(),
+            clock,
+            blocked_keys: HashMap::new(),
+            ready_keys: Vec::new(),
+        }
+    }
+
+    /// Removes the given keys, returns how many of them existed.
+    pub fn del(&mut self, keys: &[Bytes]) -> i64 {
+        let now = self.now();
+        keys.iter()
+            .filter(|key| {
+                self.remove_entry(key)
+                    .map_or(false, |entry| !entry.is_expired(now))
+            })
+            .count() as i64
+    }
+
+    /// Counts how many of the given keys exist, keys given more than once are counted
+    /// more than once.
+    pub fn exists(&mut self, keys: &[Bytes]) -> i64 {
+        keys.iter()
+            .filter(|key| self.live_entry(key).is_some())
+            .count() as i64
+    }
+
+    /// The name of the type of the value at `key` as reported by `TYPE`.
+    pub fn type_of(&mut self, key: &[u8]) -> &'static str {
+        match self.live_entry(key) {
+            Some(entry) => entry.value.type_name(),
+            None => "none",
+        }
+    }
+
+    /// The encoding of the value at `key` as reported by `OBJECT ENCODING`.
+    pub fn encoding(&mut self, key: &[u8]) -> Option<&'static str> {
+        self.live_entry(key).map(|entry| entry.value.encoding())
+    }
+
+    /// Moves the entry at `key` to `new_key` along with its time to live.
+    /// If `only_if_missing` is set, nothing happens when `new_key` exists and `false`
+    /// is returned.
+    pub fn rename(&mut self, key: &[u8], new_key: Bytes, only_if_missing: bool) -> Result<bool> {
+        if self.live_entry(key).is_none() {
+            return Err(Error::NoSuchKey);
+        }
+        if key == new_key {
+            return Ok(!only_if_missing);
+        }
+        if only_if_missing && self.live_entry(&new_key).is_some() {
+            return Ok(false);
+        }
+        let entry = self.remove_entry(key).unwrap();
+        self.insert_entry(new_key, entry);
+        Ok(true)
+    }
+
+    /// Copies the entry at `source` to `destination` along with its time to live.
+    /// Unless `replace` is set, nothing is copied over an existing `destination`.
+    pub fn copy(&mut self, source: &[u8], destination: Bytes, replace: bool) -> bool {
+        let entry = match self.live_entry(source) {
+            Some(entry) => entry.clone(),
+            None => return false,
+        };
+        if !replace && self.live_entry(&destination).is_some() {
+            return false;
+        }
+        self.insert_entry(destination, entry);
+        true
+    }
+
+    /// Sets the expiry of `key` to the unix time `deadline` in milliseconds, provided
+    /// the key exists and `flags` allow it. A deadline that has already passed deletes
+    /// the key. Returns whether the expiry was changed.
+    pub fn expire(&mut self, key: &[u8], deadline: i64, flags: ExpireFlags) -> bool {
+        let current = match self.live_entry(key) {
+            Some(entry) => entry.expires_at,
+            None => return false,
+        };
+        if !flags.allows(current, deadline) {
+            return false;
+        }
+
+        self.set_expiry(key, Some(deadline.max(0) as u64));
+        true
+    }
+
+    /// Removes the expiry of `key`, returns whether it had one.
+    pub fn persist(&mut self, key: &[u8]) -> bool {
+        match self.live_entry(key) {
+            Some(entry) if entry.expires_at.is_some() => {
+                self.set_expiry(key, None);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// The unix time in milliseconds `key` expires at, `None` if the key doesn't exist
+    /// and `Some(None)` if it doesn't expire.
+    pub fn deadline(&mut self, key: &[u8]) -> Option<Option<u64>> {
+        self.live_entry(key).map(|entry| entry.expires_at)
+    }
+
+    /// Starts recording writes to `key` for `take_ready_keys`, a client is blocked on it.
+    pub fn add_blocked_key(&mut self, key: Bytes) {
+        self.blocked_keys.entry(key).or_insert(false);
+    }
+
+    /// Stops recording writes to `key`, no client is blocked on it anymore.
+    pub fn remove_blocked_key(&mut self, key: &[u8]) {
+        self.blocked_keys.remove(key);
+    }
+
+    /// Hands over the blocked keys written to since the last call, in the order they
+    /// were first written to.
+    pub fn take_ready_keys(&mut self) -> Vec<Bytes> {
+        let ready_keys = std::mem::take(&mut self.ready_keys);
+        for key in &ready_keys {
+            if let Some(ready) = self.blocked_keys.get_mut(key) {
+                *ready = false;
+            }
+        }
+        ready_keys
+    }
+
+    /// The current unix time in milliseconds as seen by the database.
+    pub fn now(&self) -> u64 {
+        self.clock.now()
+    }
+
+    /// Changes the expiry of the existing `key`, keeping the index of volatile keys up
+    /// to date. A deadline that has already passed deletes the key.
+    fn set_expiry(&mut self, key: &[u8], expires_at: Option<u64>) {
+        if matches!(expires_at, Some(deadline) if deadline_passed(deadline, self.now())) {
+            self.remove_entry(key);
+            return;
+        }
+        if let Some(entry) = self.values.get_mut(key) {
+            entry.expires_at = expires_at;
+            if expires_at.is_some() {
+                self.volatile_keys.insert(Bytes::copy_from_slice(key));
+            } else {
+                self.volatile_keys.remove(key);
+            }
+        }
+    }
+
+    /// Inserts `entry` at `key`, keeping the key indexes up to date.
+    fn insert_entry(&mut self, key: Bytes, entry: Entry) {
+        self.signal_ready(&key);
+        self.key_slots.insert(key.clone());
+        if entry.expires_at.is_some() {
+            self.volatile_keys.insert(key.clone());
+        } else {
+            self.volatile_keys.remove(&key);
+        }
+        if matches!(&entry.value, Value::Hash(hash) if hash.has_volatile_fields()) {
+            self.volatile_hashes.insert(key.clone());
+        } else {
+            self.volatile_hashes.remove(&key);
+        }
+        self.values.insert(key, entry);
+    }
+
+    /// Removes the entry at `key`, keeping the key indexes up to date.
+    fn remove_entry(&mut self, key: &[u8]) -> Option<Entry> {
+        self.key_slots.remove(key);
+        self.volatile_keys.remove(key);
+        self.volatile_hashes.remove(key);
+        self.values.remove(key)
+    }
+
+    /// Records that `key` was written to for `take_ready_keys`, if a client is blocked
+    /// on it.
+    fn signal_ready(&mut self, key: &[u8]) {
+        if let Some(ready) = self.blocked_keys.get_mut(key) {
+            if !*ready {
+                *ready = true;
+                self.ready_keys.push(Bytes::copy_from_slice(key));
+            }
+        }
+    }
+
+    /// Removes `key` if it holds an empty collection, Redis deletes those as soon as
+    /// their last element is removed.
+    fn remove_if_empty(&mut self, key: &[u8]) {
+        if self
+            .values
+            .get(key)
+            .map_or(false, |entry| entry.value.is_empty())
+        {
+            self.remove_entry(key);
+        }
+    }
+
+    /// Looks up the entry at `key`, lazily removing it if it has expired along with the
+    /// expired fields of hashes.
+    fn live_entry(&mut self, key: &[u8]) -> Option<&Entry> {
+        self.live_entry_mut(key).map(|entry| &*entry)
+    }
+
+    /// Mutable version of `live_entry`.
+    fn live_entry_mut(&mut self, key: &[u8]) -> Option<&mut Entry> {
+        let now = self.now();
+        let entry = self.values.get_mut(key)?;
+        if entry.is_expired(now) {
+            self.remove_entry(key);
+            return None;
+        }
+        if let Value::Hash(hash) = &mut entry.value {
+            // A hash whose last field expired is gone too.
+            if hash.remove_expired(now) > 0 && hash.is_empty() {
+                self.remove_entry(key);
+                return None;
+            }
+        }
+        self.values.get_mut(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Db, ExpireFlags, SetCondition, SetExpiry};
+    use crate::utils::FakeClock;
+
+    /// A database at the unix time 1000 along with the handle on its clock.
+    fn db_with_fake_clock() -> (Db, FakeClock) {
+        let clock = FakeClock::new(1000);
+        (Db::with_clock(Box::new(clock.clone())), clock)
+    }
+
+    #[test]
+    fn conditional_sets() {
+        let mut db = Db::new();
+        let outcome = db
+            .set(
+                "k".into(),
+                "a".into(),
+                SetCondition::IfExists,
+                SetExpiry::Persist,
+                false,
+            )
+            .unwrap();
+        assert!(!outcome.written);
+        assert_eq!(db.get(b"k").unwrap(), None);
+
+        let outcome = db
+            .set(
+                "k".into(),
+                "a".into(),
+                SetCondition::IfMissing,
+                SetExpiry::Persist,
+                false,
+            )
+            .unwrap();
+        assert!(outcome.written);
+        let outcome = db
+            .set(
+                "k".into(),
+                "b".into(),
+                SetCondition::IfMissing,
+                SetExpiry::Persist,
+                true,
+            )
+            .unwrap();
+        assert!(!outcome.written);
+        assert_eq!(outcome.previous, Some("a".into()));
+        assert_eq!(db.get(b"k").unwrap(), Some("a".into()));
+    }
+
+    #[test]
+    fn keepttl_retains_the_deadline() {
+        let (mut db, _) = db_with_fake_clock();
+        db.set(
+            "k".into(),
+            "a".into(),
+            SetCondition::Always,
+            SetExpiry::In(60_000),
+            false,
+        )
+        .unwrap();
+        db.set(
+            "k".into(),
+            "b".into(),
+            SetCondition::Always,
+            SetExpiry::Keep,
+            false,
+        )
+        .unwrap();
+        assert_eq!(db.values[&b"k"[..]].expires_at, Some(61_000));
+
+        db.set(
+            "k".into(),
+            "c".into(),
+            SetCondition::Always,
+            SetExpiry::Persist,
+            false,
+        )
+        .unwrap();
+        assert_eq!(db.values[&b"k"[..]].expires_at, None);
+    }
+
+    #[test]
+    fn rename_and_copy_keep_the_ttl() {
+        let (mut db, _) = db_with_fake_clock();
+        db.set(
+            "a".into(),
+            "1".into(),
+            SetCondition::Always,
+            SetExpiry::In(60_000),
+            false,
+        )
+        .unwrap();
+        assert!(db.rename(b"a", "b".into(), false).unwrap());
+        assert!(db.copy(b"b", "c".into(), false));
+        assert_eq!(db.values[&b"b"[..]].expires_at, Some(61_000));
+        assert_eq!(db.values[&b"c"[..]].expires_at, Some(61_000));
+        assert_eq!(db.del(&["a".into(), "b".into(), "c".into()]), 2);
+        assert!(db.rename(b"a", "b".into(), false).is_err());
+    }
+
+    #[test]
+    fn expire_flags() {
+        let (mut db, _) = db_with_fake_clock();
+        let later = 61_000;
+        let gt = ExpireFlags {
+            gt: true,
+            ..Default::default()
+        };
+        let lt = ExpireFlags {
+            lt: true,
+            ..Default::default()
+        };
+        assert!(!db.expire(b"k", later, ExpireFlags::default()));
+
+        db.set(
+            "k".into(),
+            "a".into(),
+            SetCondition::Always,
+            SetExpiry::Persist,
+            false,
+        )
+        .unwrap();
+        // A key without expiry has an infinite time to live.
+        assert!(!db.expire(b"k", later, gt));
+        assert!(db.expire(b"k", later, lt));
+        assert!(!db.expire(b"k", later - 1, gt));
+        assert!(db.expire(b"k", later + 1, gt));
+        assert_eq!(db.deadline(b"k"), Some(Some(later as u64 + 1)));
+
+        assert!(db.persist(b"k"));
+        assert_eq!(db.deadline(b"k"), Some(None));
+        assert!(db.expire(b"k", 0, ExpireFlags::default()));
+        assert_eq!(db.deadline(b"k"), None);
+    }
+
+    #[test]
+    fn keys_expire_once_their_deadline_has_passed() {
+        let (mut db, clock) = db_with_fake_clock();
+        db.set(
+            "k".into(),
+            "a".into(),
+            SetCondition::Always,
+            SetExpiry::In(100),
+            false,
+        )
+        .unwrap();
+        assert_eq!(db.deadline(b"k"), Some(Some(1100)));
+
+        clock.advance(100);
+        assert_eq!(db.get(b"k").unwrap(), Some("a".into()));
+        clock.advance(1);
+        assert_eq!(db.get(b"k").unwrap(), None);
+        assert_eq!(db.deadline(b"k"), None);
+    }
+
+    #[test]
+    fn writes_and_reads_agree_on_the_deadline() {
+        let (mut db, clock) = db_with_fake_clock();
+        let set_at = |db: &mut Db, key: &'static str, deadline| {
+            db.set(
+                key.into(),
+                "a".into(),
+                SetCondition::Always,
+                SetExpiry::At(deadline),
+                false,
+            )
+            .unwrap();
+        };
+        set_at(&mut db, "now", 1000);
+        set_at(&mut db, "past", 999);
+        assert_eq!(db.get(b"now").unwrap(), Some("a".into()));
+        assert_eq!(db.get(b"past").unwrap(), None);
+
+        set_at(&mut db, "k", 2000);
+        assert!(db.expire(b"k", 1000, ExpireFlags::default()));
+        assert_eq!(db.deadline(b"k"), Some(Some(1000)));
+        db.hset("h".into(), vec![("f".into(), "v".into())], false)
+            .unwrap();
+        let fields = ["f".into()];
+        let replies = db.hexpire(b"h", &fields, 1000, ExpireFlags::default());
+        assert_eq!(replies.unwrap(), vec![1]);
+
+        clock.advance(1);
+        assert_eq!(db.get(b"now").unwrap(), None);
+        assert_eq!(db.get(b"k").unwrap(), None);
+        assert_eq!(db.hget(b"h", b"f").unwrap(), None);
+        let replies = db.hexpire(b"h", &fields, 1000, ExpireFlags::default());
+        assert_eq!(replies.unwrap(), vec![-2]);
+    }
+
+    #[test]
+    fn past_deadlines_remove_the_key() {
+        let mut db = Db::new();
+        db.set(
+            "k".into(),
+            "a".into(),
+            SetCondition::Always,
+            SetExpiry::At(1),
+            false,
+        )
+        .unwrap();
+        assert_eq!(db.get(b"k").unwrap(), None);
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+
+use bytes::Bytes;
+
+use crate::utils::{glob_match, random_below};
+
+use super::Db;
+
+/// Gives every key of `Db` a slot that `SCAN` cursors point into, hashes do the same
+/// with their fields for `HSCAN`. A key keeps its slot for as long as it exists, so a
+/// full iteration returns every key that was present throughout exactly once. Freed
+/// slots are handed out to new keys.
+#[derive(Debug, Clone, Default)]
+pub(super) struct KeySlots {
+    slots: Vec<Option<Bytes>>,
+    /// Slot of each key, for constant time removal.
+    positions: HashMap<Bytes, usize>,
+    /// Slots freed by removed keys, may hold stale or duplicate positions.
+    free: Vec<usize>,
+}
+
+impl KeySlots {
+    pub fn insert(&mut self, key: Bytes) {
+        if self.positions.contains_key(&key) {
+            return;
+        }
+        let position = loop {
+            match self.free.pop() {
+                Some(position) if matches!(self.slots.get(position), Some(None)) => {
+                    break position;
+                }
+                Some(_) => continue,
+                None => {
+                    self.slots.push(None);
+                    break self.slots.len() - 1;
+                }
+            }
+        };
+        self.slots[position] = Some(key.clone());
+        self.positions.insert(key, position);
+    }
+
+    pub fn remove(&mut self, key: &[u8]) {
+        if let Some(position) = self.positions.remove(key) {
+            self.slots[position] = None;
+            self.free.push(position);
+            while let Some(None) = self.slots.last() {
+                self.slots.pop();
+            }
+        }
+    }
+
+    /// A key picked at random, `None` if there are none. Free slots are skipped by
+    /// picking again, unless most of them are free.
+    pub fn random(&self) -> Option<&Bytes> {
+        if self.positions.is_empty() {
+            return None;
+        }
+        if self.positions.len() * 2 < self.slots.len() {
+            return self
+                .positions
+                .keys()
+                .nth(random_below(self.positions.len()));
+        }
+        loop {
+            if let Some(key) = &self.slots[random_below(self.slots.len())] {
+                return Some(key);
+            }
+        }
+    }
+
+    /// The keys in the `count` slots starting at `cursor`, along with the cursor to
+    /// continue from which is `0` once all the slots have been gone through.
+    pub fn scan(&self, cursor: usize, count: usize) -> (Vec<Bytes>, usize) {
+        let end = cursor.saturating_add(count).min(self.slots.len());
+        let keys = self
+            .slots
+            .get(cursor..end)
+            .unwrap_or_default()
+            .iter()
+            .flatten()
+            .cloned()
+            .collect();
+        let next_cursor = if end < self.slots.len() { end } else { 0 };
+        (keys, next_cursor)
+    }
+}
+
+impl Db {
+    /// All the keys matching the glob-style `pattern`.
+    pub fn keys(&self, pattern: &[u8]) -> Vec<Bytes> {
+        let now = self.now();
+        self.values
+            .iter()
+            .filter(|(key, entry)| !entry.is_expired(now) && glob_match(pattern, key))
+            .map(|(key, _)| key.clone())
+            .collect()
+    }
+
+    /// Goes through `count` key slots from `cursor` and returns the keys matching
+    /// `pattern` and holding a `value_type`, along with the cursor to continue from.
+    pub fn scan(
+        &mut self,
+        cursor: usize,
+        count: usize,
+        pattern: Option<&[u8]>,
+        value_type: Option<&str>,
+    ) -> (Vec<Bytes>, usize) {
+        let (keys, next_cursor) = self.key_slots.scan(cursor, count);
+        let keys = keys
+            .into_iter()
+            .filter(|key| pattern.map_or(true, |pattern| glob_match(pattern, key)))
+            .filter(|key| match value_type {
+                Some(value_type) => self.type_of(key) == value_type,
+                None => self.live_entry(key).is_some(),
+            })
+            .collect();
+        (keys, next_cursor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use super::KeySlots;
+
+    #[test]
+    fn keys_keep_their_slot() {
+        let mut slots = KeySlots::default();
+        for key in ["a", "b", "c", "d"] {
+            slots.insert(key.into());
+        }
+        let (keys, cursor) = slots.scan(0, 2);
+        assert_eq!(keys, vec![Bytes::from("a"), Bytes::from("b")]);
+        assert_eq!(cursor, 2);
+
+        // Removing a key already returned doesn't shift the ones left to return.
+        slots.remove(b"a");
+        slots.insert("e".into());
+        let (keys, cursor) = slots.scan(cursor, 2);
+        assert_eq!(keys, vec![Bytes::from("c"), Bytes::from("d")]);
+        assert_eq!(cursor, 0);
+
+        let (keys, _) = slots.scan(0, 10);
+        assert_eq!(keys.len(), 4);
+        assert_eq!(keys[0], Bytes::from("e"));
+    }
+}
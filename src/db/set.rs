@@ -0,0 +1,431 @@
+use std::collections::HashSet;
+
+use bytes::Bytes;
+
+use crate::{
+    error::Result,
+    utils::{parse_integer, random_below},
+};
+
+use super::{Db, Entry, Value};
+
+/// Sets of integers with more members than this are converted to a hash table,
+/// Redis' default `set-max-intset-entries` of 512.
+const INTSET_MAX_ENTRIES: usize = 512;
+
+/// The members of a set. Sets of integers start as a sorted array like Redis' intset
+/// and are converted to a hash table once they get too big or a member that isn't an
+/// integer is added.
+#[derive(Debug, Clone)]
+pub enum Set {
+    IntSet(Vec<i64>),
+    HashTable(HashSet<Bytes>),
+}
+
+/// The set algebra of `SINTER`, `SUNION` and `SDIFF`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetOp {
+    Inter,
+    Union,
+    Diff,
+}
+
+impl Set {
+    pub fn len(&self) -> usize {
+        match self {
+            Set::IntSet(integers) => integers.len(),
+            Set::HashTable(members) => members.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn members(&self) -> Vec<Bytes> {
+        match self {
+            Set::IntSet(integers) => integers
+                .iter()
+                .map(|integer| integer.to_string().into())
+                .collect(),
+            Set::HashTable(members) => members.iter().cloned().collect(),
+        }
+    }
+
+    pub fn contains(&self, member: &[u8]) -> bool {
+        match self {
+            Set::IntSet(integers) => parse_integer(member)
+                .map_or(false, |integer| integers.binary_search(&integer).is_ok()),
+            Set::HashTable(members) => members.contains(member),
+        }
+    }
+
+    /// Random members, up to `count` distinct ones if it's positive or exactly
+    /// `-count` ones that may repeat if it's negative. Only the picked members are
+    /// copied.
+    fn random_members(&self, count: i64) -> Vec<Bytes> {
+        let len = self.len();
+        if len == 0 {
+            return Vec::new();
+        }
+        if count < 0 {
+            let mut positions: Vec<usize> = (0..count.unsigned_abs())
+                .map(|_| random_below(len))
+                .collect();
+            positions.sort_unstable();
+            let mut members = self.members_at(&positions);
+            // Undoes the order of the positions.
+            for i in (1..members.len()).rev() {
+                members.swap(i, random_below(i + 1));
+            }
+            return members;
+        }
+        // Each position is kept with the odds of the picks left over the positions
+        // left.
+        let count = (count as usize).min(len);
+        let mut positions = Vec::with_capacity(count);
+        for position in 0..len {
+            if random_below(len - position) < count - positions.len() {
+                positions.push(position);
+            }
+        }
+        self.members_at(&positions)
+    }
+
+    /// The members at the sorted `positions` of an iteration, which may repeat.
+    fn members_at(&self, positions: &[usize]) -> Vec<Bytes> {
+        match self {
+            Set::IntSet(integers) => positions
+                .iter()
+                .map(|&position| integers[position].to_string().into())
+                .collect(),
+            Set::HashTable(members) => {
+                let mut picked = Vec::with_capacity(positions.len());
+                let mut positions = positions.iter().peekable();
+                for (position, member) in members.iter().enumerate() {
+                    while positions.next_if_eq(&&position).is_some() {
+                        picked.push(member.clone());
+                    }
+                    if positions.peek().is_none() {
+                        break;
+                    }
+                }
+                picked
+            }
+        }
+    }
+
+    /// An empty set in the encoding fit to hold `member`.
+    fn for_member(member: &[u8]) -> Set {
+        match parse_integer(member) {
+            Some(_) => Set::IntSet(Vec::new()),
+            None => Set::HashTable(HashSet::new()),
+        }
+    }
+
+    /// Adds `member`, returns whether it's new.
+    fn insert(&mut self, member: Bytes) -> bool {
+        if let Set::IntSet(integers) = self {
+            match parse_integer(&member) {
+                Some(integer) if integers.len() < INTSET_MAX_ENTRIES => {
+                    return match integers.binary_search(&integer) {
+                        Ok(_) => false,
+                        Err(position) => {
+                            integers.insert(position, integer);
+                            true
+                        }
+                    };
+                }
+                Some(integer) if integers.binary_search(&integer).is_ok() => return false,
+                _ => *self = Set::HashTable(self.members().into_iter().collect()),
+            }
+        }
+        match self {
+            Set::HashTable(members) => members.insert(member),
+            Set::IntSet(_) => unreachable!("converted to a hash table above"),
+        }
+    }
+
+    /// Removes `member`, returns whether it existed.
+    fn remove(&mut self, member: &[u8]) -> bool {
+        match self {
+            Set::IntSet(integers) => {
+                let position =
+                    parse_integer(member).and_then(|integer| integers.binary_search(&integer).ok());
+                match position {
+                    Some(position) => {
+                        integers.remove(position);
+                        true
+                    }
+                    None => false,
+                }
+            }
+            Set::HashTable(members) => members.remove(member),
+        }
+    }
+}
+
+impl Db {
+    /// Adds `members` to the set at `key`, creating it if needed. Returns the number of
+    /// members added.
+    pub fn sadd(&mut self, key: Bytes, members: Vec<Bytes>) -> Result<usize> {
+        if self.set_value(&key)?.is_none() {
+            let entry = Entry {
+                value: Value::Set(Set::for_member(&members[0])),
+                expires_at: None,
+            };
+            self.insert_entry(key.clone(), entry);
+        }
+        let set = self.set_value_mut(&key)?.unwrap();
+        Ok(members
+            .into_iter()
+            .filter(|member| set.insert(member.clone()))
+            .count())
+    }
+
+    /// Removes `members` from the set at `key`, deleting the key once it's empty.
+    /// Returns the number of members removed.
+    pub fn srem(&mut self, key: &[u8], members: &[Bytes]) -> Result<usize> {
+        let set = match self.set_value_mut(key)? {
+            Some(set) => set,
+            None => return Ok(0),
+        };
+        let removed = members.iter().filter(|member| set.remove(member)).count();
+        self.remove_if_empty(key);
+        Ok(removed)
+    }
+
+    pub fn smembers(&mut self, key: &[u8]) -> Result<Vec<Bytes>> {
+        Ok(self
+            .set_value(key)?
+            .map_or_else(Vec::new, |set| set.members()))
+    }
+
+    pub fn sismember(&mut self, key: &[u8], member: &[u8]) -> Result<bool> {
+        Ok(self
+            .set_value(key)?
+            .map_or(false, |set| set.contains(member)))
+    }
+
+    pub fn smismember(&mut self, key: &[u8], members: &[Bytes]) -> Result<Vec<bool>> {
+        let set = self.set_value(key)?;
+        Ok(members
+            .iter()
+            .map(|member| set.map_or(false, |set| set.contains(member)))
+            .collect())
+    }
+
+    pub fn scard(&mut self, key: &[u8]) -> Result<usize> {
+        Ok(self.set_value(key)?.map_or(0, |set| set.len()))
+    }
+
+    /// Removes up to `count` random members from the set at `key` and returns them.
+    pub fn spop(&mut self, key: &[u8], count: usize) -> Result<Vec<Bytes>> {
+        let set = match self.set_value_mut(key)? {
+            Some(set) => set,
+            None => return Ok(Vec::new()),
+        };
+        let popped = set.random_members(count.min(set.len()) as i64);
+        for member in &popped {
+            set.remove(member);
+        }
+        self.remove_if_empty(key);
+        Ok(popped)
+    }
+
+    /// Random members of the set at `key`. A positive `count` picks up to that many
+    /// distinct members, a negative one picks exactly `-count` members that may repeat.
+    pub fn srandmember(&mut self, key: &[u8], count: i64) -> Result<Vec<Bytes>> {
+        Ok(self
+            .set_value(key)?
+            .map_or_else(Vec::new, |set| set.random_members(count)))
+    }
+
+    /// Moves `member` from the set at `source` to the set at `destination`. Returns
+    /// whether `member` was in `source`.
+    pub fn smove(&mut self, source: &[u8], destination: Bytes, member: Bytes) -> Result<bool> {
+        let is_member = self.sismember(source, &member)?;
+        // Nothing is removed if it can't be added.
+        self.set_value(&destination)?;
+        if !is_member || source == destination {
+            return Ok(is_member);
+        }
+        self.srem(source, std::slice::from_ref(&member))?;
+        self.sadd(destination, vec![member])?;
+        Ok(true)
+    }
+
+    /// The members resulting from applying `op` to the sets at `keys`, missing keys
+    /// counting as empty sets. With a `limit` the result is cut short once it holds
+    /// that many members.
+    pub fn set_op(
+        &mut self,
+        op: SetOp,
+        keys: &[Bytes],
+        limit: Option<usize>,
+    ) -> Result<Vec<Bytes>> {
+        let sets = self.sets(keys)?;
+        let limit = limit.unwrap_or(usize::MAX);
+        let members = match op {
+            SetOp::Inter => {
+                // Going through the smallest set means fewer lookups in the others.
+                let smallest = match sets.iter().min_by_key(|set| set.map_or(0, |set| set.len())) {
+                    Some(Some(smallest)) => smallest,
+                    _ => return Ok(Vec::new()),
+                };
+                smallest
+                    .members()
+                    .into_iter()
+                    .filter(|member| sets.iter().all(|set| set.unwrap().contains(member)))
+                    .take(limit)
+                    .collect()
+            }
+            SetOp::Union => {
+                let mut union = HashSet::new();
+                for set in sets.iter().flatten() {
+                    union.extend(set.members());
+                }
+                union.into_iter().take(limit).collect()
+            }
+            SetOp::Diff => {
+                let (first, others) = match sets.split_first() {
+                    Some((Some(first), others)) => (first, others),
+                    _ => return Ok(Vec::new()),
+                };
+                first
+                    .members()
+                    .into_iter()
+                    .filter(|member| !others.iter().flatten().any(|set| set.contains(member)))
+                    .take(limit)
+                    .collect()
+            }
+        };
+        Ok(members)
+    }
+
+    /// Stores the result of applying `op` to the sets at `keys` at `destination`,
+    /// replacing whatever it held. Returns the size of the resulting set.
+    pub fn set_op_store(&mut self, op: SetOp, destination: Bytes, keys: &[Bytes]) -> Result<usize> {
+        let members = self.set_op(op, keys, None)?;
+        let len = members.len();
+        self.remove_entry(&destination);
+        if len > 0 {
+            self.sadd(destination, members)?;
+        }
+        Ok(len)
+    }
+
+    /// The sets at `keys`, `None` for the missing ones. Fails with `WRONGTYPE` if any
+    /// key holds another type.
+    fn sets(&mut self, keys: &[Bytes]) -> Result<Vec<Option<&Set>>> {
+        // Looking the keys up first checks their type and removes the expired ones.
+        for key in keys {
+            self.set_value(key)?;
+        }
+        Ok(keys
+            .iter()
+            .map(|key| {
+                let entry = self.values.get(key)?;
+                entry.value.as_set().ok()
+            })
+            .collect())
+    }
+
+    /// The set at `key`, fails with `WRONGTYPE` if the key holds another type.
+    fn set_value(&mut self, key: &[u8]) -> Result<Option<&Set>> {
+        self.live_entry(key)
+            .map(|entry| entry.value.as_set())
+            .transpose()
+    }
+
+    /// Mutable version of `set_value`.
+    fn set_value_mut(&mut self, key: &[u8]) -> Result<Option<&mut Set>> {
+        self.live_entry_mut(key)
+            .map(|entry| entry.value.as_set_mut())
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use super::{Set, SetOp};
+    use crate::db::Db;
+
+    fn members(members: &[&'static str]) -> Vec<Bytes> {
+        members.iter().map(|member| Bytes::from(*member)).collect()
+    }
+
+    fn sorted(mut members: Vec<Bytes>) -> Vec<Bytes> {
+        members.sort();
+        members
+    }
+
+    #[test]
+    fn integer_sets_until_a_string_is_added() {
+        let mut set = Set::for_member(b"3");
+        assert!(set.insert("3".into()));
+        assert!(set.insert("-1".into()));
+        assert!(!set.insert("3".into()));
+        assert!(matches!(&set, Set::IntSet(integers) if integers == &[-1, 3]));
+        // Non canonical integers are strings.
+        assert!(set.insert("03".into()));
+        assert!(matches!(set, Set::HashTable(_)));
+        assert!(set.contains(b"-1"));
+        assert_eq!(set.len(), 3);
+    }
+
+    #[test]
+    fn set_algebra() {
+        let mut db = Db::new();
+        db.sadd("a".into(), members(&["1", "2", "3", "x"])).unwrap();
+        db.sadd("b".into(), members(&["2", "3", "y"])).unwrap();
+        let keys = members(&["a", "b"]);
+        assert_eq!(
+            sorted(db.set_op(SetOp::Inter, &keys, None).unwrap()),
+            members(&["2", "3"])
+        );
+        assert_eq!(db.set_op(SetOp::Inter, &keys, Some(1)).unwrap().len(), 1);
+        assert_eq!(
+            sorted(db.set_op(SetOp::Union, &keys, None).unwrap()),
+            members(&["1", "2", "3", "x", "y"])
+        );
+        assert_eq!(
+            sorted(db.set_op(SetOp::Diff, &keys, None).unwrap()),
+            members(&["1", "x"])
+        );
+        let with_missing = members(&["a", "missing"]);
+        assert!(db
+            .set_op(SetOp::Inter, &with_missing, None)
+            .unwrap()
+            .is_empty());
+
+        assert_eq!(db.set_op_store(SetOp::Diff, "a".into(), &keys).unwrap(), 2);
+        assert_eq!(sorted(db.smembers(b"a").unwrap()), members(&["1", "x"]));
+        assert!(db.smove(b"a", "b".into(), "x".into()).unwrap());
+        assert_eq!(db.spop(b"a", 5).unwrap(), members(&["1"]));
+        assert_eq!(db.type_of(b"a"), "none");
+    }
+
+    #[test]
+    fn random_members_of_both_encodings() {
+        let mut db = Db::new();
+        db.sadd("i".into(), members(&["1", "2", "3"])).unwrap();
+        db.sadd("s".into(), members(&["a", "b", "c"])).unwrap();
+        for (key, all) in [("i", ["1", "2", "3"]), ("s", ["a", "b", "c"])] {
+            let key = key.as_bytes();
+            assert_eq!(sorted(db.srandmember(key, 5).unwrap()), members(&all));
+            assert_eq!(db.srandmember(key, 2).unwrap().len(), 2);
+            let repeated = db.srandmember(key, -7).unwrap();
+            assert_eq!(repeated.len(), 7);
+            assert!(repeated.iter().all(|member| members(&all).contains(member)));
+
+            let popped = db.spop(key, 2).unwrap();
+            assert_eq!(popped.len(), 2);
+            assert_eq!(db.scard(key).unwrap(), 1);
+            let left = db.smembers(key).unwrap();
+            assert_eq!(sorted([popped, left].concat()), members(&all));
+        }
+    }
+}
@@ -0,0 +1,321 @@
+use bytes::Bytes;
+
+use crate::utils::random_below;
+
+/// Redis' `ZSKIPLIST_MAXLEVEL`, enough for 2^64 elements.
+const MAX_LEVEL: usize = 32;
+/// The header node sits at the start of `SkipList::nodes`, before any element.
+const HEADER: usize = 0;
+
+/// The skiplist behind sorted sets, a port of Redis' `zskiplist`. Elements are ordered
+/// by score then member, and each link knows how many elements it spans so ranks can
+/// be found in O(log n). Nodes live in a vector and refer to each other by index.
+#[derive(Debug, Clone)]
+pub struct SkipList {
+    nodes: Vec<Node>,
+    /// Indexes of the nodes of removed elements, reused by the next inserts.
+    free: Vec<usize>,
+    /// Number of levels in use, at least one.
+    level: usize,
+    len: usize,
+    tail: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    member: Bytes,
+    score: f64,
+    backward: Option<usize>,
+    levels: Vec<Level>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Level {
+    forward: Option<usize>,
+    /// Number of elements between this node and `forward`, `forward` included.
+    span: usize,
+}
+
+impl Node {
+    /// Whether the node comes before the element with `score` and `member`.
+    fn precedes(&self, score: f64, member: &[u8]) -> bool {
+        self.score < score || (self.score == score && &self.member[..] < member)
+    }
+}
+
+impl Default for SkipList {
+    fn default() -> SkipList {
+        let header = Node {
+            member: Bytes::new(),
+            score: 0.0,
+            backward: None,
+            levels: vec![Level::default(); MAX_LEVEL],
+        };
+        SkipList {
+            nodes: vec![header],
+            free: Vec::new(),
+            level: 1,
+            len: 0,
+            tail: None,
+        }
+    }
+}
+
+impl SkipList {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Inserts an element, which mustn't be in the list already.
+    pub fn insert(&mut self, score: f64, member: Bytes) {
+        let mut update = [HEADER; MAX_LEVEL];
+        let mut rank = [0; MAX_LEVEL];
+        let mut x = HEADER;
+        for i in (0..self.level).rev() {
+            rank[i] = if i == self.level - 1 { 0 } else { rank[i + 1] };
+            while let Some(next) = self.nodes[x].levels[i].forward {
+                if !self.nodes[next].precedes(score, &member) {
+                    break;
+                }
+                rank[i] += self.nodes[x].levels[i].span;
+                x = next;
+            }
+            update[i] = x;
+        }
+
+        let level = random_level();
+        if level > self.level {
+            for i in self.level..level {
+                rank[i] = 0;
+                update[i] = HEADER;
+                self.nodes[HEADER].levels[i].span = self.len;
+            }
+            self.level = level;
+        }
+
+        let node = Node {
+            member,
+            score,
+            backward: (update[0] != HEADER).then_some(update[0]),
+            levels: vec![Level::default(); level],
+        };
+        let id = match self.free.pop() {
+            Some(id) => {
+                self.nodes[id] = node;
+                id
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        };
+        for i in 0..level {
+            let previous = update[i];
+            let previous_level = self.nodes[previous].levels[i];
+            self.nodes[id].levels[i] = Level {
+                forward: previous_level.forward,
+                span: previous_level.span - (rank[0] - rank[i]),
+            };
+            self.nodes[previous].levels[i] = Level {
+                forward: Some(id),
+                span: rank[0] - rank[i] + 1,
+            };
+        }
+        // The links above the new node now span one more element.
+        for (i, &previous) in update.iter().enumerate().take(self.level).skip(level) {
+            self.nodes[previous].levels[i].span += 1;
+        }
+        match self.nodes[id].levels[0].forward {
+            Some(next) => self.nodes[next].backward = Some(id),
+            None => self.tail = Some(id),
+        }
+        self.len += 1;
+    }
+
+    /// Removes an element, returns whether it was in the list.
+    pub fn remove(&mut self, score: f64, member: &[u8]) -> bool {
+        let mut update = [HEADER; MAX_LEVEL];
+        let mut x = HEADER;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.nodes[x].levels[i].forward {
+                if !self.nodes[next].precedes(score, member) {
+                    break;
+                }
+                x = next;
+            }
+            update[i] = x;
+        }
+        let id = match self.nodes[x].levels[0].forward {
+            Some(id) if self.nodes[id].score == score && self.nodes[id].member == member => id,
+            _ => return false,
+        };
+
+        for (i, &previous) in update.iter().enumerate().take(self.level) {
+            if self.nodes[previous].levels[i].forward == Some(id) {
+                let removed_level = self.nodes[id].levels[i];
+                let previous_level = &mut self.nodes[previous].levels[i];
+                previous_level.span += removed_level.span;
+                previous_level.span -= 1;
+                previous_level.forward = removed_level.forward;
+            } else {
+                self.nodes[previous].levels[i].span -= 1;
+            }
+        }
+        let backward = self.nodes[id].backward;
+        match self.nodes[id].levels[0].forward {
+            Some(next) => self.nodes[next].backward = backward,
+            None => self.tail = backward,
+        }
+        while self.level > 1 && self.nodes[HEADER].levels[self.level - 1].forward.is_none() {
+            self.level -= 1;
+        }
+        self.len -= 1;
+
+        self.nodes[id].member = Bytes::new();
+        self.nodes[id].levels = Vec::new();
+        self.free.push(id);
+        true
+    }
+
+    /// The 0-based rank of an element, `None` if it isn't in the list.
+    pub fn rank(&self, score: f64, member: &[u8]) -> Option<usize> {
+        let mut rank = 0;
+        let mut x = HEADER;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.nodes[x].levels[i].forward {
+                let node = &self.nodes[next];
+                if !(node.precedes(score, member) || (node.score == score && node.member == member))
+                {
+                    break;
+                }
+                rank += self.nodes[x].levels[i].span;
+                x = next;
+            }
+            if x != HEADER && self.nodes[x].score == score && self.nodes[x].member == member {
+                return Some(rank - 1);
+            }
+        }
+        None
+    }
+
+    /// The node of the element at the 0-based `rank`.
+    pub fn by_rank(&self, rank: usize) -> Option<usize> {
+        let target = rank + 1;
+        let mut traversed = 0;
+        let mut x = HEADER;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.nodes[x].levels[i].forward {
+                if traversed + self.nodes[x].levels[i].span > target {
+                    break;
+                }
+                traversed += self.nodes[x].levels[i].span;
+                x = next;
+            }
+            if traversed == target {
+                return Some(x);
+            }
+        }
+        None
+    }
+
+    /// The first node that isn't `before` the range looked for. `before` must hold for
+    /// a prefix of the list and not for the rest.
+    pub fn first_not(&self, before: impl Fn(f64, &[u8]) -> bool) -> Option<usize> {
+        let mut x = HEADER;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.nodes[x].levels[i].forward {
+                if !before(self.nodes[next].score, &self.nodes[next].member) {
+                    break;
+                }
+                x = next;
+            }
+        }
+        self.nodes[x].levels[0].forward
+    }
+
+    /// The last node that's `within` the range looked for. `within` must hold for a
+    /// prefix of the list and not for the rest.
+    pub fn last_within(&self, within: impl Fn(f64, &[u8]) -> bool) -> Option<usize> {
+        let mut x = HEADER;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.nodes[x].levels[i].forward {
+                if !within(self.nodes[next].score, &self.nodes[next].member) {
+                    break;
+                }
+                x = next;
+            }
+        }
+        (x != HEADER).then_some(x)
+    }
+
+    pub fn first(&self) -> Option<usize> {
+        self.nodes[HEADER].levels[0].forward
+    }
+
+    pub fn last(&self) -> Option<usize> {
+        self.tail
+    }
+
+    pub fn next(&self, node: usize) -> Option<usize> {
+        self.nodes[node].levels[0].forward
+    }
+
+    pub fn previous(&self, node: usize) -> Option<usize> {
+        self.nodes[node].backward
+    }
+
+    pub fn score(&self, node: usize) -> f64 {
+        self.nodes[node].score
+    }
+
+    pub fn member(&self, node: usize) -> &Bytes {
+        &self.nodes[node].member
+    }
+}
+
+/// A level between 1 and `MAX_LEVEL`, each level being a quarter as likely as the one
+/// below like in Redis.
+fn random_level() -> usize {
+    let mut level = 1;
+    while level < MAX_LEVEL && random_below(4) == 0 {
+        level += 1;
+    }
+    level
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SkipList;
+
+    #[test]
+    fn ranks_follow_inserts_and_removals() {
+        let mut list = SkipList::default();
+        for i in (0..200).rev() {
+            list.insert((i / 2) as f64, i.to_string().into());
+        }
+        assert_eq!(list.len(), 200);
+        assert_eq!(list.rank(0.0, b"0"), Some(0));
+        assert_eq!(list.rank(0.0, b"1"), Some(1));
+        assert_eq!(list.rank(99.0, b"199"), Some(199));
+        assert_eq!(list.rank(99.0, b"0"), None);
+
+        for i in (0..200).step_by(2) {
+            assert!(list.remove((i / 2) as f64, i.to_string().as_bytes()));
+        }
+        assert!(!list.remove(0.0, b"0"));
+        assert_eq!(list.len(), 100);
+        for rank in 0..100 {
+            let node = list.by_rank(rank).unwrap();
+            assert_eq!(list.member(node), &(rank * 2 + 1).to_string());
+            assert_eq!(list.rank(list.score(node), list.member(node)), Some(rank));
+        }
+        assert_eq!(list.by_rank(100), None);
+
+        let first = list.first_not(|score, _| score < 10.5).unwrap();
+        assert_eq!(list.member(first), "23");
+        let last = list.last_within(|score, _| score <= 10.0).unwrap();
+        assert_eq!(list.member(last), "21");
+        assert_eq!(list.previous(first), Some(last));
+        assert_eq!(list.member(list.by_rank(99).unwrap()), "199");
+    }
+}
@@ -0,0 +1,791 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ops::Bound,
+};
+
+use bytes::Bytes;
+
+use crate::{
+    db::{Db, Entry, Value},
+    error::{Error, Result},
+};
+
+use super::{Stream, StreamFields, StreamId, StreamRead};
+
+/// A consumer group: how far it read the stream and the entries its consumers were
+/// delivered but haven't acknowledged yet.
+#[derive(Debug, Clone, Default)]
+pub(super) struct ConsumerGroup {
+    last_delivered: StreamId,
+    /// The number of entries the group read, `None` when it can't be told.
+    entries_read: Option<u64>,
+    /// The pending entries list, or PEL, of the group.
+    pending: BTreeMap<StreamId, PendingEntry>,
+    consumers: BTreeMap<Bytes, Consumer>,
+}
+
+#[derive(Debug, Clone)]
+struct PendingEntry {
+    consumer: Bytes,
+    /// The unix time in milliseconds the entry was last delivered at.
+    delivered_at: u64,
+    deliveries: u64,
+}
+
+#[derive(Debug, Clone)]
+struct Consumer {
+    /// The unix time in milliseconds the consumer last attempted to read or claim.
+    seen_at: u64,
+    /// The unix time in milliseconds the consumer last read or claimed something.
+    active_at: Option<u64>,
+    /// The IDs of the entries pending for the consumer.
+    pending: BTreeSet<StreamId>,
+}
+
+/// The options of `XCLAIM` besides the IDs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct XClaimOptions {
+    /// `IDLE`, set the idle time of the claimed entries rather than reset it.
+    pub idle: Option<u64>,
+    /// `TIME`, like `idle` but as the unix time in milliseconds of the delivery.
+    pub time: Option<u64>,
+    /// `RETRYCOUNT`, set the delivery count rather than increment it.
+    pub retry_count: Option<u64>,
+    /// `FORCE`, claim entries even if they aren't pending for any consumer.
+    pub force: bool,
+    /// `JUSTID`, leave the delivery count alone as only the IDs are replied.
+    pub just_id: bool,
+    /// `LASTID`, move the last delivered ID of the group forward.
+    pub last_id: Option<StreamId>,
+}
+
+/// What `XAUTOCLAIM` did.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AutoClaim {
+    /// Where to resume scanning the PEL, `0-0` once it's been scanned entirely.
+    pub next: StreamId,
+    pub claimed: Vec<(StreamId, StreamFields)>,
+    /// The pending entries found deleted from the stream, which were dropped.
+    pub deleted: Vec<StreamId>,
+}
+
+/// The summary form of `XPENDING`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingSummary {
+    pub count: usize,
+    /// The smallest and greatest pending IDs.
+    pub bounds: Option<(StreamId, StreamId)>,
+    /// The number of pending entries of each consumer that has some.
+    pub consumers: Vec<(Bytes, usize)>,
+}
+
+/// A pending entry as listed by the extended form of `XPENDING`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingInfo {
+    pub id: StreamId,
+    pub consumer: Bytes,
+    /// Milliseconds since the entry was last delivered.
+    pub idle: u64,
+    pub deliveries: u64,
+}
+
+/// The reply of `XINFO STREAM`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamInfo {
+    pub length: usize,
+    /// The number of nodes the entries are spread over.
+    pub radix_tree_keys: usize,
+    pub last_generated_id: StreamId,
+    pub max_deleted_id: StreamId,
+    pub entries_added: u64,
+    pub recorded_first_id: StreamId,
+    pub groups: usize,
+    pub first_entry: Option<(StreamId, StreamFields)>,
+    pub last_entry: Option<(StreamId, StreamFields)>,
+}
+
+/// An item of the reply of `XINFO GROUPS`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupInfo {
+    pub name: Bytes,
+    pub consumers: usize,
+    pub pending: usize,
+    pub last_delivered: StreamId,
+    pub entries_read: Option<u64>,
+    /// The number of entries left for the group to read, `None` when it can't be told.
+    pub lag: Option<u64>,
+}
+
+/// An item of the reply of `XINFO CONSUMERS`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsumerInfo {
+    pub name: Bytes,
+    pub pending: usize,
+    /// Milliseconds since the consumer last attempted to read or claim.
+    pub idle: u64,
+    /// Milliseconds since the consumer last read or claimed something.
+    pub inactive: Option<u64>,
+}
+
+impl Consumer {
+    fn new(now: u64) -> Consumer {
+        Consumer {
+            seen_at: now,
+            active_at: None,
+            pending: BTreeSet::new(),
+        }
+    }
+}
+
+impl ConsumerGroup {
+    /// The consumer named `name`, created if needed, marked as seen at `now`.
+    fn consumer(&mut self, name: &Bytes, now: u64) -> &mut Consumer {
+        let consumer = self
+            .consumers
+            .entry(name.clone())
+            .or_insert_with(|| Consumer::new(now));
+        consumer.seen_at = now;
+        consumer
+    }
+
+    /// Makes the entry `id` pending for `consumer`, taking it from the consumer it
+    /// was pending for if any.
+    fn assign(&mut self, id: StreamId, consumer: &Bytes, delivered_at: u64, deliveries: u64) {
+        if let Some(previous) = self.pending.get(&id) {
+            if let Some(previous) = self.consumers.get_mut(&previous.consumer) {
+                previous.pending.remove(&id);
+            }
+        }
+        self.pending.insert(
+            id,
+            PendingEntry {
+                consumer: consumer.clone(),
+                delivered_at,
+                deliveries,
+            },
+        );
+        if let Some(consumer) = self.consumers.get_mut(consumer) {
+            consumer.pending.insert(id);
+        }
+    }
+
+    /// Acknowledges the entry `id`, returns whether it was pending.
+    fn acknowledge(&mut self, id: StreamId) -> bool {
+        match self.pending.remove(&id) {
+            Some(entry) => {
+                if let Some(consumer) = self.consumers.get_mut(&entry.consumer) {
+                    consumer.pending.remove(&id);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl Stream {
+    /// The number of entries read by a group that read `entries_read` of them before
+    /// being delivered `id`. Mirrors how Redis keeps the counter it computes lags with.
+    fn entries_read_after(&self, entries_read: Option<u64>, id: StreamId) -> Option<u64> {
+        match entries_read {
+            Some(read) if !self.has_tombstones_from(id) => Some(read + 1),
+            _ if self.entries_added > 0 => self.entries_added_until(id),
+            _ => entries_read,
+        }
+    }
+
+    /// The number of entries the group has yet to read, `None` if it can't be told.
+    fn lag(&self, group: &ConsumerGroup) -> Option<u64> {
+        if self.entries_added == 0 {
+            return Some(0);
+        }
+        let entries_read = match group.entries_read {
+            Some(read) if !self.has_tombstones_from(group.last_delivered) => Some(read),
+            _ => self.entries_added_until(group.last_delivered),
+        };
+        entries_read.map(|read| self.entries_added.saturating_sub(read))
+    }
+
+    /// The `entries_read` a group starting after `id` gets: `ENTRIESREAD` if given, -1
+    /// standing for unknown, an estimate otherwise when `estimate` is set.
+    fn initial_entries_read(
+        &self,
+        id: StreamId,
+        given: Option<i64>,
+        estimate: bool,
+    ) -> Option<u64> {
+        match given {
+            Some(read) => u64::try_from(read)
+                .ok()
+                .map(|read| read.min(self.entries_added)),
+            None if estimate => self.entries_added_until(id),
+            None => None,
+        }
+    }
+}
+
+fn no_group(key: &[u8], group: &[u8]) -> Error {
+    Error::NoGroup(format!(
+        "No such key '{}' or consumer group '{}'",
+        String::from_utf8_lossy(key),
+        String::from_utf8_lossy(group)
+    ))
+}
+
+fn no_group_for_key(key: &[u8], group: &[u8]) -> Error {
+    Error::NoGroup(format!(
+        "No such consumer group '{}' for key name '{}'",
+        String::from_utf8_lossy(group),
+        String::from_utf8_lossy(key)
+    ))
+}
+
+impl Db {
+    /// Creates the consumer group `group` starting after `id`, `None` meaning the last
+    /// ID of the stream. `entries_read` is the `ENTRIESREAD` option, -1 standing for
+    /// unknown. With `mkstream` a missing stream is created empty.
+    pub fn xgroup_create(
+        &mut self,
+        key: Bytes,
+        group: Bytes,
+        id: Option<StreamId>,
+        mkstream: bool,
+        entries_read: Option<i64>,
+    ) -> Result<()> {
+        if self.stream(&key)?.is_none() {
+            if !mkstream {
+                return Err(Error::Msg(
+                    "The XGROUP subcommand requires the key to exist. Note that for CREATE you may want to use the MKSTREAM option to create an empty stream automatically."
+                        .to_string(),
+                ));
+            }
+            let entry = Entry {
+                value: Value::Stream(Stream::default()),
+                expires_at: None,
+            };
+            self.insert_entry(key.clone(), entry);
+        }
+        let stream = self.stream_mut(&key)?.unwrap();
+        if stream.groups.contains_key(&group) {
+            return Err(Error::BusyGroup);
+        }
+        let id = id.unwrap_or(stream.last_id);
+        let group_state = ConsumerGroup {
+            last_delivered: id,
+            entries_read: stream.initial_entries_read(id, entries_read, true),
+            ..Default::default()
+        };
+        stream.groups.insert(group, group_state);
+        Ok(())
+    }
+
+    /// Moves the last delivered ID of `group` to `id`, `None` meaning the last ID of
+    /// the stream.
+    pub fn xgroup_setid(
+        &mut self,
+        key: &[u8],
+        group: &[u8],
+        id: Option<StreamId>,
+        entries_read: Option<i64>,
+    ) -> Result<()> {
+        let stream = self.existing_stream_mut(key)?;
+        let id = id.unwrap_or(stream.last_id);
+        let entries_read = stream.initial_entries_read(id, entries_read, false);
+        let group_state = stream
+            .groups
+            .get_mut(group)
+            .ok_or_else(|| no_group_for_key(key, group))?;
+        group_state.last_delivered = id;
+        group_state.entries_read = entries_read;
+        Ok(())
+    }
+
+    /// Deletes `group`, returns whether it existed.
+    pub fn xgroup_destroy(&mut self, key: &[u8], group: &[u8]) -> Result<bool> {
+        let stream = self.existing_stream_mut(key)?;
+        Ok(stream.groups.remove(group).is_some())
+    }
+
+    /// Creates `consumer` in `group`, returns whether it didn't exist already.
+    pub fn xgroup_create_consumer(
+        &mut self,
+        key: &[u8],
+        group: &[u8],
+        consumer: Bytes,
+    ) -> Result<bool> {
+        let now = self.now();
+        let group_state = self.existing_group_mut(key, group)?;
+        if group_state.consumers.contains_key(&consumer) {
+            return Ok(false);
+        }
+        group_state.consumers.insert(consumer, Consumer::new(now));
+        Ok(true)
+    }
+
+    /// Deletes `consumer` from `group` along with its pending entries, returns how
+    /// many pending entries it had.
+    pub fn xgroup_del_consumer(
+        &mut self,
+        key: &[u8],
+        group: &[u8],
+        consumer: &[u8],
+    ) -> Result<usize> {
+        let group_state = self.existing_group_mut(key, group)?;
+        let removed = match group_state.consumers.remove(consumer) {
+            Some(removed) => removed,
+            None => return Ok(0),
+        };
+        for id in &removed.pending {
+            group_state.pending.remove(id);
+        }
+        Ok(removed.pending.len())
+    }
+
+    /// Reads as `consumer` of `group` from each stream, either the entries never
+    /// delivered to the group when the ID is `None` (`>`), or the entries pending for
+    /// the consumer after the ID otherwise. New entries become pending unless `noack`
+    /// is set. Streams without new entries are left out of the reply.
+    pub fn xreadgroup(
+        &mut self,
+        group: &Bytes,
+        consumer: &Bytes,
+        streams: &[(Bytes, Option<StreamId>)],
+        count: Option<usize>,
+        noack: bool,
+    ) -> Result<Vec<StreamRead>> {
+        // Nothing is read unless all the groups exist.
+        for (key, _) in streams {
+            let exists = self
+                .stream(key)?
+                .map_or(false, |stream| stream.groups.contains_key(group));
+            if !exists {
+                return Err(Error::NoGroup(format!(
+                    "No such key '{}' or consumer group '{}' in XREADGROUP with GROUP option",
+                    String::from_utf8_lossy(key),
+                    String::from_utf8_lossy(group)
+                )));
+            }
+        }
+
+        let now = self.now();
+        let count = count.unwrap_or(usize::MAX);
+        let mut reads = Vec::new();
+        for (key, id) in streams {
+            let stream = self.stream_mut(key)?.unwrap();
+            let entries = match id {
+                Some(id) => {
+                    let group_state = stream.groups.get_mut(group).unwrap();
+                    let pending = &group_state.consumer(consumer, now).pending;
+                    let ids: Vec<_> = pending
+                        .range((Bound::Excluded(*id), Bound::Unbounded))
+                        .take(count)
+                        .copied()
+                        .collect();
+                    // Reading the history delivers the entries again.
+                    for id in &ids {
+                        if let Some(entry) = group_state.pending.get_mut(id) {
+                            entry.delivered_at = now;
+                            entry.deliveries += 1;
+                        }
+                    }
+                    ids.into_iter()
+                        .map(|id| (id, stream.entries.get(&id).cloned()))
+                        .collect()
+                }
+                None => {
+                    let last_delivered = stream.groups[group].last_delivered;
+                    let entries = match last_delivered.next() {
+                        Some(start) => stream.range(start, StreamId::MAX, false, Some(count)),
+                        None => Vec::new(),
+                    };
+                    let mut entries_read = stream.groups[group].entries_read;
+                    for (id, _) in &entries {
+                        entries_read = stream.entries_read_after(entries_read, *id);
+                    }
+                    let group_state = stream.groups.get_mut(group).unwrap();
+                    group_state.consumer(consumer, now);
+                    if let Some((last, _)) = entries.last() {
+                        group_state.last_delivered = *last;
+                        group_state.entries_read = entries_read;
+                        group_state.consumer(consumer, now).active_at = Some(now);
+                    }
+                    if !noack {
+                        for (id, _) in &entries {
+                            group_state.assign(*id, consumer, now, 1);
+                        }
+                    }
+                    if entries.is_empty() {
+                        continue;
+                    }
+                    entries
+                        .into_iter()
+                        .map(|(id, fields)| (id, Some(fields)))
+                        .collect()
+                }
+            };
+            reads.push((key.clone(), entries));
+        }
+        Ok(reads)
+    }
+
+    /// Acknowledges the entries `ids` for `group`, returns how many were pending.
+    pub fn xack(&mut self, key: &[u8], group: &[u8], ids: &[StreamId]) -> Result<usize> {
+        let group_state = match self.stream_mut(key)? {
+            Some(stream) => match stream.groups.get_mut(group) {
+                Some(group_state) => group_state,
+                None => return Ok(0),
+            },
+            None => return Ok(0),
+        };
+        Ok(ids
+            .iter()
+            .filter(|id| group_state.acknowledge(**id))
+            .count())
+    }
+
+    /// The summary form of `XPENDING`.
+    pub fn xpending_summary(&mut self, key: &[u8], group: &[u8]) -> Result<PendingSummary> {
+        let group_state = self.pending_group(key, group)?;
+        let bounds = group_state
+            .pending
+            .keys()
+            .next()
+            .zip(group_state.pending.keys().next_back())
+            .map(|(first, last)| (*first, *last));
+        let consumers = group_state
+            .consumers
+            .iter()
+            .filter(|(_, consumer)| !consumer.pending.is_empty())
+            .map(|(name, consumer)| (name.clone(), consumer.pending.len()))
+            .collect();
+        Ok(PendingSummary {
+            count: group_state.pending.len(),
+            bounds,
+            consumers,
+        })
+    }
+
+    /// The extended form of `XPENDING`: at most `count` entries pending from `start`
+    /// to `end` included, idle for at least `min_idle` milliseconds and pending for
+    /// `consumer` if given.
+    #[allow(clippy::too_many_arguments)]
+    pub fn xpending(
+        &mut self,
+        key: &[u8],
+        group: &[u8],
+        min_idle: u64,
+        start: StreamId,
+        end: StreamId,
+        count: usize,
+        consumer: Option<&[u8]>,
+    ) -> Result<Vec<PendingInfo>> {
+        let now = self.now();
+        let group_state = self.pending_group(key, group)?;
+        if start > end {
+            return Ok(Vec::new());
+        }
+        Ok(group_state
+            .pending
+            .range(start..=end)
+            .filter(|(_, entry)| consumer.map_or(true, |consumer| entry.consumer == consumer))
+            .map(|(id, entry)| PendingInfo {
+                id: *id,
+                consumer: entry.consumer.clone(),
+                idle: now.saturating_sub(entry.delivered_at),
+                deliveries: entry.deliveries,
+            })
+            .filter(|info| info.idle >= min_idle)
+            .take(count)
+            .collect())
+    }
+
+    /// Makes the entries `ids` idle for at least `min_idle` milliseconds pending for
+    /// `consumer`. Pending entries since deleted from the stream are dropped. Returns
+    /// the entries claimed.
+    pub fn xclaim(
+        &mut self,
+        key: &[u8],
+        group: &[u8],
+        consumer: &Bytes,
+        min_idle: u64,
+        ids: &[StreamId],
+        options: XClaimOptions,
+    ) -> Result<Vec<(StreamId, StreamFields)>> {
+        let now = self.now();
+        let stream = match self.stream_mut(key)? {
+            Some(stream) if stream.groups.contains_key(group) => stream,
+            _ => return Err(no_group(key, group)),
+        };
+        let group_state = stream.groups.get_mut(group).unwrap();
+        if let Some(last_id) = options.last_id {
+            group_state.last_delivered = group_state.last_delivered.max(last_id);
+        }
+        group_state.consumer(consumer, now);
+
+        let delivered_at = match (options.idle, options.time) {
+            (Some(idle), _) => now.saturating_sub(idle),
+            (None, Some(time)) => time.min(now),
+            (None, None) => now,
+        };
+        let mut claimed = Vec::new();
+        for id in ids {
+            let fields = stream.entries.get(id);
+            let group_state = stream.groups.get_mut(group).unwrap();
+            let deliveries = match group_state.pending.get(id) {
+                Some(entry) => {
+                    if now.saturating_sub(entry.delivered_at) < min_idle {
+                        continue;
+                    }
+                    entry.deliveries
+                }
+                None if options.force && fields.is_some() => 0,
+                None => continue,
+            };
+            let fields = match fields {
+                Some(fields) => fields.clone(),
+                None => {
+                    group_state.acknowledge(*id);
+                    continue;
+                }
+            };
+            let deliveries = match options.retry_count {
+                Some(retry_count) => retry_count,
+                None if options.just_id => deliveries,
+                None => deliveries + 1,
+            };
+            group_state.assign(*id, consumer, delivered_at, deliveries);
+            group_state.consumer(consumer, now).active_at = Some(now);
+            claimed.push((*id, fields));
+        }
+        Ok(claimed)
+    }
+
+    /// Scans the PEL of `group` from `start` and claims for `consumer` up to `count`
+    /// entries idle for at least `min_idle` milliseconds, like `XCLAIM` would.
+    /// `just_id` leaves the delivery counts alone.
+    #[allow(clippy::too_many_arguments)]
+    pub fn xautoclaim(
+        &mut self,
+        key: &[u8],
+        group: &[u8],
+        consumer: &Bytes,
+        min_idle: u64,
+        start: StreamId,
+        count: usize,
+        just_id: bool,
+    ) -> Result<AutoClaim> {
+        let now = self.now();
+        let stream = match self.stream_mut(key)? {
+            Some(stream) if stream.groups.contains_key(group) => stream,
+            _ => return Err(no_group(key, group)),
+        };
+        let group_state = stream.groups.get_mut(group).unwrap();
+        group_state.consumer(consumer, now);
+
+        // Like Redis, look at no more than ten pending entries per entry to claim.
+        let attempts = count.saturating_mul(10);
+        let scanned: Vec<StreamId> = group_state
+            .pending
+            .range(start..)
+            .map(|(id, _)| *id)
+            .take(attempts.saturating_add(1))
+            .collect();
+        let mut next = StreamId::MIN;
+        let mut claimed = Vec::new();
+        let mut deleted = Vec::new();
+        for (attempt, id) in scanned.into_iter().enumerate() {
+            if attempt == attempts || claimed.len() == count {
+                next = id;
+                break;
+            }
+            let group_state = stream.groups.get_mut(group).unwrap();
+            let entry = &group_state.pending[&id];
+            if now.saturating_sub(entry.delivered_at) < min_idle {
+                continue;
+            }
+            let deliveries = entry.deliveries + !just_id as u64;
+            match stream.entries.get(&id) {
+                Some(fields) => {
+                    group_state.assign(id, consumer, now, deliveries);
+                    group_state.consumer(consumer, now).active_at = Some(now);
+                    claimed.push((id, fields.clone()));
+                }
+                None => {
+                    group_state.acknowledge(id);
+                    deleted.push(id);
+                }
+            }
+        }
+        Ok(AutoClaim {
+            next,
+            claimed,
+            deleted,
+        })
+    }
+
+    /// The reply of `XINFO STREAM`.
+    pub fn xinfo_stream(&mut self, key: &[u8]) -> Result<StreamInfo> {
+        let stream = self.stream(key)?.ok_or(Error::NoSuchKey)?;
+        let entry = |entry: Option<(&StreamId, &StreamFields)>| {
+            entry.map(|(id, fields)| (*id, fields.clone()))
+        };
+        Ok(StreamInfo {
+            length: stream.len(),
+            radix_tree_keys: stream.nodes.len(),
+            last_generated_id: stream.last_id,
+            max_deleted_id: stream.max_deleted_id,
+            entries_added: stream.entries_added,
+            recorded_first_id: stream.first_id(),
+            groups: stream.groups.len(),
+            first_entry: entry(stream.entries.iter().next()),
+            last_entry: entry(stream.entries.iter().next_back()),
+        })
+    }
+
+    /// The reply of `XINFO GROUPS`.
+    pub fn xinfo_groups(&mut self, key: &[u8]) -> Result<Vec<GroupInfo>> {
+        let stream = self.stream(key)?.ok_or(Error::NoSuchKey)?;
+        Ok(stream
+            .groups
+            .iter()
+            .map(|(name, group)| GroupInfo {
+                name: name.clone(),
+                consumers: group.consumers.len(),
+                pending: group.pending.len(),
+                last_delivered: group.last_delivered,
+                entries_read: group.entries_read,
+                lag: stream.lag(group),
+            })
+            .collect())
+    }
+
+    /// The reply of `XINFO CONSUMERS`.
+    pub fn xinfo_consumers(&mut self, key: &[u8], group: &[u8]) -> Result<Vec<ConsumerInfo>> {
+        let now = self.now();
+        let stream = self.stream(key)?.ok_or(Error::NoSuchKey)?;
+        let group_state = stream
+            .groups
+            .get(group)
+            .ok_or_else(|| no_group_for_key(key, group))?;
+        Ok(group_state
+            .consumers
+            .iter()
+            .map(|(name, consumer)| ConsumerInfo {
+                name: name.clone(),
+                pending: consumer.pending.len(),
+                idle: now.saturating_sub(consumer.seen_at),
+                inactive: consumer
+                    .active_at
+                    .map(|active_at| now.saturating_sub(active_at)),
+            })
+            .collect())
+    }
+
+    /// The group `XPENDING` looks at.
+    fn pending_group(&mut self, key: &[u8], group: &[u8]) -> Result<&ConsumerGroup> {
+        self.stream(key)?
+            .and_then(|stream| stream.groups.get(group))
+            .ok_or_else(|| no_group(key, group))
+    }
+
+    /// The stream `XGROUP` works on, which must exist.
+    fn existing_stream_mut(&mut self, key: &[u8]) -> Result<&mut Stream> {
+        self.stream_mut(key)?.ok_or_else(|| {
+            Error::Msg(
+                "The XGROUP subcommand requires the key to exist. Note that for CREATE you may want to use the MKSTREAM option to create an empty stream automatically."
+                    .to_string(),
+            )
+        })
+    }
+
+    /// The group `XGROUP` works on, which must exist along with its stream.
+    fn existing_group_mut(&mut self, key: &[u8], group: &[u8]) -> Result<&mut ConsumerGroup> {
+        self.existing_stream_mut(key)?
+            .groups
+            .get_mut(group)
+            .ok_or_else(|| no_group_for_key(key, group))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use super::XClaimOptions;
+    use crate::{
+        db::{Db, StreamId, XAddId},
+        utils::FakeClock,
+    };
+
+    fn id(ms: u64) -> StreamId {
+        StreamId { ms, seq: 0 }
+    }
+
+    fn read_ids(reads: Vec<crate::db::StreamRead>) -> Vec<StreamId> {
+        reads
+            .into_iter()
+            .flat_map(|(_, entries)| entries.into_iter().map(|(id, _)| id))
+            .collect()
+    }
+
+    #[test]
+    fn delivered_entries_stay_pending_until_acknowledged() {
+        let clock = FakeClock::new(1000);
+        let mut db = Db::with_clock(Box::new(clock.clone()));
+        for ms in 1..=3 {
+            let fields = vec![("f".into(), "v".into())];
+            db.xadd("s".into(), XAddId::Explicit(id(ms)), fields, false, None)
+                .unwrap();
+        }
+        db.xgroup_create("s".into(), "g".into(), Some(StreamId::MIN), false, None)
+            .unwrap();
+        let (group, alice, bob): (Bytes, Bytes, Bytes) = ("g".into(), "alice".into(), "bob".into());
+
+        let new = [(Bytes::from("s"), None)];
+        let read = db.xreadgroup(&group, &alice, &new, Some(2), false).unwrap();
+        assert_eq!(read_ids(read), vec![id(1), id(2)]);
+        let read = db.xreadgroup(&group, &bob, &new, None, false).unwrap();
+        assert_eq!(read_ids(read), vec![id(3)]);
+        assert!(db
+            .xreadgroup(&group, &bob, &new, None, false)
+            .unwrap()
+            .is_empty());
+        let groups = db.xinfo_groups(b"s").unwrap();
+        assert_eq!((groups[0].entries_read, groups[0].lag), (Some(3), Some(0)));
+
+        clock.advance(100);
+        let claimed = db.xclaim(
+            b"s",
+            b"g",
+            &bob,
+            50,
+            &[id(1), id(9)],
+            XClaimOptions::default(),
+        );
+        assert_eq!(claimed.unwrap().len(), 1);
+        let history = [(Bytes::from("s"), Some(StreamId::MIN))];
+        let read = db.xreadgroup(&group, &bob, &history, None, false).unwrap();
+        assert_eq!(read_ids(read), vec![id(1), id(3)]);
+
+        clock.advance(100);
+        db.xdel(b"s", &[id(2)]).unwrap();
+        let claim = db
+            .xautoclaim(b"s", b"g", &bob, 50, StreamId::MIN, 10, false)
+            .unwrap();
+        assert_eq!(claim.claimed.len(), 2);
+        assert_eq!(claim.deleted, vec![id(2)]);
+        assert_eq!(claim.next, StreamId::MIN);
+
+        let pending = db
+            .xpending(b"s", b"g", 0, StreamId::MIN, StreamId::MAX, 10, None)
+            .unwrap();
+        let deliveries: Vec<_> = pending
+            .iter()
+            .map(|info| (info.id, info.deliveries))
+            .collect();
+        assert_eq!(deliveries, vec![(id(1), 4), (id(3), 3)]);
+        assert_eq!(db.xack(b"s", b"g", &[id(1), id(1), id(3)]).unwrap(), 2);
+        let summary = db.xpending_summary(b"s", b"g").unwrap();
+        assert_eq!((summary.count, summary.bounds), (0, None));
+    }
+}
@@ -0,0 +1,567 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt,
+    ops::Bound,
+};
+
+use bytes::Bytes;
+
+use crate::error::{Error, Result};
+
+use super::{Db, Entry, Value};
+
+mod group;
+
+use group::ConsumerGroup;
+pub use group::XClaimOptions;
+
+/// How many entries Redis packs in a node of a stream's radix tree, its default
+/// `stream-node-max-entries`. Approximate trimming only removes whole nodes.
+const STREAM_NODE_MAX_ENTRIES: usize = 100;
+/// How many entries approximate trimming removes at most when no `LIMIT` is given.
+const DEFAULT_TRIM_LIMIT: usize = 100 * STREAM_NODE_MAX_ENTRIES;
+
+/// The ID of a stream entry: the unix time in milliseconds it was added at and a
+/// sequence number telling apart the entries added within the same millisecond.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StreamId {
+    pub ms: u64,
+    pub seq: u64,
+}
+
+/// The ID `XADD` is given.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XAddId {
+    /// `*`, generated from the current time.
+    Auto,
+    /// `ms-*`, with a generated sequence number.
+    AutoSeq(u64),
+    Explicit(StreamId),
+}
+
+/// The `MAXLEN` or `MINID` trimming of `XADD` and `XTRIM`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamTrim {
+    pub strategy: TrimStrategy,
+    /// `~`, only remove whole nodes which is how Redis trims efficiently.
+    pub approximate: bool,
+    /// `LIMIT`, the most entries an approximate trimming may remove, `Some(0)` for no
+    /// limit.
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrimStrategy {
+    /// Keep at most that many entries.
+    MaxLen(usize),
+    /// Remove the entries with a lower ID.
+    MinId(StreamId),
+}
+
+/// The fields and values of a stream entry.
+pub type StreamFields = Vec<(Bytes, Bytes)>;
+
+/// The entries read from a stream along with its key, `None` fields standing for an
+/// entry pending for a consumer but since deleted from the stream.
+pub type StreamRead = (Bytes, Vec<(StreamId, Option<StreamFields>)>);
+
+/// An append-only log of entries ordered by ID.
+#[derive(Debug, Clone, Default)]
+pub struct Stream {
+    entries: BTreeMap<StreamId, StreamFields>,
+    /// The ID of the last entry ever added, new IDs must be greater.
+    last_id: StreamId,
+    /// The nodes Redis would have spread the entries over, oldest first.
+    nodes: VecDeque<Node>,
+    /// The number of entries ever added, deleted ones included.
+    entries_added: u64,
+    /// The greatest ID `XDEL` removed.
+    max_deleted_id: StreamId,
+    groups: BTreeMap<Bytes, ConsumerGroup>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Node {
+    /// The ID of the first entry added to the node, the node holds the entries up to
+    /// the first one of the next node.
+    first: StreamId,
+    /// The number of entries added to the node, deleted ones included.
+    added: usize,
+}
+
+impl StreamId {
+    pub const MIN: StreamId = StreamId { ms: 0, seq: 0 };
+    pub const MAX: StreamId = StreamId {
+        ms: u64::MAX,
+        seq: u64::MAX,
+    };
+
+    /// The smallest ID greater than this one.
+    pub fn next(self) -> Option<StreamId> {
+        match self.seq.checked_add(1) {
+            Some(seq) => Some(StreamId { ms: self.ms, seq }),
+            None => Some(StreamId {
+                ms: self.ms.checked_add(1)?,
+                seq: 0,
+            }),
+        }
+    }
+
+    /// The greatest ID smaller than this one.
+    pub fn previous(self) -> Option<StreamId> {
+        match self.seq.checked_sub(1) {
+            Some(seq) => Some(StreamId { ms: self.ms, seq }),
+            None => Some(StreamId {
+                ms: self.ms.checked_sub(1)?,
+                seq: u64::MAX,
+            }),
+        }
+    }
+}
+
+impl fmt::Display for StreamId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.ms, self.seq)
+    }
+}
+
+impl Stream {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// The ID an entry added at the unix time `now` in milliseconds gets. Generated
+    /// IDs never go back in time even if the clock does.
+    fn next_id(&self, id: XAddId, now: u64) -> Result<StreamId> {
+        let too_small = || {
+            Error::Msg(
+                "The ID specified in XADD is equal or smaller than the target stream top item"
+                    .to_string(),
+            )
+        };
+        let last = self.last_id;
+        let id = match id {
+            XAddId::Auto if now > last.ms => StreamId { ms: now, seq: 0 },
+            XAddId::Auto => last.next().ok_or_else(too_small)?,
+            XAddId::AutoSeq(ms) if ms > last.ms => StreamId { ms, seq: 0 },
+            XAddId::AutoSeq(ms) if ms == last.ms => last.next().ok_or_else(too_small)?,
+            XAddId::AutoSeq(_) => return Err(too_small()),
+            XAddId::Explicit(StreamId::MIN) => {
+                return Err(Error::Msg(
+                    "The ID specified in XADD must be greater than 0-0".to_string(),
+                ))
+            }
+            XAddId::Explicit(id) => id,
+        };
+        if id <= last {
+            return Err(too_small());
+        }
+        Ok(id)
+    }
+
+    /// Appends an entry, `id` must be greater than any ID added before.
+    fn insert(&mut self, id: StreamId, fields: StreamFields) {
+        match self.nodes.back_mut() {
+            Some(node) if node.added < STREAM_NODE_MAX_ENTRIES => node.added += 1,
+            _ => self.nodes.push_back(Node {
+                first: id,
+                added: 1,
+            }),
+        }
+        self.entries.insert(id, fields);
+        self.last_id = id;
+        self.entries_added += 1;
+    }
+
+    /// Removes the entry `id`, returns whether it existed. A node left without entries
+    /// is dropped like Redis does.
+    fn remove(&mut self, id: StreamId) -> bool {
+        if self.entries.remove(&id).is_none() {
+            return false;
+        }
+        self.max_deleted_id = self.max_deleted_id.max(id);
+        let node = self.nodes.partition_point(|node| node.first <= id) - 1;
+        if self.node_ids(node).is_empty() {
+            self.nodes.remove(node);
+        }
+        true
+    }
+
+    /// The ID of the first entry, `0-0` if there's none.
+    fn first_id(&self) -> StreamId {
+        self.entries.keys().next().copied().unwrap_or_default()
+    }
+
+    /// Whether an entry from `start` onwards has been deleted, which makes counting
+    /// the entries read by consumer groups unreliable.
+    fn has_tombstones_from(&self, start: StreamId) -> bool {
+        !self.entries.is_empty()
+            && self.max_deleted_id != StreamId::MIN
+            && self.max_deleted_id >= start
+    }
+
+    /// The number of entries added up to `id` included, when it can be told despite
+    /// deletions and trimming. Mirrors Redis' `streamEstimateDistanceFromFirstEverEntry`.
+    fn entries_added_until(&self, id: StreamId) -> Option<u64> {
+        if self.entries_added == 0 || (self.entries.is_empty() && id <= self.last_id) {
+            return Some(self.entries_added);
+        }
+        if id == self.last_id {
+            return Some(self.entries_added);
+        }
+        if id > self.last_id {
+            return None;
+        }
+        let first_id = self.first_id();
+        if self.max_deleted_id == StreamId::MIN || self.max_deleted_id < first_id {
+            // Nothing was deleted past the first entry, so the trimmed entries are the
+            // only ones missing.
+            let trimmed = self.entries_added - self.len() as u64;
+            if id < first_id {
+                return Some(trimmed);
+            } else if id == first_id {
+                return Some(trimmed + 1);
+            }
+        }
+        None
+    }
+
+    /// The IDs of the entries left in the `index`th node.
+    fn node_ids(&self, index: usize) -> Vec<StreamId> {
+        let end = match self.nodes.get(index + 1) {
+            Some(next) => Bound::Excluded(next.first),
+            None => Bound::Unbounded,
+        };
+        let start = Bound::Included(self.nodes[index].first);
+        self.entries
+            .range((start, end))
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    /// The entries from `start` to `end` included, from the last one if `rev` is set.
+    pub fn range(
+        &self,
+        start: StreamId,
+        end: StreamId,
+        rev: bool,
+        count: Option<usize>,
+    ) -> Vec<(StreamId, StreamFields)> {
+        if start > end {
+            return Vec::new();
+        }
+        let range = self.entries.range(start..=end);
+        let count = count.unwrap_or(usize::MAX);
+        let entries: Box<dyn Iterator<Item = _>> = if rev {
+            Box::new(range.rev())
+        } else {
+            Box::new(range)
+        };
+        entries
+            .take(count)
+            .map(|(id, fields)| (*id, fields.clone()))
+            .collect()
+    }
+
+    /// Removes the oldest entries as `trim` says, returns how many there were.
+    fn trim(&mut self, trim: StreamTrim) -> usize {
+        let limit = match (trim.approximate, trim.limit) {
+            (true, None) => Some(DEFAULT_TRIM_LIMIT),
+            (_, Some(0)) => None,
+            (_, limit) => limit,
+        };
+        let mut removed = 0;
+        while !self.nodes.is_empty() {
+            let ids = self.node_ids(0);
+            let remove_node = match trim.strategy {
+                TrimStrategy::MaxLen(max_len) => self.len() - ids.len() >= max_len,
+                TrimStrategy::MinId(min_id) => ids.last().map_or(true, |last| *last < min_id),
+            };
+            if remove_node {
+                if limit.map_or(false, |limit| removed + ids.len() > limit) {
+                    break;
+                }
+                for id in &ids {
+                    self.entries.remove(id);
+                }
+                removed += ids.len();
+                self.nodes.pop_front();
+                continue;
+            }
+            if trim.approximate {
+                break;
+            }
+            // Exact trimming goes on within the node.
+            for id in ids {
+                let done = match trim.strategy {
+                    TrimStrategy::MaxLen(max_len) => self.len() <= max_len,
+                    TrimStrategy::MinId(min_id) => id >= min_id,
+                };
+                if done {
+                    break;
+                }
+                self.entries.remove(&id);
+                removed += 1;
+            }
+            break;
+        }
+        removed
+    }
+}
+
+impl Db {
+    /// Appends an entry to the stream at `key`, creating the stream unless
+    /// `no_mkstream` is set, then trims it if asked to. Returns the ID of the entry,
+    /// `None` if the stream doesn't exist and wasn't created.
+    pub fn xadd(
+        &mut self,
+        key: Bytes,
+        id: XAddId,
+        fields: StreamFields,
+        no_mkstream: bool,
+        trim: Option<StreamTrim>,
+    ) -> Result<Option<StreamId>> {
+        let now = self.now();
+        let id = match self.stream(&key)? {
+            Some(stream) => stream.next_id(id, now)?,
+            None if no_mkstream => return Ok(None),
+            None => {
+                let id = Stream::default().next_id(id, now)?;
+                let entry = Entry {
+                    value: Value::Stream(Stream::default()),
+                    expires_at: None,
+                };
+                self.insert_entry(key.clone(), entry);
+                id
+            }
+        };
+        let stream = self.stream_mut(&key)?.unwrap();
+        stream.insert(id, fields);
+        if let Some(trim) = trim {
+            stream.trim(trim);
+        }
+        // Wakes up the clients blocked reading the stream.
+        self.signal_ready(&key);
+        Ok(Some(id))
+    }
+
+    /// Reads from each stream the entries after the ID given along with it, at most
+    /// `count` per stream. Streams without such entries are left out of the reply.
+    pub fn xread(
+        &mut self,
+        streams: &[(Bytes, StreamId)],
+        count: Option<usize>,
+    ) -> Result<Vec<StreamRead>> {
+        // All the keys are type checked before anything is read.
+        for (key, _) in streams {
+            self.stream(key)?;
+        }
+        let mut reads = Vec::new();
+        for (key, id) in streams {
+            let (stream, start) = match (self.stream(key)?, id.next()) {
+                (Some(stream), Some(start)) => (stream, start),
+                _ => continue,
+            };
+            let entries = stream.range(start, StreamId::MAX, false, count);
+            if !entries.is_empty() {
+                let entries = entries
+                    .into_iter()
+                    .map(|(id, fields)| (id, Some(fields)))
+                    .collect();
+                reads.push((key.clone(), entries));
+            }
+        }
+        Ok(reads)
+    }
+
+    /// The ID of the last entry ever added to the stream at `key`, which `$` stands
+    /// for. `0-0` if the stream doesn't exist.
+    pub fn xlast_id(&mut self, key: &[u8]) -> Result<StreamId> {
+        Ok(self
+            .stream(key)?
+            .map_or(StreamId::MIN, |stream| stream.last_id))
+    }
+
+    /// The entries of the stream at `key` from `start` to `end` included, from the
+    /// last one if `rev` is set, at most `count` of them.
+    pub fn xrange(
+        &mut self,
+        key: &[u8],
+        start: StreamId,
+        end: StreamId,
+        rev: bool,
+        count: Option<usize>,
+    ) -> Result<Vec<(StreamId, StreamFields)>> {
+        Ok(self
+            .stream(key)?
+            .map_or_else(Vec::new, |stream| stream.range(start, end, rev, count)))
+    }
+
+    pub fn xlen(&mut self, key: &[u8]) -> Result<usize> {
+        Ok(self.stream(key)?.map_or(0, |stream| stream.len()))
+    }
+
+    /// Removes the entries `ids` from the stream at `key`, returns how many existed.
+    /// Unlike other collections, streams are kept around once empty.
+    pub fn xdel(&mut self, key: &[u8], ids: &[StreamId]) -> Result<usize> {
+        Ok(self.stream_mut(key)?.map_or(0, |stream| {
+            ids.iter().filter(|id| stream.remove(**id)).count()
+        }))
+    }
+
+    /// Trims the stream at `key`, returns the number of entries removed.
+    pub fn xtrim(&mut self, key: &[u8], trim: StreamTrim) -> Result<usize> {
+        Ok(self.stream_mut(key)?.map_or(0, |stream| stream.trim(trim)))
+    }
+
+    /// The stream at `key`, fails with `WRONGTYPE` if the key holds another type.
+    fn stream(&mut self, key: &[u8]) -> Result<Option<&Stream>> {
+        self.live_entry(key)
+            .map(|entry| entry.value.as_stream())
+            .transpose()
+    }
+
+    /// Mutable version of `stream`.
+    fn stream_mut(&mut self, key: &[u8]) -> Result<Option<&mut Stream>> {
+        self.live_entry_mut(key)
+            .map(|entry| entry.value.as_stream_mut())
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use super::{StreamId, StreamTrim, TrimStrategy, XAddId};
+    use crate::{
+        db::Db,
+        utils::{Clock, FakeClock},
+    };
+
+    fn id(ms: u64, seq: u64) -> StreamId {
+        StreamId { ms, seq }
+    }
+
+    fn fields() -> Vec<(Bytes, Bytes)> {
+        vec![("f".into(), "v".into())]
+    }
+
+    fn ids(db: &mut Db) -> Vec<StreamId> {
+        let entries = db.xrange(b"s", StreamId::MIN, StreamId::MAX, false, None);
+        entries.unwrap().into_iter().map(|(id, _)| id).collect()
+    }
+
+    #[test]
+    fn ids_only_go_up() {
+        let clock = FakeClock::new(1000);
+        let mut db = Db::with_clock(Box::new(clock.clone()));
+        let mut add = |id| db.xadd("s".into(), id, fields(), false, None);
+
+        assert_eq!(add(XAddId::Auto).unwrap(), Some(id(clock.now(), 0)));
+        assert_eq!(add(XAddId::Auto).unwrap(), Some(id(clock.now(), 1)));
+        assert!(add(XAddId::Explicit(id(5, 0))).is_err());
+        assert!(add(XAddId::AutoSeq(5)).is_err());
+        let later = clock.now() + 10;
+        assert_eq!(add(XAddId::AutoSeq(later)).unwrap(), Some(id(later, 0)));
+        // A clock behind the last ID doesn't make IDs go back.
+        assert_eq!(add(XAddId::Auto).unwrap(), Some(id(later, 1)));
+
+        assert!(db
+            .xadd(
+                "t".into(),
+                XAddId::Explicit(StreamId::MIN),
+                fields(),
+                false,
+                None
+            )
+            .is_err());
+        assert_eq!(db.type_of(b"t"), "none");
+        assert_eq!(
+            db.xadd("t".into(), XAddId::AutoSeq(0), fields(), false, None)
+                .unwrap(),
+            Some(id(0, 1))
+        );
+        assert_eq!(
+            db.xadd("u".into(), XAddId::Auto, fields(), true, None)
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn approximate_trimming_removes_whole_nodes() {
+        let mut db = Db::new();
+        for ms in 1..=250 {
+            let added = db.xadd(
+                "s".into(),
+                XAddId::Explicit(id(ms, 0)),
+                fields(),
+                false,
+                None,
+            );
+            added.unwrap();
+        }
+        let max_len = |max_len, approximate| StreamTrim {
+            strategy: TrimStrategy::MaxLen(max_len),
+            approximate,
+            limit: None,
+        };
+        assert_eq!(db.xtrim(b"s", max_len(180, true)).unwrap(), 0);
+        assert_eq!(db.xtrim(b"s", max_len(140, true)).unwrap(), 100);
+        assert_eq!(db.xtrim(b"s", max_len(120, false)).unwrap(), 30);
+        assert_eq!(ids(&mut db)[0], id(131, 0));
+
+        assert_eq!(
+            db.xdel(b"s", &[id(131, 0), id(131, 0), id(1, 0)]).unwrap(),
+            1
+        );
+        let min_id = StreamTrim {
+            strategy: TrimStrategy::MinId(id(240, 0)),
+            approximate: true,
+            limit: Some(0),
+        };
+        assert_eq!(db.xtrim(b"s", min_id).unwrap(), 69);
+        assert_eq!(db.xlen(b"s").unwrap(), 50);
+        assert_eq!(db.xtrim(b"s", max_len(0, false)).unwrap(), 50);
+        assert_eq!(db.type_of(b"s"), "stream");
+    }
+
+    #[test]
+    fn reads_start_after_the_given_ids() {
+        let mut db = Db::new();
+        for (key, ms) in [("s", 1), ("s", 2), ("t", 3)] {
+            let added = db.xadd(
+                key.into(),
+                XAddId::Explicit(id(ms, 0)),
+                fields(),
+                false,
+                None,
+            );
+            added.unwrap();
+        }
+        let read_ids = |db: &mut Db, streams: &[(Bytes, StreamId)], count| {
+            let reads = db.xread(streams, count).unwrap();
+            reads
+                .into_iter()
+                .map(|(key, entries)| (key, entries.into_iter().map(|(id, _)| id).collect()))
+                .collect::<Vec<(Bytes, Vec<StreamId>)>>()
+        };
+
+        let streams = [("s".into(), id(1, 0)), ("t".into(), id(3, 0))];
+        assert_eq!(
+            read_ids(&mut db, &streams, None),
+            vec![("s".into(), vec![id(2, 0)])]
+        );
+        let streams = [
+            ("missing".into(), StreamId::MIN),
+            ("s".into(), StreamId::MIN),
+        ];
+        assert_eq!(
+            read_ids(&mut db, &streams, Some(1)),
+            vec![("s".into(), vec![id(1, 0)])]
+        );
+        assert_eq!(db.xlast_id(b"t").unwrap(), id(3, 0));
+        assert_eq!(db.xlast_id(b"missing").unwrap(), StreamId::MIN);
+    }
+}
@@ -0,0 +1,367 @@
+use bytes::{Bytes, BytesMut};
+
+use crate::{
+    error::{Error, Result},
+    utils::{format_float, parse_float, parse_integer},
+};
+
+use super::{deadline_passed, Db, Entry, Value};
+
+/// Largest string a command may build, Redis' default `proto-max-bulk-len` of 512MB.
+const MAX_STRING_LENGTH: usize = 512 * 1024 * 1024;
+
+/// Whether a `SET` should go through depending on the key already existing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetCondition {
+    Always,
+    /// `NX`, only set the key if it doesn't exist.
+    IfMissing,
+    /// `XX`, only set the key if it already exists.
+    IfExists,
+}
+
+/// What a `SET` does to the key's time to live.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetExpiry {
+    /// Drop any previous time to live.
+    Persist,
+    /// `KEEPTTL`, retain the previous time to live.
+    Keep,
+    /// `EX`/`PX`, expire this many milliseconds from now.
+    In(u64),
+    /// `EXAT`/`PXAT`, expire at this unix time in milliseconds.
+    At(u64),
+}
+
+#[derive(Debug)]
+pub struct SetOutcome {
+    /// Whether the value was written, `false` if the `SetCondition` wasn't met.
+    pub written: bool,
+    /// The string held before the `SET`, only read when asked for.
+    pub previous: Option<Bytes>,
+}
+
+impl Db {
+    /// Sets `key` to the string `data` whatever it held before.
+    /// With `get` the previous value is returned, in which case it must be a string.
+    pub fn set(
+        &mut self,
+        key: Bytes,
+        data: Bytes,
+        condition: SetCondition,
+        expiry: SetExpiry,
+        get: bool,
+    ) -> Result<SetOutcome> {
+        let now = self.now();
+        // Only what's needed is copied out of the previous entry, not its whole value.
+        let previous_entry = self.live_entry(&key);
+        let exists = previous_entry.is_some();
+        let previous_expires_at = previous_entry.and_then(|entry| entry.expires_at);
+        let previous = match previous_entry {
+            Some(entry) if get => Some(entry.value.as_string()?.clone()),
+            _ => None,
+        };
+
+        let allowed = match condition {
+            SetCondition::Always => true,
+            SetCondition::IfMissing => !exists,
+            SetCondition::IfExists => exists,
+        };
+        if !allowed {
+            return Ok(SetOutcome {
+                written: false,
+                previous,
+            });
+        }
+
+        let expires_at = match expiry {
+            SetExpiry::Persist => None,
+            SetExpiry::Keep => previous_expires_at,
+            SetExpiry::In(ttl) => Some(now.saturating_add(ttl)),
+            SetExpiry::At(deadline) => Some(deadline),
+        };
+        // A deadline in the past leaves a key that's already expired.
+        if matches!(expires_at, Some(deadline) if deadline_passed(deadline, now)) {
+            self.remove_entry(&key);
+        } else {
+            let value = Value::String(data);
+            self.insert_entry(key, Entry { value, expires_at });
+        }
+
+        Ok(SetOutcome {
+            written: true,
+            previous,
+        })
+    }
+
+    pub fn get(&mut self, key: &[u8]) -> Result<Option<Bytes>> {
+        Ok(self.string(key)?.cloned())
+    }
+
+    /// Adds `delta` to the integer stored at `key`, a missing key counting as `0`.
+    /// The key keeps its time to live. Returns the new value.
+    pub fn incr_by(&mut self, key: &[u8], delta: i64) -> Result<i64> {
+        let current = match self.string(key)? {
+            Some(data) => parse_integer(data).ok_or(Error::NotAnInteger)?,
+            None => 0,
+        };
+        let updated = current.checked_add(delta).ok_or(Error::Overflow)?;
+        self.update_string(key, updated.to_string().into());
+        Ok(updated)
+    }
+
+    /// Adds `delta` to the float stored at `key`, a missing key counting as `0`.
+    /// The key keeps its time to live. Returns the new value as stored.
+    pub fn incr_by_float(&mut self, key: &[u8], delta: f64) -> Result<Bytes> {
+        let current = match self.string(key)? {
+            Some(data) => parse_float(data).ok_or(Error::NotAFloat)?,
+            None => 0.0,
+        };
+        let updated = current + delta;
+        if !updated.is_finite() {
+            return Err(Error::Msg(
+                "increment would produce NaN or Infinity".to_string(),
+            ));
+        }
+        let updated: Bytes = format_float(updated).into();
+        self.update_string(key, updated.clone());
+        Ok(updated)
+    }
+
+    /// Appends `value` to the string at `key`, creating it if needed.
+    /// Returns the length of the string after the append.
+    pub fn append(&mut self, key: &[u8], value: &[u8]) -> Result<usize> {
+        let mut data = match self.string(key)? {
+            Some(data) => BytesMut::from(&data[..]),
+            None => BytesMut::new(),
+        };
+        data.extend_from_slice(value);
+        let length = data.len();
+        self.update_string(key, data.freeze());
+        Ok(length)
+    }
+
+    pub fn strlen(&mut self, key: &[u8]) -> Result<usize> {
+        Ok(self.string(key)?.map_or(0, |data| data.len()))
+    }
+
+    /// The substring between the inclusive offsets `start` and `end`, negative offsets
+    /// counting from the end of the string.
+    pub fn getrange(&mut self, key: &[u8], start: i64, end: i64) -> Result<Bytes> {
+        let data = match self.string(key)? {
+            Some(data) => data.clone(),
+            None => return Ok(Bytes::new()),
+        };
+        let length = data.len() as i64;
+        if start < 0 && end < 0 && start > end {
+            return Ok(Bytes::new());
+        }
+        let start = if start < 0 { length + start } else { start }.max(0);
+        let end = if end < 0 { length + end } else { end }
+            .max(0)
+            .min(length - 1);
+        if length == 0 || start > end {
+            return Ok(Bytes::new());
+        }
+        Ok(data.slice(start as usize..=end as usize))
+    }
+
+    /// Overwrites the string at `key` with `value` from `offset` on, padding it with
+    /// zero bytes if it's too short. Returns the length of the string afterwards.
+    pub fn setrange(&mut self, key: &[u8], offset: usize, value: &[u8]) -> Result<usize> {
+        // An empty value doesn't create the key nor pad it.
+        if value.is_empty() {
+            return Ok(self.string(key)?.map_or(0, |data| data.len()));
+        }
+        if offset.saturating_add(value.len()) > MAX_STRING_LENGTH {
+            return Err(Error::OutOfRange(
+                "string exceeds maximum allowed size (proto-max-bulk-len)".to_string(),
+            ));
+        }
+
+        self.with_string_mut(key, |data| {
+            if data.len() < offset + value.len() {
+                data.resize(offset + value.len(), 0);
+            }
+            data[offset..offset + value.len()].copy_from_slice(value);
+            Ok(data.len())
+        })
+    }
+
+    /// Deletes `key` and returns the string it held.
+    pub fn getdel(&mut self, key: &[u8]) -> Result<Option<Bytes>> {
+        let data = self.string(key)?.cloned();
+        if data.is_some() {
+            self.remove_entry(key);
+        }
+        Ok(data)
+    }
+
+    /// Returns the string at `key` and changes its time to live according to `expiry`.
+    pub fn getex(&mut self, key: &[u8], expiry: SetExpiry) -> Result<Option<Bytes>> {
+        let data = match self.string(key)? {
+            Some(data) => data.clone(),
+            None => return Ok(None),
+        };
+        let expires_at = match expiry {
+            SetExpiry::Keep => return Ok(Some(data)),
+            SetExpiry::Persist => None,
+            SetExpiry::In(ttl) => Some(self.now().saturating_add(ttl)),
+            SetExpiry::At(deadline) => Some(deadline),
+        };
+        self.set_expiry(key, expires_at);
+        Ok(Some(data))
+    }
+
+    /// The strings at each of `keys`, `None` for the missing ones and the ones holding
+    /// another type.
+    pub fn mget(&mut self, keys: &[Bytes]) -> Vec<Option<Bytes>> {
+        keys.iter()
+            .map(|key| self.get(key).ok().flatten())
+            .collect()
+    }
+
+    /// Sets all the key value `pairs` clearing their time to live, a key given more
+    /// than once ends up with its last value. With `only_if_none_exist` nothing is set
+    /// if any of the keys exists. Returns whether the keys were set.
+    pub fn mset(&mut self, pairs: Vec<(Bytes, Bytes)>, only_if_none_exist: bool) -> bool {
+        if only_if_none_exist && pairs.iter().any(|(key, _)| self.live_entry(key).is_some()) {
+            return false;
+        }
+        for (key, data) in pairs {
+            self.insert_entry(
+                key,
+                Entry {
+                    value: Value::String(data),
+                    expires_at: None,
+                },
+            );
+        }
+        true
+    }
+
+    /// The string at `key`, fails with `WRONGTYPE` if the key holds another type.
+    pub(super) fn string(&mut self, key: &[u8]) -> Result<Option<&Bytes>> {
+        self.live_entry(key)
+            .map(|entry| entry.value.as_string())
+            .transpose()
+    }
+
+    /// Replaces the string at `key` keeping its time to live, or creates the key.
+    pub(super) fn update_string(&mut self, key: &[u8], data: Bytes) {
+        match self.live_entry_mut(key) {
+            Some(entry) => entry.value = Value::String(data),
+            None => self.insert_entry(
+                Bytes::copy_from_slice(key),
+                Entry {
+                    value: Value::String(data),
+                    expires_at: None,
+                },
+            ),
+        }
+    }
+
+    /// Lets `change` edit the string at `key` in place, keeping its time to live or
+    /// creating the key. The string is only copied if its buffer is shared.
+    pub(super) fn with_string_mut<T>(
+        &mut self,
+        key: &[u8],
+        change: impl FnOnce(&mut Vec<u8>) -> Result<T>,
+    ) -> Result<T> {
+        let data = match self.live_entry_mut(key) {
+            Some(entry) => entry.value.as_string_mut()?,
+            None => {
+                self.update_string(key, Bytes::new());
+                self.live_entry_mut(key).unwrap().value.as_string_mut()?
+            }
+        };
+        let mut buffer = Vec::from(std::mem::take(data));
+        let result = change(&mut buffer);
+        *data = buffer.into();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::{Db, SetCondition, SetExpiry};
+
+    #[test]
+    fn counters() {
+        let mut db = Db::new();
+        assert_eq!(db.incr_by(b"n", 5).unwrap(), 5);
+        assert_eq!(db.incr_by(b"n", -7).unwrap(), -2);
+
+        db.set(
+            "n".into(),
+            "9223372036854775806".into(),
+            SetCondition::Always,
+            SetExpiry::In(60_000),
+            false,
+        )
+        .unwrap();
+        assert_eq!(db.incr_by(b"n", 1).unwrap(), i64::MAX);
+        assert!(db.incr_by(b"n", 1).is_err());
+        assert!(db.deadline(b"n").unwrap().is_some());
+
+        db.set(
+            "n".into(),
+            " 1".into(),
+            SetCondition::Always,
+            SetExpiry::Persist,
+            false,
+        )
+        .unwrap();
+        assert!(db.incr_by(b"n", 1).is_err());
+    }
+
+    #[test]
+    fn ranges() {
+        let mut db = Db::new();
+        db.set(
+            "s".into(),
+            "This is a string".into(),
+            SetCondition::Always,
+            SetExpiry::Persist,
+            false,
+        )
+        .unwrap();
+        assert_eq!(db.getrange(b"s", 0, 3).unwrap(), "This");
+        assert_eq!(db.getrange(b"s", -3, -1).unwrap(), "ing");
+        assert_eq!(db.getrange(b"s", 0, -1).unwrap(), "This is a string");
+        assert_eq!(db.getrange(b"s", 10, 100).unwrap(), "string");
+        assert_eq!(db.getrange(b"s", -1, -5).unwrap(), "");
+        assert_eq!(db.getrange(b"missing", 0, -1).unwrap(), "");
+
+        assert_eq!(db.setrange(b"s", 10, b"Redis").unwrap(), 16);
+        assert_eq!(db.get(b"s").unwrap().unwrap(), "This is a Redisg");
+        assert_eq!(db.setrange(b"padded", 3, b"x").unwrap(), 4);
+        assert_eq!(db.get(b"padded").unwrap().unwrap(), &b"\0\0\0x"[..]);
+        assert_eq!(db.setrange(b"empty", 3, b"").unwrap(), 0);
+        assert_eq!(db.get(b"empty").unwrap(), None);
+    }
+
+    #[test]
+    fn msetnx_sets_all_or_nothing() {
+        let mut db = Db::new();
+        assert!(db.mset(vec![("a".into(), "1".into())], true));
+        assert!(!db.mset(
+            vec![("b".into(), "2".into()), ("a".into(), "3".into())],
+            true
+        ));
+        assert_eq!(
+            db.mget(&["a".into(), "b".into()]),
+            vec![Some("1".into()), None]
+        );
+    }
+
+    #[test]
+    fn float_counters() {
+        let mut db = Db::new();
+        assert_eq!(db.incr_by_float(b"f", 10.5).unwrap(), "10.5");
+        assert_eq!(db.incr_by_float(b"f", 0.1).unwrap(), "10.6");
+        assert_eq!(db.incr_by_float(b"f", -5.0e3).unwrap(), "-4989.4");
+        assert!(db.incr_by_float(b"f", f64::INFINITY).is_err());
+        assert_eq!(db.get(b"f").unwrap().unwrap(), "-4989.4");
+    }
+}
@@ -0,0 +1,214 @@
+use std::collections::VecDeque;
+
+use bytes::Bytes;
+
+use crate::{
+    error::{Error, Result},
+    utils::parse_integer,
+};
+
+use super::{hash::Hash, set::Set, stream::Stream, zset::SortedSet};
+
+/// Strings up to this length are reported with Redis' `embstr` encoding.
+const EMBSTR_SIZE_LIMIT: usize = 44;
+/// Lists with more elements than this are reported with the `quicklist` encoding,
+/// Redis' default `list-max-listpack-size` of 128.
+const LISTPACK_MAX_ENTRIES: usize = 128;
+/// Lists holding an element longer than this are reported with the `quicklist` encoding.
+const LISTPACK_MAX_VALUE: usize = 64;
+/// Sets with more members than this are reported with the `hashtable` encoding,
+/// Redis' default `set-max-listpack-entries` of 128.
+const SET_LISTPACK_MAX_ENTRIES: usize = 128;
+/// Sorted sets with more members than this are reported with the `skiplist` encoding,
+/// Redis' default `zset-max-listpack-entries` of 128.
+const ZSET_LISTPACK_MAX_ENTRIES: usize = 128;
+/// Hashes with more fields than this are reported with the `hashtable` encoding,
+/// Redis' default `hash-max-listpack-entries` of 128.
+const HASH_LISTPACK_MAX_ENTRIES: usize = 128;
+
+/// The value held by a key, one variant per Redis data type.
+#[derive(Debug, Clone)]
+pub enum Value {
+    String(Bytes),
+    List(VecDeque<Bytes>),
+    Hash(Hash),
+    Set(Set),
+    SortedSet(SortedSet),
+    Stream(Stream),
+}
+
+impl Value {
+    /// The name of the type as reported by `TYPE`.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "string",
+            Value::List(_) => "list",
+            Value::Hash(_) => "hash",
+            Value::Set(_) => "set",
+            Value::SortedSet(_) => "zset",
+            Value::Stream(_) => "stream",
+        }
+    }
+
+    /// The encoding Redis would store the value with, as reported by `OBJECT ENCODING`.
+    pub fn encoding(&self) -> &'static str {
+        match self {
+            Value::String(data) if parse_integer(data).is_some() => "int",
+            Value::String(data) if data.len() <= EMBSTR_SIZE_LIMIT => "embstr",
+            Value::String(_) => "raw",
+            Value::List(list)
+                if list.len() <= LISTPACK_MAX_ENTRIES
+                    && list
+                        .iter()
+                        .all(|element| element.len() <= LISTPACK_MAX_VALUE) =>
+            {
+                "listpack"
+            }
+            Value::List(_) => "quicklist",
+            Value::Hash(hash)
+                if hash.len() <= HASH_LISTPACK_MAX_ENTRIES
+                    && hash.iter().all(|(field, value)| {
+                        field.len() <= LISTPACK_MAX_VALUE && value.len() <= LISTPACK_MAX_VALUE
+                    }) =>
+            {
+                "listpack"
+            }
+            Value::Hash(_) => "hashtable",
+            Value::Set(Set::IntSet(_)) => "intset",
+            Value::Set(Set::HashTable(members))
+                if members.len() <= SET_LISTPACK_MAX_ENTRIES
+                    && members
+                        .iter()
+                        .all(|member| member.len() <= LISTPACK_MAX_VALUE) =>
+            {
+                "listpack"
+            }
+            Value::Set(Set::HashTable(_)) => "hashtable",
+            Value::SortedSet(zset)
+                if zset.len() <= ZSET_LISTPACK_MAX_ENTRIES
+                    && zset
+                        .members()
+                        .all(|member| member.len() <= LISTPACK_MAX_VALUE) =>
+            {
+                "listpack"
+            }
+            Value::SortedSet(_) => "skiplist",
+            Value::Stream(_) => "stream",
+        }
+    }
+
+    /// The value as a string, fails with `WRONGTYPE` for any other type.
+    pub fn as_string(&self) -> Result<&Bytes> {
+        match self {
+            Value::String(data) => Ok(data),
+            _ => Err(Error::WrongType),
+        }
+    }
+
+    pub fn as_string_mut(&mut self) -> Result<&mut Bytes> {
+        match self {
+            Value::String(data) => Ok(data),
+            _ => Err(Error::WrongType),
+        }
+    }
+
+    /// The value as a list, fails with `WRONGTYPE` for any other type.
+    pub fn as_list(&self) -> Result<&VecDeque<Bytes>> {
+        match self {
+            Value::List(list) => Ok(list),
+            _ => Err(Error::WrongType),
+        }
+    }
+
+    pub fn as_list_mut(&mut self) -> Result<&mut VecDeque<Bytes>> {
+        match self {
+            Value::List(list) => Ok(list),
+            _ => Err(Error::WrongType),
+        }
+    }
+
+    /// The value as a hash, fails with `WRONGTYPE` for any other type.
+    pub fn as_hash(&self) -> Result<&Hash> {
+        match self {
+            Value::Hash(hash) => Ok(hash),
+            _ => Err(Error::WrongType),
+        }
+    }
+
+    pub fn as_hash_mut(&mut self) -> Result<&mut Hash> {
+        match self {
+            Value::Hash(hash) => Ok(hash),
+            _ => Err(Error::WrongType),
+        }
+    }
+
+    /// The value as a set, fails with `WRONGTYPE` for any other type.
+    pub fn as_set(&self) -> Result<&Set> {
+        match self {
+            Value::Set(set) => Ok(set),
+            _ => Err(Error::WrongType),
+        }
+    }
+
+    pub fn as_set_mut(&mut self) -> Result<&mut Set> {
+        match self {
+            Value::Set(set) => Ok(set),
+            _ => Err(Error::WrongType),
+        }
+    }
+
+    /// The value as a sorted set, fails with `WRONGTYPE` for any other type.
+    pub fn as_zset(&self) -> Result<&SortedSet> {
+        match self {
+            Value::SortedSet(zset) => Ok(zset),
+            _ => Err(Error::WrongType),
+        }
+    }
+
+    pub fn as_zset_mut(&mut self) -> Result<&mut SortedSet> {
+        match self {
+            Value::SortedSet(zset) => Ok(zset),
+            _ => Err(Error::WrongType),
+        }
+    }
+
+    /// The value as a stream, fails with `WRONGTYPE` for any other type.
+    pub fn as_stream(&self) -> Result<&Stream> {
+        match self {
+            Value::Stream(stream) => Ok(stream),
+            _ => Err(Error::WrongType),
+        }
+    }
+
+    pub fn as_stream_mut(&mut self) -> Result<&mut Stream> {
+        match self {
+            Value::Stream(stream) => Ok(stream),
+            _ => Err(Error::WrongType),
+        }
+    }
+
+    /// Whether the value is an empty collection, which Redis never keeps around.
+    pub fn is_empty(&self) -> bool {
+        match self {
+            // Streams are kept around once empty, they remember their last ID.
+            Value::String(_) | Value::Stream(_) => false,
+            Value::List(list) => list.is_empty(),
+            Value::Hash(hash) => hash.is_empty(),
+            Value::Set(set) => set.is_empty(),
+            Value::SortedSet(zset) => zset.is_empty(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Value;
+
+    #[test]
+    fn string_encodings() {
+        assert_eq!(Value::String("-42".into()).encoding(), "int");
+        assert_eq!(Value::String("042".into()).encoding(), "embstr");
+        assert_eq!(Value::String(vec![b'a'; 44].into()).encoding(), "embstr");
+        assert_eq!(Value::String(vec![b'a'; 45].into()).encoding(), "raw");
+    }
+}
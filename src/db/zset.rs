@@ -0,0 +1,729 @@
+use std::collections::{hash_map, HashMap};
+
+use bytes::Bytes;
+
+use crate::error::{Error, Result};
+
+use super::{list::index_range, skiplist::SkipList, Db, Entry, SetOp, Value};
+
+/// The members of a sorted set: their scores for O(1) lookups and a skiplist ordering
+/// them for rank and range queries, like Redis' `zset`.
+#[derive(Debug, Clone, Default)]
+pub struct SortedSet {
+    scores: HashMap<Bytes, f64>,
+    list: SkipList,
+}
+
+/// A member of a sorted set along with its score.
+pub type ScoredMember = (Bytes, f64);
+
+/// The `NX`, `XX`, `GT`, `LT`, `CH` and `INCR` flags of `ZADD`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ZAddFlags {
+    /// Only add new members.
+    pub nx: bool,
+    /// Only update existing members.
+    pub xx: bool,
+    /// Only update members whose score increases.
+    pub gt: bool,
+    /// Only update members whose score decreases.
+    pub lt: bool,
+    /// Count the members updated along with the ones added.
+    pub ch: bool,
+    /// Increment the score of the member rather than set it.
+    pub incr: bool,
+}
+
+/// One end of a score range.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScoreBound {
+    pub score: f64,
+    pub exclusive: bool,
+}
+
+/// One end of a lexicographical range.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LexBound {
+    /// `-`, before every member.
+    Min,
+    /// `+`, after every member.
+    Max,
+    Inclusive(Bytes),
+    Exclusive(Bytes),
+}
+
+/// The members of a sorted set a command works on.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ZRange {
+    /// Inclusive 0-based ranks, negative ones counting from the end.
+    Rank {
+        start: i64,
+        stop: i64,
+    },
+    Score {
+        min: ScoreBound,
+        max: ScoreBound,
+    },
+    /// Only meaningful if all the members have the same score.
+    Lex {
+        min: LexBound,
+        max: LexBound,
+    },
+}
+
+/// How `ZUNIONSTORE` and `ZINTERSTORE` combine the scores of a member found in
+/// several sets.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ZAggregate {
+    #[default]
+    Sum,
+    Min,
+    Max,
+}
+
+impl ZAggregate {
+    fn apply(self, a: f64, b: f64) -> f64 {
+        match self {
+            // Like Redis, `inf + -inf` sums up to zero rather than NaN.
+            ZAggregate::Sum => zero_if_nan(a + b),
+            ZAggregate::Min => a.min(b),
+            ZAggregate::Max => a.max(b),
+        }
+    }
+}
+
+fn zero_if_nan(score: f64) -> f64 {
+    if score.is_nan() {
+        0.0
+    } else {
+        score
+    }
+}
+
+/// What adding a member did to a sorted set.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct AddOutcome {
+    score: f64,
+    added: bool,
+    updated: bool,
+}
+
+impl ScoreBound {
+    fn is_above(&self, score: f64) -> bool {
+        score < self.score || (self.exclusive && score == self.score)
+    }
+
+    fn is_below(&self, score: f64) -> bool {
+        score > self.score || (self.exclusive && score == self.score)
+    }
+}
+
+impl LexBound {
+    fn is_above(&self, member: &[u8]) -> bool {
+        match self {
+            LexBound::Min => false,
+            LexBound::Max => true,
+            LexBound::Inclusive(bound) => member < &bound[..],
+            LexBound::Exclusive(bound) => member <= &bound[..],
+        }
+    }
+
+    fn is_below(&self, member: &[u8]) -> bool {
+        match self {
+            LexBound::Min => true,
+            LexBound::Max => false,
+            LexBound::Inclusive(bound) => member > &bound[..],
+            LexBound::Exclusive(bound) => member >= &bound[..],
+        }
+    }
+}
+
+impl SortedSet {
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scores.is_empty()
+    }
+
+    pub fn members(&self) -> impl Iterator<Item = &Bytes> {
+        self.scores.keys()
+    }
+
+    pub fn score(&self, member: &[u8]) -> Option<f64> {
+        self.scores.get(member).copied()
+    }
+
+    /// The 0-based rank of `member`, counting from the highest score if `rev` is set.
+    pub fn rank(&self, member: &[u8], rev: bool) -> Option<usize> {
+        let rank = self.list.rank(self.score(member)?, member)?;
+        Some(if rev { self.len() - 1 - rank } else { rank })
+    }
+
+    /// Sets the score of `member` or adds to it with `INCR`, as far as `flags` allow.
+    /// Returns `None` if `flags` didn't allow it.
+    fn add(&mut self, member: Bytes, score: f64, flags: ZAddFlags) -> Result<Option<AddOutcome>> {
+        let current = self.score(&member);
+        let score = match current {
+            Some(current) if flags.incr => current + score,
+            _ => score,
+        };
+        if score.is_nan() {
+            return Err(Error::Msg(
+                "resulting score is not a number (NaN)".to_string(),
+            ));
+        }
+
+        let outcome = match current {
+            None if flags.xx => return Ok(None),
+            None => {
+                self.list.insert(score, member.clone());
+                self.scores.insert(member, score);
+                AddOutcome {
+                    score,
+                    added: true,
+                    updated: false,
+                }
+            }
+            Some(_) if flags.nx => return Ok(None),
+            Some(current) if (flags.gt && score <= current) || (flags.lt && score >= current) => {
+                return Ok(None);
+            }
+            Some(current) => {
+                let updated = score != current;
+                if updated {
+                    self.list.remove(current, &member);
+                    self.list.insert(score, member.clone());
+                    self.scores.insert(member, score);
+                }
+                AddOutcome {
+                    score,
+                    added: false,
+                    updated,
+                }
+            }
+        };
+        Ok(Some(outcome))
+    }
+
+    /// Removes `member`, returns whether it existed.
+    fn remove(&mut self, member: &[u8]) -> bool {
+        match self.scores.remove(member) {
+            Some(score) => self.list.remove(score, member),
+            None => false,
+        }
+    }
+
+    /// The inclusive ranks of the first and last members in `range`, ranks of ranges
+    /// counting from the highest score if `rev` is set.
+    fn rank_span(&self, range: &ZRange, rev: bool) -> Option<(usize, usize)> {
+        match range {
+            ZRange::Rank { start, stop } => {
+                let (start, stop) = index_range(*start, *stop, self.len())?;
+                if rev {
+                    Some((self.len() - 1 - stop, self.len() - 1 - start))
+                } else {
+                    Some((start, stop))
+                }
+            }
+            ZRange::Score { min, max } => {
+                let first = self.list.first_not(|score, _| min.is_above(score))?;
+                let last = self.list.last_within(|score, _| !max.is_below(score))?;
+                self.node_ranks(first, last)
+            }
+            ZRange::Lex { min, max } => {
+                let first = self.list.first_not(|_, member| min.is_above(member))?;
+                let last = self.list.last_within(|_, member| !max.is_below(member))?;
+                self.node_ranks(first, last)
+            }
+        }
+    }
+
+    /// The ranks of the nodes `first` and `last`, `None` if they cross.
+    fn node_ranks(&self, first: usize, last: usize) -> Option<(usize, usize)> {
+        let rank = |node| {
+            let score = self.list.score(node);
+            self.list.rank(score, self.list.member(node)).unwrap()
+        };
+        let (first, last) = (rank(first), rank(last));
+        (first <= last).then_some((first, last))
+    }
+
+    /// The members in `range` along with their scores, from the highest score if `rev`
+    /// is set, skipping `offset` of them and returning at most `count`.
+    pub fn range(
+        &self,
+        range: &ZRange,
+        rev: bool,
+        offset: usize,
+        count: Option<usize>,
+    ) -> Vec<ScoredMember> {
+        let (first, last) = match self.rank_span(range, rev) {
+            Some(span) => span,
+            None => return Vec::new(),
+        };
+        let len = last - first + 1;
+        if offset >= len {
+            return Vec::new();
+        }
+        let count = count.unwrap_or(len).min(len - offset);
+        let mut node = if rev {
+            self.list.by_rank(last - offset)
+        } else {
+            self.list.by_rank(first + offset)
+        };
+        let mut members = Vec::with_capacity(count);
+        while let Some(current) = node.filter(|_| members.len() < count) {
+            members.push((self.list.member(current).clone(), self.list.score(current)));
+            node = if rev {
+                self.list.previous(current)
+            } else {
+                self.list.next(current)
+            };
+        }
+        members
+    }
+
+    /// Removes the member with the lowest score, or the highest if `rev` is set.
+    fn pop(&mut self, rev: bool) -> Option<ScoredMember> {
+        let node = if rev {
+            self.list.last()
+        } else {
+            self.list.first()
+        }?;
+        let (member, score) = (self.list.member(node).clone(), self.list.score(node));
+        self.remove(&member);
+        Some((member, score))
+    }
+
+    /// The number of members in `range`.
+    pub fn count(&self, range: &ZRange) -> usize {
+        self.rank_span(range, false)
+            .map_or(0, |(first, last)| last - first + 1)
+    }
+}
+
+impl Db {
+    /// Sets the scores of the members of the sorted set at `key` as far as `flags`
+    /// allow, creating the set if needed. Returns the number of members added, along
+    /// with the ones whose score changed with `CH`.
+    pub fn zadd(
+        &mut self,
+        key: Bytes,
+        pairs: Vec<(f64, Bytes)>,
+        flags: ZAddFlags,
+    ) -> Result<usize> {
+        let zset = match self.zset_or_insert(key.clone(), flags)? {
+            Some(zset) => zset,
+            None => return Ok(0),
+        };
+        let mut counted = 0;
+        for (score, member) in pairs {
+            if let Some(outcome) = zset.add(member, score, flags)? {
+                counted += (outcome.added || (flags.ch && outcome.updated)) as usize;
+            }
+        }
+        self.remove_if_empty(&key);
+        Ok(counted)
+    }
+
+    /// Adds `delta` to the score of `member`, a missing member counting as `0`, as far
+    /// as `flags` allow. Returns the new score, `None` if `flags` didn't allow it.
+    pub fn zincrby(
+        &mut self,
+        key: Bytes,
+        member: Bytes,
+        delta: f64,
+        flags: ZAddFlags,
+    ) -> Result<Option<f64>> {
+        let flags = ZAddFlags {
+            incr: true,
+            ..flags
+        };
+        let zset = match self.zset_or_insert(key.clone(), flags)? {
+            Some(zset) => zset,
+            None => return Ok(None),
+        };
+        let outcome = zset.add(member, delta, flags);
+        self.remove_if_empty(&key);
+        Ok(outcome?.map(|outcome| outcome.score))
+    }
+
+    /// Removes `members` from the sorted set at `key`, deleting the key once it's
+    /// empty. Returns the number of members removed.
+    pub fn zrem(&mut self, key: &[u8], members: &[Bytes]) -> Result<usize> {
+        let zset = match self.zset_mut(key)? {
+            Some(zset) => zset,
+            None => return Ok(0),
+        };
+        let removed = members.iter().filter(|member| zset.remove(member)).count();
+        self.remove_if_empty(key);
+        Ok(removed)
+    }
+
+    pub fn zscore(&mut self, key: &[u8], member: &[u8]) -> Result<Option<f64>> {
+        Ok(self.zset(key)?.and_then(|zset| zset.score(member)))
+    }
+
+    pub fn zmscore(&mut self, key: &[u8], members: &[Bytes]) -> Result<Vec<Option<f64>>> {
+        let zset = self.zset(key)?;
+        Ok(members
+            .iter()
+            .map(|member| zset.and_then(|zset| zset.score(member)))
+            .collect())
+    }
+
+    pub fn zcard(&mut self, key: &[u8]) -> Result<usize> {
+        Ok(self.zset(key)?.map_or(0, |zset| zset.len()))
+    }
+
+    /// The 0-based rank of `member` along with its score, ranks counting from the
+    /// highest score if `rev` is set.
+    pub fn zrank(&mut self, key: &[u8], member: &[u8], rev: bool) -> Result<Option<(usize, f64)>> {
+        Ok(self.zset(key)?.and_then(|zset| {
+            let rank = zset.rank(member, rev)?;
+            Some((rank, zset.score(member)?))
+        }))
+    }
+
+    /// The members in `range` along with their scores, see `SortedSet::range`.
+    pub fn zrange(
+        &mut self,
+        key: &[u8],
+        range: &ZRange,
+        rev: bool,
+        offset: usize,
+        count: Option<usize>,
+    ) -> Result<Vec<ScoredMember>> {
+        Ok(self
+            .zset(key)?
+            .map_or_else(Vec::new, |zset| zset.range(range, rev, offset, count)))
+    }
+
+    /// The number of members in `range`.
+    pub fn zcount(&mut self, key: &[u8], range: &ZRange) -> Result<usize> {
+        Ok(self.zset(key)?.map_or(0, |zset| zset.count(range)))
+    }
+
+    /// Removes the members in `range`, returns how many there were.
+    pub fn zremrange(&mut self, key: &[u8], range: &ZRange) -> Result<usize> {
+        let zset = match self.zset_mut(key)? {
+            Some(zset) => zset,
+            None => return Ok(0),
+        };
+        let removed = zset.range(range, false, 0, None);
+        for (member, _) in &removed {
+            zset.remove(member);
+        }
+        self.remove_if_empty(key);
+        Ok(removed.len())
+    }
+
+    /// Pops up to `count` members with the lowest scores, or the highest if `rev` is
+    /// set, deleting the key once it's empty. Returns `None` if the key doesn't exist.
+    pub fn zpop(
+        &mut self,
+        key: &[u8],
+        rev: bool,
+        count: usize,
+    ) -> Result<Option<Vec<ScoredMember>>> {
+        let zset = match self.zset_mut(key)? {
+            Some(zset) => zset,
+            None => return Ok(None),
+        };
+        let popped = (0..count).map_while(|_| zset.pop(rev)).collect();
+        self.remove_if_empty(key);
+        Ok(Some(popped))
+    }
+
+    /// Pops up to `count` members from the first existing sorted set among `keys`,
+    /// returned along with its key.
+    pub fn zmpop(
+        &mut self,
+        keys: &[Bytes],
+        rev: bool,
+        count: usize,
+    ) -> Result<Option<(Bytes, Vec<ScoredMember>)>> {
+        for key in keys {
+            if let Some(popped) = self.zpop(key, rev, count)? {
+                return Ok(Some((key.clone(), popped)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// The union, intersection or difference of the sorted sets at `keys`, ordered by
+    /// score. Plain sets count as sorted sets whose members all score `1`. Scores are
+    /// multiplied by the matching `weights` and combined with `aggregate`, both of
+    /// which a difference ignores.
+    pub fn zset_op(
+        &mut self,
+        op: SetOp,
+        keys: &[Bytes],
+        weights: Option<&[f64]>,
+        aggregate: ZAggregate,
+    ) -> Result<Vec<ScoredMember>> {
+        let mut sources = Vec::with_capacity(keys.len());
+        for (i, key) in keys.iter().enumerate() {
+            let weight = weights.map_or(1.0, |weights| weights[i]);
+            sources.push(self.zset_source(key, weight)?);
+        }
+        let mut sources = sources.into_iter();
+        let mut scores = sources.next().flatten().unwrap_or_default();
+        for source in sources {
+            match op {
+                SetOp::Union => {
+                    for (member, score) in source.into_iter().flatten() {
+                        match scores.entry(member) {
+                            hash_map::Entry::Occupied(mut entry) => {
+                                let combined = aggregate.apply(*entry.get(), score);
+                                entry.insert(combined);
+                            }
+                            hash_map::Entry::Vacant(entry) => {
+                                entry.insert(score);
+                            }
+                        }
+                    }
+                }
+                SetOp::Inter => {
+                    let source = source.unwrap_or_default();
+                    scores.retain(|member, score| match source.get(member) {
+                        Some(&other) => {
+                            *score = aggregate.apply(*score, other);
+                            true
+                        }
+                        None => false,
+                    });
+                }
+                SetOp::Diff => {
+                    for member in source.iter().flat_map(|source| source.keys()) {
+                        scores.remove(member);
+                    }
+                }
+            }
+        }
+
+        let mut members: Vec<ScoredMember> = scores.into_iter().collect();
+        members.sort_by(|(a, a_score), (b, b_score)| {
+            a_score.total_cmp(b_score).then_with(|| a.cmp(b))
+        });
+        Ok(members)
+    }
+
+    /// Stores the result of `zset_op` at `destination`, replacing any value there.
+    /// Returns the number of members stored.
+    pub fn zset_op_store(
+        &mut self,
+        op: SetOp,
+        destination: Bytes,
+        keys: &[Bytes],
+        weights: Option<&[f64]>,
+        aggregate: ZAggregate,
+    ) -> Result<usize> {
+        let members = self.zset_op(op, keys, weights, aggregate)?;
+        let len = members.len();
+        self.remove_entry(&destination);
+        if len > 0 {
+            let pairs = members
+                .into_iter()
+                .map(|(member, score)| (score, member))
+                .collect();
+            self.zadd(destination, pairs, ZAddFlags::default())?;
+        }
+        Ok(len)
+    }
+
+    /// The scores of the sorted set or set at `key` multiplied by `weight`, `None` if
+    /// the key doesn't exist. Fails with `WRONGTYPE` if it holds another type.
+    fn zset_source(&mut self, key: &[u8], weight: f64) -> Result<Option<HashMap<Bytes, f64>>> {
+        let entry = match self.live_entry(key) {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        let weighted = |score: f64| zero_if_nan(score * weight);
+        let scores = match &entry.value {
+            Value::SortedSet(zset) => zset
+                .scores
+                .iter()
+                .map(|(member, &score)| (member.clone(), weighted(score)))
+                .collect(),
+            Value::Set(set) => set
+                .members()
+                .into_iter()
+                .map(|member| (member, weighted(1.0)))
+                .collect(),
+            _ => return Err(Error::WrongType),
+        };
+        Ok(Some(scores))
+    }
+
+    /// The sorted set at `key`, created empty if the key doesn't exist unless `flags`
+    /// only allow updating members.
+    fn zset_or_insert(&mut self, key: Bytes, flags: ZAddFlags) -> Result<Option<&mut SortedSet>> {
+        if self.zset(&key)?.is_none() {
+            if flags.xx {
+                return Ok(None);
+            }
+            let entry = Entry {
+                value: Value::SortedSet(SortedSet::default()),
+                expires_at: None,
+            };
+            self.insert_entry(key.clone(), entry);
+        }
+        self.zset_mut(&key)
+    }
+
+    /// The sorted set at `key`, fails with `WRONGTYPE` if the key holds another type.
+    fn zset(&mut self, key: &[u8]) -> Result<Option<&SortedSet>> {
+        self.live_entry(key)
+            .map(|entry| entry.value.as_zset())
+            .transpose()
+    }
+
+    /// Mutable version of `zset`.
+    fn zset_mut(&mut self, key: &[u8]) -> Result<Option<&mut SortedSet>> {
+        self.live_entry_mut(key)
+            .map(|entry| entry.value.as_zset_mut())
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use super::{LexBound, ScoreBound, ZAddFlags, ZAggregate, ZRange};
+    use crate::db::{Db, SetOp};
+
+    fn pairs(pairs: &[(f64, &'static str)]) -> Vec<(f64, Bytes)> {
+        pairs
+            .iter()
+            .map(|(score, member)| (*score, Bytes::from(*member)))
+            .collect()
+    }
+
+    fn members(range: Vec<(Bytes, f64)>) -> Vec<Bytes> {
+        range.into_iter().map(|(member, _)| member).collect()
+    }
+
+    fn bound(score: f64, exclusive: bool) -> ScoreBound {
+        ScoreBound { score, exclusive }
+    }
+
+    #[test]
+    fn zadd_flags() {
+        let mut db = Db::new();
+        let flags = ZAddFlags::default();
+        let added = db.zadd("z".into(), pairs(&[(1.0, "a"), (2.0, "b")]), flags);
+        assert_eq!(added.unwrap(), 2);
+
+        let gt_ch = ZAddFlags {
+            gt: true,
+            ch: true,
+            ..Default::default()
+        };
+        let changed = db.zadd(
+            "z".into(),
+            pairs(&[(0.0, "a"), (3.0, "b"), (1.0, "c")]),
+            gt_ch,
+        );
+        assert_eq!(changed.unwrap(), 2);
+        assert_eq!(db.zscore(b"z", b"a").unwrap(), Some(1.0));
+        assert_eq!(db.zscore(b"z", b"b").unwrap(), Some(3.0));
+
+        let xx = ZAddFlags {
+            xx: true,
+            ..Default::default()
+        };
+        assert_eq!(db.zincrby("z".into(), "d".into(), 1.0, xx).unwrap(), None);
+        assert_eq!(db.zincrby("y".into(), "d".into(), 1.0, xx).unwrap(), None);
+        assert_eq!(db.type_of(b"y"), "none");
+        let incremented = db.zincrby("z".into(), "a".into(), -3.5, flags);
+        assert_eq!(incremented.unwrap(), Some(-2.5));
+        assert_eq!(db.zrank(b"z", b"a", false).unwrap(), Some((0, -2.5)));
+        assert_eq!(db.zrank(b"z", b"a", true).unwrap(), Some((2, -2.5)));
+    }
+
+    #[test]
+    fn ranges() {
+        let mut db = Db::new();
+        let scores = pairs(&[(1.0, "a"), (2.0, "b"), (2.0, "c"), (3.0, "d"), (4.0, "e")]);
+        db.zadd("z".into(), scores, ZAddFlags::default()).unwrap();
+
+        let by_rank = ZRange::Rank { start: 1, stop: -2 };
+        let range = db.zrange(b"z", &by_rank, false, 0, None).unwrap();
+        assert_eq!(members(range), vec!["b", "c", "d"]);
+        let range = db.zrange(b"z", &by_rank, true, 0, None).unwrap();
+        assert_eq!(members(range), vec!["d", "c", "b"]);
+
+        let by_score = ZRange::Score {
+            min: bound(1.0, true),
+            max: bound(f64::INFINITY, false),
+        };
+        let range = db.zrange(b"z", &by_score, false, 1, Some(2)).unwrap();
+        assert_eq!(members(range), vec!["c", "d"]);
+        let range = db.zrange(b"z", &by_score, true, 0, Some(1)).unwrap();
+        assert_eq!(members(range), vec!["e"]);
+        assert_eq!(db.zcount(b"z", &by_score).unwrap(), 4);
+        let empty = ZRange::Score {
+            min: bound(2.0, true),
+            max: bound(3.0, true),
+        };
+        assert_eq!(db.zcount(b"z", &empty).unwrap(), 0);
+
+        let by_lex = ZRange::Lex {
+            min: LexBound::Exclusive("b".into()),
+            max: LexBound::Inclusive("c".into()),
+        };
+        let range = db.zrange(b"z", &by_lex, false, 0, None).unwrap();
+        assert_eq!(members(range), vec!["c"]);
+
+        assert_eq!(db.zremrange(b"z", &by_rank).unwrap(), 3);
+        assert_eq!(db.zcard(b"z").unwrap(), 2);
+        let all = ZRange::Lex {
+            min: LexBound::Min,
+            max: LexBound::Max,
+        };
+        assert_eq!(db.zremrange(b"z", &all).unwrap(), 2);
+        assert_eq!(db.type_of(b"z"), "none");
+    }
+
+    #[test]
+    fn aggregates_and_pops() {
+        let mut db = Db::new();
+        let flags = ZAddFlags::default();
+        db.zadd("a".into(), pairs(&[(1.0, "x"), (2.0, "y")]), flags)
+            .unwrap();
+        db.zadd(
+            "b".into(),
+            pairs(&[(10.0, "y"), (f64::INFINITY, "z")]),
+            flags,
+        )
+        .unwrap();
+        db.sadd("s".into(), vec!["y".into()]).unwrap();
+        let keys: Vec<Bytes> = vec!["a".into(), "b".into(), "s".into()];
+
+        let union = db.zset_op(SetOp::Union, &keys[..2], Some(&[2.0, 0.5]), ZAggregate::Sum);
+        assert_eq!(
+            union.unwrap(),
+            pairs(&[(2.0, "x"), (9.0, "y"), (f64::INFINITY, "z")])
+                .into_iter()
+                .map(|(score, member)| (member, score))
+                .collect::<Vec<_>>()
+        );
+        let inter = db.zset_op(SetOp::Inter, &keys, None, ZAggregate::Max);
+        assert_eq!(inter.unwrap(), vec![(Bytes::from("y"), 10.0)]);
+        let diff = db.zset_op(SetOp::Diff, &keys, None, ZAggregate::Sum);
+        assert_eq!(diff.unwrap(), vec![(Bytes::from("x"), 1.0)]);
+
+        let stored = db.zset_op_store(SetOp::Inter, "a".into(), &keys, None, ZAggregate::Sum);
+        assert_eq!(stored.unwrap(), 1);
+        assert_eq!(db.zscore(b"a", b"y").unwrap(), Some(13.0));
+
+        let popped = db.zmpop(&["none".into(), "b".into()], true, 5).unwrap();
+        assert_eq!(members(popped.unwrap().1), vec!["z", "y"]);
+        assert_eq!(db.type_of(b"b"), "none");
+        assert_eq!(db.zpop(b"b", false, 1).unwrap(), None);
+    }
+}
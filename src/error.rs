@@ -1,14 +1,59 @@
+use crate::resp::RESP;
+
 #[derive(Debug)]
 pub enum Error {
     IncompleteRequestData,
     InvalidRequestData,
     ConnectionClosed,
+    /// The command name isn't one we know of.
+    UnknownCommand(String),
+    /// The command was called with the wrong number of arguments, holds the command name.
+    WrongArity(String),
+    /// The command's arguments don't follow its grammar.
+    Syntax,
+    /// The command was run against a key holding a value of another type.
+    WrongType,
+    NotAnInteger,
+    NotAFloat,
+    /// Holds the full message, e.g. "index out of range".
+    OutOfRange(String),
+    /// Holds the name of the command the expire time was given to.
+    InvalidExpireTime(String),
+    InvalidStreamId,
+    Overflow,
+    NoSuchKey,
+    NoScript,
+    NoAuth,
+    /// The consumer group a stream command names doesn't exist, holds the full message
+    /// since it varies between commands.
+    NoGroup(String),
+    BusyGroup,
+    /// A HyperLogLog command was run against a string that isn't one.
+    InvalidHll,
+    /// The registers of a HyperLogLog don't add up.
+    CorruptedHll,
     Msg(String), // If possible make static
     Io(std::io::Error),
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+impl Error {
+    /// The error code Redis prefixes the error message with, clients match on it
+    /// to tell errors apart.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::WrongType | Error::InvalidHll => "WRONGTYPE",
+            Error::NoScript => "NOSCRIPT",
+            Error::NoAuth => "NOAUTH",
+            Error::NoGroup(_) => "NOGROUP",
+            Error::BusyGroup => "BUSYGROUP",
+            Error::CorruptedHll => "INVALIDOBJ",
+            _ => "ERR",
+        }
+    }
+}
+
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         use Error::*;
@@ -16,8 +61,56 @@ impl std::fmt::Display for Error {
             IncompleteRequestData => write!(f, "Parse Error: Incomplete request data"),
             InvalidRequestData => write!(f, "Parse Error: Invalid request data"),
             ConnectionClosed => write!(f, "Network Error: Peer closed connection"),
-            Msg(err) => write!(f, "General Error: {err}"),
+            UnknownCommand(name) => write!(f, "unknown command '{name}'"),
+            WrongArity(name) => write!(f, "wrong number of arguments for '{name}' command"),
+            Syntax => write!(f, "syntax error"),
+            WrongType => write!(f, "Operation against a key holding the wrong kind of value"),
+            NotAnInteger => write!(f, "value is not an integer or out of range"),
+            NotAFloat => write!(f, "value is not a valid float"),
+            OutOfRange(msg) => write!(f, "{msg}"),
+            InvalidExpireTime(name) => write!(f, "invalid expire time in '{name}' command"),
+            InvalidStreamId => write!(f, "Invalid stream ID specified as stream command argument"),
+            Overflow => write!(f, "increment or decrement would overflow"),
+            NoSuchKey => write!(f, "no such key"),
+            NoScript => write!(f, "No matching script. Please use EVAL."),
+            NoAuth => write!(f, "Authentication required."),
+            NoGroup(msg) => write!(f, "{msg}"),
+            BusyGroup => write!(f, "Consumer Group name already exists"),
+            InvalidHll => write!(f, "Key is not a valid HyperLogLog string value."),
+            CorruptedHll => write!(f, "Corrupted HLL object detected"),
+            Msg(err) => write!(f, "{err}"),
             Io(err) => write!(f, "IO Error: {err}"),
         }
     }
 }
+
+impl From<Error> for RESP {
+    /// Renders the error as a RESP error reply prefixed with its Redis error code.
+    fn from(err: Error) -> RESP {
+        RESP::Error(format!("{} {err}", err.code()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Error;
+    use crate::resp::RESP;
+
+    #[test]
+    fn errors_render_with_their_redis_prefix() {
+        assert_eq!(
+            RESP::from(Error::WrongType),
+            RESP::Error(
+                "WRONGTYPE Operation against a key holding the wrong kind of value".to_string()
+            )
+        );
+        assert_eq!(
+            RESP::from(Error::WrongArity("set".to_string())),
+            RESP::Error("ERR wrong number of arguments for 'set' command".to_string())
+        );
+        assert_eq!(
+            RESP::from(Error::NoScript),
+            RESP::Error("NOSCRIPT No matching script. Please use EVAL.".to_string())
+        );
+    }
+}
@@ -11,6 +11,8 @@ pub enum RESP {
     Integer(i64),
     Error(String),
     Null,
+    /// The null array `*-1`, which some commands reply instead of `Null`.
+    NullArray,
     Bulk(Bytes),
     Array(Vec<RESP>),
 }
@@ -24,14 +26,14 @@ impl RESP {
         // println!("SRC: {:?}", bytes_to_string(src));
         match src[0] {
             b'+' => {
-                let crlf_start_index = find_crlf(&src).ok_or(Error::IncompleteRequestData)?;
+                let crlf_start_index = find_crlf(src).ok_or(Error::IncompleteRequestData)?;
                 let body: String = bytes_to_string(&src[1..crlf_start_index]);
                 let resp = RESP::Simple(body);
 
                 Ok((resp, crlf_start_index + 2))
             }
             b':' => {
-                let crlf_start_index = find_crlf(&src).ok_or(Error::IncompleteRequestData)?;
+                let crlf_start_index = find_crlf(src).ok_or(Error::IncompleteRequestData)?;
                 let int: i64 = bytes_to_string(&src[1..crlf_start_index])
                     .parse::<i64>()
                     .map_err(|_| Error::Msg("Invalid integer literal".to_string()))?;
@@ -40,46 +42,61 @@ impl RESP {
                 Ok((resp, crlf_start_index + 2))
             }
             b'-' => {
-                let crlf_start_index = find_crlf(&src).ok_or(Error::IncompleteRequestData)?;
+                let crlf_start_index = find_crlf(src).ok_or(Error::IncompleteRequestData)?;
                 let body: String = bytes_to_string(&src[1..crlf_start_index]);
                 let resp = RESP::Error(body);
 
-                return Ok((resp, crlf_start_index + 2));
+                Ok((resp, crlf_start_index + 2))
             }
             b'$' => {
-                let length_bytes_crlf = find_crlf(&src).ok_or(Error::IncompleteRequestData)?;
+                let length_bytes_crlf = find_crlf(src).ok_or(Error::IncompleteRequestData)?;
 
                 let length: i64 = bytes_to_string(&src[1..length_bytes_crlf])
                     .parse()
                     .map_err(|_| Error::Msg("Invalid bulk string length.".to_string()))?;
 
-                if length == -1 && src.len() < 6 && src[length_bytes_crlf + 1] != b'\r' {
-                    return Ok((RESP::Null, 5));
+                if length == -1 {
+                    // A `$-1` followed by an empty line is an empty string, otherwise it's a null.
+                    return if src[length_bytes_crlf + 2..].starts_with(&RESP::CRLF) {
+                        Ok((RESP::Bulk(Bytes::new()), length_bytes_crlf + 4))
+                    } else {
+                        Ok((RESP::Null, length_bytes_crlf + 2))
+                    };
                 }
+                let length = usize::try_from(length).map_err(|_| Error::InvalidRequestData)?;
 
+                // The body is read using the length rather than by looking for a CRLF
+                // as it may contain any byte.
                 let body_start = length_bytes_crlf + 2;
-                let body_end_crlf = body_start
-                    + find_crlf(&src[body_start..]).ok_or(Error::IncompleteRequestData)?;
-                let string_body: Bytes = Bytes::copy_from_slice(&src[body_start..body_end_crlf]);
-
-                if length != -1 && string_body.len() != length as usize {
+                let body_end = body_start + length;
+                if src.len() < body_end + 2 {
+                    return Err(Error::IncompleteRequestData);
+                }
+                if src[body_end..body_end + 2] != RESP::CRLF {
                     return Err(Error::Msg(
                         "Bulk string length doesn't match body length".to_string(),
                     ));
                 }
-                return Ok((RESP::Bulk(string_body), body_end_crlf + 2));
+                let string_body: Bytes = Bytes::copy_from_slice(&src[body_start..body_end]);
+
+                Ok((RESP::Bulk(string_body), body_end + 2))
             }
             b'*' => {
-                let length_bytes_crlf = find_crlf(&src).ok_or(Error::IncompleteRequestData)?;
+                let length_bytes_crlf = find_crlf(src).ok_or(Error::IncompleteRequestData)?;
 
                 let length: i64 = bytes_to_string(&src[1..length_bytes_crlf])
                     .parse()
                     .map_err(|_| Error::Msg("Invalid bulk string length.".to_string()))?;
 
-                if length == -1 && src.len() < 6 && src[length_bytes_crlf + 1] != b'\r' {
-                    return Ok((RESP::Null, 5));
+                if length == -1 {
+                    // A `*-1` followed by an empty line is an empty array, otherwise it's a null.
+                    return if src[length_bytes_crlf + 2..].starts_with(&RESP::CRLF) {
+                        Ok((RESP::Array(Vec::new()), length_bytes_crlf + 4))
+                    } else {
+                        Ok((RESP::NullArray, length_bytes_crlf + 2))
+                    };
                 }
-                let length = length as usize;
+                let length = usize::try_from(length).map_err(|_| Error::InvalidRequestData)?;
 
                 let mut elements: Vec<RESP> = Vec::new();
                 let mut cursor = length_bytes_crlf + 2;
@@ -89,34 +106,23 @@ impl RESP {
                     cursor += offset;
                 }
 
-                return Ok((RESP::Array(elements), cursor));
-            }
-            _ => {
-                return Err(Error::InvalidRequestData);
+                Ok((RESP::Array(elements), cursor))
             }
+            _ => Err(Error::InvalidRequestData),
         }
     }
 
     pub fn is_string(&self) -> bool {
-        use RESP::*;
-        match self {
-            Simple(_) | Bulk(_) => true,
-            _ => false,
-        }
+        matches!(self, RESP::Simple(_) | RESP::Bulk(_))
     }
 }
 
 fn find_crlf(bytes: &[u8]) -> Option<usize> {
-    for i in 0..bytes.len() - 1 {
-        if bytes[i] == b'\r' && bytes[i + 1] == b'\n' {
-            return Some(i);
-        }
-    }
-    None
+    bytes.windows(2).position(|window| window == RESP::CRLF)
 }
 
 fn bytes_to_string(bytes: &[u8]) -> String {
-    bytes.into_iter().map(|byte| *byte as char).collect()
+    bytes.iter().map(|byte| *byte as char).collect()
 }
 
 #[cfg(test)]
@@ -140,7 +146,7 @@ mod tests {
     fn null_arrays_works() {
         let src = b"*-1\r\n";
         let res = RESP::parse(src);
-        assert_eq!((RESP::Null, 5), res.unwrap())
+        assert_eq!((RESP::NullArray, 5), res.unwrap())
     }
 
     #[test]
@@ -151,6 +157,17 @@ mod tests {
         assert_eq!((RESP::Array(Vec::new()), 7), res.unwrap());
     }
 
+    #[test]
+    fn bulk_strings_are_binary_safe() {
+        let src = b"$6\r\na\r\n\xff\x00b\r\n";
+        let res = RESP::parse(src);
+        assert_eq!(
+            (RESP::Bulk((&b"a\r\n\xff\x00b"[..]).into()), 12),
+            res.unwrap()
+        );
+        assert!(RESP::parse(&src[..10]).is_err());
+    }
+
     #[test]
     fn can_parse() {
         let src =
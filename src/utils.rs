@@ -1,8 +1,236 @@
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    time::{SystemTime, UNIX_EPOCH},
+};
 
-pub fn now() -> u128 {
+/// The current unix time in milliseconds.
+pub fn now() -> u64 {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap()
-        .as_millis()
+        .as_millis() as u64
+}
+
+/// Source of the current time, swapped out in tests to control time.
+pub trait Clock: Send {
+    /// The current unix time in milliseconds.
+    fn now(&self) -> u64;
+}
+
+/// Reads the time from the system, see `now`.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        now()
+    }
+}
+
+/// A clock that only moves when told to, clones share the same time.
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct FakeClock(std::sync::Arc<std::sync::atomic::AtomicU64>);
+
+#[cfg(test)]
+impl FakeClock {
+    pub fn new(now: u64) -> FakeClock {
+        FakeClock(std::sync::Arc::new(now.into()))
+    }
+
+    pub fn advance(&self, millis: u64) {
+        self.0
+            .fetch_add(millis, std::sync::atomic::Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+impl Clock for FakeClock {
+    fn now(&self) -> u64 {
+        self.0.load(std::sync::atomic::Ordering::SeqCst)
+    }
+}
+
+/// A random number below `bound`, good enough to pick random elements but not for
+/// anything that has to be unpredictable. `bound` can't be `0`.
+pub fn random_below(bound: usize) -> usize {
+    // Every `RandomState` hashes with new keys, so even hashing nothing gives a new
+    // number every time.
+    let random = RandomState::new().build_hasher().finish();
+    (random % bound as u64) as usize
+}
+
+/// Parses an integer the way Redis does: no whitespace, no `+` sign and no leading
+/// zeros, so only values that print back the same are accepted.
+pub fn parse_integer(bytes: &[u8]) -> Option<i64> {
+    let digits = bytes.strip_prefix(b"-").unwrap_or(bytes);
+    let is_canonical = match digits {
+        [] => false,
+        [b'0'] => digits.len() == bytes.len(),
+        [first, rest @ ..] => {
+            (b'1'..=b'9').contains(first) && rest.iter().all(|byte| byte.is_ascii_digit())
+        }
+    };
+    if !is_canonical {
+        return None;
+    }
+    std::str::from_utf8(bytes).ok()?.parse().ok()
+}
+
+/// Parses a float the way Redis does, rejecting surrounding whitespace and NaN.
+pub fn parse_float(bytes: &[u8]) -> Option<f64> {
+    let float: f64 = std::str::from_utf8(bytes).ok()?.parse().ok()?;
+    if float.is_nan() {
+        None
+    } else {
+        Some(float)
+    }
+}
+
+/// Formats a float in its shortest form that parses back to the same value,
+/// without exponent, like Redis replies with doubles.
+pub fn format_float(float: f64) -> String {
+    if float.is_infinite() {
+        if float > 0.0 { "inf" } else { "-inf" }.to_string()
+    } else {
+        format!("{float}")
+    }
+}
+
+/// Formats a double reply like Redis does: in its shortest form that parses back to
+/// the same value, switching to an exponent like `%.17g` for very large or small ones.
+pub fn format_double(double: f64) -> String {
+    if double.is_infinite() {
+        return format_float(double);
+    }
+    let scientific = format!("{double:e}");
+    let (mantissa, exponent) = scientific.split_once('e').unwrap();
+    let exponent: i32 = exponent.parse().unwrap();
+    if (-4..17).contains(&exponent) {
+        format_float(double)
+    } else {
+        let sign = if exponent < 0 { '-' } else { '+' };
+        format!("{mantissa}e{sign}{:02}", exponent.abs())
+    }
+}
+
+/// Matches `string` against the glob-style `pattern` like Redis' `KEYS` does.
+/// Supports `*`, `?`, `[...]` character classes with `^` negation and `a-z` ranges,
+/// and `\` escapes.
+pub fn glob_match(pattern: &[u8], string: &[u8]) -> bool {
+    let mut p = 0;
+    let mut s = 0;
+    while p < pattern.len() {
+        if pattern[p] == b'*' {
+            while pattern.get(p + 1) == Some(&b'*') {
+                p += 1;
+            }
+            if p + 1 == pattern.len() {
+                return true;
+            }
+            return (s..string.len()).any(|start| glob_match(&pattern[p + 1..], &string[start..]));
+        }
+        let c = match string.get(s) {
+            Some(c) => *c,
+            None => return false,
+        };
+        match pattern[p] {
+            b'?' => {}
+            b'[' => {
+                p += 1;
+                let negate = pattern.get(p) == Some(&b'^');
+                if negate {
+                    p += 1;
+                }
+                let mut matched = false;
+                loop {
+                    match pattern.get(p) {
+                        // An unterminated class ends with the pattern.
+                        None => {
+                            p -= 1;
+                            break;
+                        }
+                        Some(b'\\') if p + 1 < pattern.len() => {
+                            p += 1;
+                            matched |= pattern[p] == c;
+                        }
+                        Some(b']') => break,
+                        Some(&start) if p + 2 < pattern.len() && pattern[p + 1] == b'-' => {
+                            let end = pattern[p + 2];
+                            matched |= (start.min(end)..=start.max(end)).contains(&c);
+                            p += 2;
+                        }
+                        Some(&other) => matched |= other == c,
+                    }
+                    p += 1;
+                }
+                if matched == negate {
+                    return false;
+                }
+            }
+            b'\\' if p + 1 < pattern.len() => {
+                p += 1;
+                if pattern[p] != c {
+                    return false;
+                }
+            }
+            other => {
+                if other != c {
+                    return false;
+                }
+            }
+        }
+        p += 1;
+        s += 1;
+    }
+    s == string.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_double, format_float, glob_match, parse_integer};
+
+    #[test]
+    fn integers_must_be_canonical() {
+        assert_eq!(parse_integer(b"0"), Some(0));
+        assert_eq!(parse_integer(b"-12"), Some(-12));
+        assert_eq!(parse_integer(b"9223372036854775807"), Some(i64::MAX));
+        assert_eq!(parse_integer(b"9223372036854775808"), None);
+        for invalid in [&b""[..], b"-", b"-0", b"01", b"+1", b" 1", b"1 ", b"1.0"] {
+            assert_eq!(parse_integer(invalid), None);
+        }
+    }
+
+    #[test]
+    fn floats_use_their_shortest_form() {
+        assert_eq!(format_float(10.5 + 0.1), "10.6");
+        assert_eq!(format_float(5.0e3), "5000");
+        assert_eq!(format_float(-0.25), "-0.25");
+        assert_eq!(format_float(f64::INFINITY), "inf");
+    }
+
+    #[test]
+    fn doubles_switch_to_an_exponent() {
+        assert_eq!(format_double(1.5), "1.5");
+        assert_eq!(format_double(0.0001), "0.0001");
+        assert_eq!(format_double(0.00001), "1e-05");
+        assert_eq!(format_double(1.25e20), "1.25e+20");
+        assert_eq!(format_double(f64::NEG_INFINITY), "-inf");
+    }
+
+    #[test]
+    fn glob_patterns() {
+        assert!(glob_match(b"*", b""));
+        assert!(glob_match(b"h?llo", b"hello"));
+        assert!(glob_match(b"h*llo", b"heeeello"));
+        assert!(glob_match(b"h*llo", b"hllo"));
+        assert!(glob_match(b"h[ae]llo", b"hallo"));
+        assert!(!glob_match(b"h[^e]llo", b"hello"));
+        assert!(glob_match(b"h[a-b]llo", b"hbllo"));
+        assert!(glob_match(b"user:\\*", b"user:*"));
+        assert!(!glob_match(b"user:\\*", b"user:1"));
+        assert!(glob_match(b"*\xff", b"binary\xff"));
+        assert!(!glob_match(b"a?", b"a"));
+        assert!(!glob_match(b"a", b"ab"));
+    }
 }